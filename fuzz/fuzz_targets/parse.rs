@@ -0,0 +1,49 @@
+#![no_main]
+
+// cargo +nightly fuzz run parse
+use libfuzzer_sys::fuzz_target;
+use parser::{parse_cst, parse_program, parse_program_recovering, to_python_ast, unparse, Diagnostic};
+use serde_json::Value;
+use tokenizer::{tokenize_spanned, try_tokenize};
+
+// The ast of a program without lines and columns, which unparsing changes
+fn without_locations(mut value: Value) -> Value {
+    match &mut value {
+        Value::Object(object) => {
+            for key in ["lineno", "col_offset", "end_lineno", "end_col_offset"] {
+                object.remove(key);
+            }
+            for field in object.values_mut() {
+                *field = without_locations(field.take());
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(|v| *v = without_locations(v.take())),
+        _ => {}
+    }
+    value
+}
+
+fuzz_target!(|input: &str| {
+    let tokens = tokenize_spanned(input);
+    let result = parse_program(&tokens);
+    let (program, errors) = parse_program_recovering(&tokens);
+    assert_eq!(result.is_ok(), errors.is_empty());
+
+    for error in &errors {
+        let _ = Diagnostic::from(error).render("<input>", input, false);
+    }
+
+    if let Ok(cst) = parse_cst(input) {
+        assert_eq!(cst.to_string(), input);
+    }
+
+    // Whatever parses prints back to source that parses to the same ast, and
+    // that source prints back as itself
+    if let (Ok(parsed), Ok(_)) = (result, try_tokenize(input)) {
+        assert_eq!(parsed, program);
+        let source = unparse(&parsed);
+        let reparsed = parse_program(&tokenize_spanned(&source)).unwrap_or_else(|e| panic!("{}, unparsed as:\n{}", e, source));
+        assert_eq!(without_locations(to_python_ast(&reparsed)), without_locations(to_python_ast(&parsed)), "unparsed as:\n{}", source);
+        assert_eq!(unparse(&reparsed), source);
+    }
+});
@@ -0,0 +1,27 @@
+#![no_main]
+
+// cargo +nightly fuzz run tokenize
+use libfuzzer_sys::fuzz_target;
+use tokenizer::{tokenize_spanned, tokenize_with_trivia, try_tokenize};
+
+fuzz_target!(|input: &str| {
+    let tokens = tokenize_spanned(input);
+    let _ = try_tokenize(input);
+
+    // Comments and whitespace fill the gaps between tokens exactly
+    if let Ok(tokens) = tokenize_with_trivia(input) {
+        let mut end = 0;
+        for token in &tokens {
+            assert_eq!(token.span.start.offset, end, "gap or overlap before {:?}", token);
+            end = token.span.end.offset;
+        }
+        assert_eq!(end, input.len());
+    }
+
+    let mut end = 0;
+    for token in &tokens {
+        assert!(end <= token.span.start.offset && token.span.start.offset <= token.span.end.offset);
+        end = token.span.end.offset;
+    }
+    assert!(end <= input.len());
+});
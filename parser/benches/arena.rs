@@ -0,0 +1,53 @@
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use parser::{parse_program, parse_program_arena, Ast, Program};
+use tokenizer::tokenize_spanned;
+
+// Compares the boxed AST with the arena one on a large generated program:
+// building each from tokens, dropping it, and converting between them.
+// parse_program_arena parses each statement into boxes before moving it into
+// the arena, so only dropping is faster. Run with `cargo bench --bench arena`
+
+fn generated_program(functions: usize) -> String {
+    let mut source = String::new();
+    for i in 0..functions {
+        source += &format!(
+            "@cache\ndef f{i}(a, b: int = {i}) -> int:\n    x = a * (b + {i}) - f{i}(a, b) // 2\n    for item in x.y[a]:\n        total = item ** 2\n    match x:\n        case [1, *rest] | {{\"k\": _}}:\n            pass\n    return a + b + x + {i} + a * b\n\n",
+        );
+    }
+    source += &format!("total = 0{}\n", " + 1".repeat(10_000));
+    source
+}
+
+// The fastest of a few runs, which is the least disturbed by other work
+fn time<T>(runs: usize, mut setup: impl FnMut() -> T, mut run: impl FnMut(T)) -> Duration {
+    (0..runs).map(|_| {
+        let input = setup();
+        let start = Instant::now();
+        run(input);
+        start.elapsed()
+    }).min().unwrap_or_default()
+}
+
+fn main() {
+    let source = generated_program(5_000);
+    let tokens = tokenize_spanned(&source);
+    let nodes = parse_program_arena(&tokens).0.len();
+    println!("{} bytes, {} tokens, {} nodes", source.len(), tokens.len(), nodes);
+
+    let runs = 10;
+    let rows = [
+        ("parse and drop, boxed", time(runs, || (), |_| drop(black_box(parse_program(&tokens))))),
+        ("parse and drop, arena", time(runs, || (), |_| drop(black_box(parse_program_arena(&tokens))))),
+        ("drop, boxed", time(runs, || parse_program(&tokens).unwrap(), drop)),
+        ("drop, arena", time(runs, || parse_program_arena(&tokens).0, drop)),
+        ("boxed to arena", time(runs, || parse_program(&tokens).unwrap(), |program| drop(black_box(Ast::from(program))))),
+        ("arena to boxed", time(runs, || parse_program_arena(&tokens).0, |ast| drop(black_box(Program::from(ast))))),
+    ];
+
+    for (name, elapsed) in rows {
+        println!("{:<24} {:>10.2?}", name, elapsed);
+    }
+    println!("the arena is built from boxed statements, so only dropping it is faster");
+}
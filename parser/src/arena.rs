@@ -0,0 +1,696 @@
+use std::mem;
+use std::ops::Index;
+
+use tokenizer::{SpannedToken, Token};
+
+use crate as boxed;
+use crate::{check_statements, current_token, parse_statement_or_recover, take_expression, Alias, BinaryOperator, CompareOperator, Located, ParseError, Scope, Symbol, DEFAULT_NESTING_LIMIT};
+
+// The AST kept in flat arenas. Nodes refer to their children by index rather
+// than owning them through a Box, so a whole tree is a few vectors that are
+// cheap to drop. Every type mirrors the boxed type of the same name, and
+// From converts either way
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExprId(u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StmtId(u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PatternId(u32);
+
+#[derive(Debug)]
+pub enum Expression {
+    Number(i64),
+    String(String),
+    Variable(Symbol),
+    Boolean(bool),
+    None,
+    BinaryOp(ExprId, BinaryOperator, ExprId),
+    CompareOp(ExprId, CompareOperator, ExprId),
+    Compare(ExprId, Vec<(CompareOperator, ExprId)>),
+    Tuple(Vec<ExprId>),
+    Attribute(ExprId, Symbol),
+    Subscript(ExprId, ExprId),
+    Call(ExprId, Vec<ExprId>),
+    Await(ExprId),
+    Yield(Option<ExprId>),
+    YieldFrom(ExprId),
+    Error,
+}
+
+#[derive(Debug)]
+pub enum Stmt {
+    Error,
+    Assignment(Symbol, ExprId),
+    Expr(ExprId),
+    Pass,
+    Import(Vec<Located<Alias>>),
+    ImportFrom {
+        module: Option<Symbol>,
+        names: Vec<Located<Alias>>,
+        level: usize,
+    },
+    Raise {
+        exc: Option<ExprId>,
+        cause: Option<ExprId>,
+    },
+    Try {
+        body: Vec<StmtId>,
+        handlers: Vec<Located<ExceptHandler>>,
+        orelse: Vec<StmtId>,
+        finalbody: Vec<StmtId>,
+        star: bool,
+    },
+    With {
+        items: Vec<WithItem>,
+        body: Vec<StmtId>,
+        is_async: bool,
+    },
+    FunctionDef {
+        name: Symbol,
+        type_params: Vec<Located<TypeParam>>,
+        params: Vec<Located<Parameter>>,
+        returns: Option<ExprId>,
+        body: Vec<StmtId>,
+        decorator_list: Vec<ExprId>,
+        is_async: bool,
+        is_generator: bool,
+    },
+    ClassDef {
+        name: Symbol,
+        type_params: Vec<Located<TypeParam>>,
+        bases: Vec<ExprId>,
+        body: Vec<StmtId>,
+        decorator_list: Vec<ExprId>,
+    },
+    Return(Option<ExprId>),
+    AnnAssign {
+        target: Symbol,
+        annotation: ExprId,
+        value: Option<ExprId>,
+    },
+    TypeAlias {
+        name: Symbol,
+        type_params: Vec<Located<TypeParam>>,
+        value: ExprId,
+    },
+    Match {
+        subject: ExprId,
+        cases: Vec<MatchCase>,
+    },
+    For {
+        target: ExprId,
+        iter: ExprId,
+        body: Vec<StmtId>,
+        orelse: Vec<StmtId>,
+        is_async: bool,
+    },
+}
+
+#[derive(Debug)]
+pub enum Pattern {
+    Value(ExprId),
+    Singleton(ExprId),
+    Sequence(Vec<PatternId>),
+    Mapping {
+        keys: Vec<ExprId>,
+        patterns: Vec<PatternId>,
+        rest: Option<Symbol>,
+    },
+    Class {
+        cls: ExprId,
+        patterns: Vec<PatternId>,
+        kwd_attrs: Vec<Symbol>,
+        kwd_patterns: Vec<PatternId>,
+    },
+    Star(Option<Symbol>),
+    As(Option<PatternId>, Option<Symbol>),
+    Or(Vec<PatternId>),
+}
+
+#[derive(Debug)]
+pub struct MatchCase {
+    pub pattern: PatternId,
+    pub guard: Option<ExprId>,
+    pub body: Vec<StmtId>,
+}
+
+#[derive(Debug)]
+pub struct Parameter {
+    pub name: Symbol,
+    pub annotation: Option<ExprId>,
+    pub default: Option<ExprId>,
+}
+
+#[derive(Debug)]
+pub enum TypeParam {
+    TypeVar {
+        name: Symbol,
+        bound: Option<ExprId>,
+    },
+    TypeVarTuple(Symbol),
+    ParamSpec(Symbol),
+}
+
+#[derive(Debug)]
+pub struct WithItem {
+    pub context_expr: ExprId,
+    pub optional_vars: Option<ExprId>,
+}
+
+#[derive(Debug)]
+pub struct ExceptHandler {
+    pub exception: Option<ExprId>,
+    pub name: Option<Symbol>,
+    pub body: Vec<StmtId>,
+}
+
+// A program and the arenas its nodes live in. Look nodes up by indexing,
+// `ast[id]`
+#[derive(Debug, Default)]
+pub struct Ast {
+    // The top-level statements
+    pub statements: Vec<StmtId>,
+    expressions: Vec<Located<Expression>>,
+    stmts: Vec<Located<Stmt>>,
+    patterns: Vec<Located<Pattern>>,
+}
+
+fn next_id(len: usize) -> u32 {
+    u32::try_from(len).expect("more AST nodes than an arena can index")
+}
+
+impl Ast {
+    pub fn add_expression(&mut self, expr: Located<Expression>) -> ExprId {
+        let id = ExprId(next_id(self.expressions.len()));
+        self.expressions.push(expr);
+        id
+    }
+
+    pub fn add_stmt(&mut self, stmt: Located<Stmt>) -> StmtId {
+        let id = StmtId(next_id(self.stmts.len()));
+        self.stmts.push(stmt);
+        id
+    }
+
+    pub fn add_pattern(&mut self, pattern: Located<Pattern>) -> PatternId {
+        let id = PatternId(next_id(self.patterns.len()));
+        self.patterns.push(pattern);
+        id
+    }
+
+    // Number of expressions, statements and patterns in the arenas
+    pub fn len(&self) -> usize {
+        self.expressions.len() + self.stmts.len() + self.patterns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Move a boxed statement and everything under it into the arenas
+    fn move_stmt(&mut self, stmt: Located<boxed::Stmt>) -> StmtId {
+        let Located { node, span } = stmt;
+        let node = match node {
+            boxed::Stmt::Error => Stmt::Error,
+            boxed::Stmt::Assignment(target, value) => Stmt::Assignment(target, self.move_expression(value)),
+            boxed::Stmt::Expr(value) => Stmt::Expr(self.move_expression(value)),
+            boxed::Stmt::Pass => Stmt::Pass,
+            boxed::Stmt::Import(names) => Stmt::Import(names),
+            boxed::Stmt::ImportFrom { module, names, level } => Stmt::ImportFrom { module, names, level },
+            boxed::Stmt::Raise { exc, cause } => Stmt::Raise {
+                exc: exc.map(|e| self.move_expression(e)),
+                cause: cause.map(|e| self.move_expression(e)),
+            },
+            boxed::Stmt::Try { body, handlers, orelse, finalbody, star } => Stmt::Try {
+                body: self.move_stmts(body),
+                handlers: handlers.into_iter().map(|handler| {
+                    let Located { node, span } = handler;
+                    Located::new(ExceptHandler {
+                        exception: node.exception.map(|e| self.move_expression(e)),
+                        name: node.name,
+                        body: self.move_stmts(node.body),
+                    }, span)
+                }).collect(),
+                orelse: self.move_stmts(orelse),
+                finalbody: self.move_stmts(finalbody),
+                star,
+            },
+            boxed::Stmt::With { items, body, is_async } => Stmt::With {
+                items: items.into_iter().map(|item| WithItem {
+                    context_expr: self.move_expression(item.context_expr),
+                    optional_vars: item.optional_vars.map(|e| self.move_expression(e)),
+                }).collect(),
+                body: self.move_stmts(body),
+                is_async,
+            },
+            boxed::Stmt::FunctionDef { name, type_params, params, returns, body, decorator_list, is_async, is_generator } => Stmt::FunctionDef {
+                name,
+                type_params: self.move_type_params(type_params),
+                params: params.into_iter().map(|param| {
+                    let Located { node, span } = param;
+                    Located::new(Parameter {
+                        name: node.name,
+                        annotation: node.annotation.map(|e| self.move_expression(e)),
+                        default: node.default.map(|e| self.move_expression(e)),
+                    }, span)
+                }).collect(),
+                returns: returns.map(|e| self.move_expression(e)),
+                body: self.move_stmts(body),
+                decorator_list: self.move_expressions(decorator_list),
+                is_async,
+                is_generator,
+            },
+            boxed::Stmt::ClassDef { name, type_params, bases, body, decorator_list } => Stmt::ClassDef {
+                name,
+                type_params: self.move_type_params(type_params),
+                bases: self.move_expressions(bases),
+                body: self.move_stmts(body),
+                decorator_list: self.move_expressions(decorator_list),
+            },
+            boxed::Stmt::Return(value) => Stmt::Return(value.map(|e| self.move_expression(e))),
+            boxed::Stmt::AnnAssign { target, annotation, value } => Stmt::AnnAssign {
+                target,
+                annotation: self.move_expression(annotation),
+                value: value.map(|e| self.move_expression(e)),
+            },
+            boxed::Stmt::TypeAlias { name, type_params, value } => Stmt::TypeAlias {
+                name,
+                type_params: self.move_type_params(type_params),
+                value: self.move_expression(value),
+            },
+            boxed::Stmt::Match { subject, cases } => Stmt::Match {
+                subject: self.move_expression(subject),
+                cases: cases.into_iter().map(|case| MatchCase {
+                    pattern: self.move_pattern(case.pattern),
+                    guard: case.guard.map(|e| self.move_expression(e)),
+                    body: self.move_stmts(case.body),
+                }).collect(),
+            },
+            boxed::Stmt::For { target, iter, body, orelse, is_async } => Stmt::For {
+                target: self.move_expression(target),
+                iter: self.move_expression(iter),
+                body: self.move_stmts(body),
+                orelse: self.move_stmts(orelse),
+                is_async,
+            },
+        };
+        self.add_stmt(Located::new(node, span))
+    }
+
+    fn move_stmts(&mut self, stmts: Vec<Located<boxed::Stmt>>) -> Vec<StmtId> {
+        stmts.into_iter().map(|stmt| self.move_stmt(stmt)).collect()
+    }
+
+    fn move_expressions(&mut self, exprs: Vec<Located<boxed::Expression>>) -> Vec<ExprId> {
+        exprs.into_iter().map(|expr| self.move_expression(expr)).collect()
+    }
+
+    fn move_type_params(&mut self, type_params: Vec<Located<boxed::TypeParam>>) -> Vec<Located<TypeParam>> {
+        type_params.into_iter().map(|type_param| {
+            let Located { node, span } = type_param;
+            let node = match node {
+                boxed::TypeParam::TypeVar { name, bound } => TypeParam::TypeVar { name, bound: bound.map(|e| self.move_expression(e)) },
+                boxed::TypeParam::TypeVarTuple(name) => TypeParam::TypeVarTuple(name),
+                boxed::TypeParam::ParamSpec(name) => TypeParam::ParamSpec(name),
+            };
+            Located::new(node, span)
+        }).collect()
+    }
+
+    // Chains like `1 + 1 + ...` nest as deeply as they are long, so rather
+    // than recursing, each node claims slots for its children and they are
+    // moved in from a work list
+    fn move_expression(&mut self, expr: Located<boxed::Expression>) -> ExprId {
+        let root = self.add_expression(Expression::Error.into());
+        let mut pending = vec![(root, expr)];
+
+        while let Some((id, Located { mut node, span })) = pending.pop() {
+            // Children are taken out in place, boxed expressions being
+            // dropped by taking them apart
+            let mut child = |expr: &mut Located<boxed::Expression>| {
+                let id = self.add_expression(Expression::Error.into());
+                pending.push((id, take_expression(expr)));
+                id
+            };
+
+            let node = match &mut node {
+                boxed::Expression::Number(n) => Expression::Number(*n),
+                boxed::Expression::String(value) => Expression::String(std::mem::take(value)),
+                boxed::Expression::Variable(name) => Expression::Variable(name.clone()),
+                boxed::Expression::Boolean(b) => Expression::Boolean(*b),
+                boxed::Expression::None => Expression::None,
+                boxed::Expression::BinaryOp(left, op, right) => {
+                    let left = child(left);
+                    Expression::BinaryOp(left, *op, child(right))
+                }
+                boxed::Expression::CompareOp(left, op, right) => {
+                    let left = child(left);
+                    Expression::CompareOp(left, *op, child(right))
+                }
+                boxed::Expression::Compare(left, rest) => {
+                    let left = child(left);
+                    Expression::Compare(left, rest.iter_mut().map(|(op, right)| (*op, child(right))).collect())
+                }
+                boxed::Expression::Tuple(items) => Expression::Tuple(items.iter_mut().map(&mut child).collect()),
+                boxed::Expression::Attribute(value, attr) => Expression::Attribute(child(value), attr.clone()),
+                boxed::Expression::Subscript(value, index) => {
+                    let value = child(value);
+                    Expression::Subscript(value, child(index))
+                }
+                boxed::Expression::Call(func, args) => {
+                    let func = child(func);
+                    Expression::Call(func, args.iter_mut().map(&mut child).collect())
+                }
+                boxed::Expression::Await(value) => Expression::Await(child(value)),
+                boxed::Expression::Yield(value) => Expression::Yield(value.as_mut().map(|value| child(value))),
+                boxed::Expression::YieldFrom(value) => Expression::YieldFrom(child(value)),
+                boxed::Expression::Error => Expression::Error,
+            };
+            self.expressions[id.0 as usize] = Located::new(node, span);
+        }
+
+        root
+    }
+
+    fn move_pattern(&mut self, pattern: Located<boxed::Pattern>) -> PatternId {
+        let Located { node, span } = pattern;
+        let node = match node {
+            boxed::Pattern::Value(value) => Pattern::Value(self.move_expression(value)),
+            boxed::Pattern::Singleton(value) => Pattern::Singleton(self.move_expression(value)),
+            boxed::Pattern::Sequence(patterns) => Pattern::Sequence(self.move_patterns(patterns)),
+            boxed::Pattern::Mapping { keys, patterns, rest } => Pattern::Mapping {
+                keys: self.move_expressions(keys),
+                patterns: self.move_patterns(patterns),
+                rest,
+            },
+            boxed::Pattern::Class { cls, patterns, kwd_attrs, kwd_patterns } => Pattern::Class {
+                cls: self.move_expression(cls),
+                patterns: self.move_patterns(patterns),
+                kwd_attrs,
+                kwd_patterns: self.move_patterns(kwd_patterns),
+            },
+            boxed::Pattern::Star(name) => Pattern::Star(name),
+            boxed::Pattern::As(pattern, name) => Pattern::As(pattern.map(|p| self.move_pattern(*p)), name),
+            boxed::Pattern::Or(patterns) => Pattern::Or(self.move_patterns(patterns)),
+        };
+        self.add_pattern(Located::new(node, span))
+    }
+
+    fn move_patterns(&mut self, patterns: Vec<Located<boxed::Pattern>>) -> Vec<PatternId> {
+        patterns.into_iter().map(|pattern| self.move_pattern(pattern)).collect()
+    }
+}
+
+impl Index<ExprId> for Ast {
+    type Output = Located<Expression>;
+
+    fn index(&self, id: ExprId) -> &Self::Output {
+        &self.expressions[id.0 as usize]
+    }
+}
+
+impl Index<StmtId> for Ast {
+    type Output = Located<Stmt>;
+
+    fn index(&self, id: StmtId) -> &Self::Output {
+        &self.stmts[id.0 as usize]
+    }
+}
+
+impl Index<PatternId> for Ast {
+    type Output = Located<Pattern>;
+
+    fn index(&self, id: PatternId) -> &Self::Output {
+        &self.patterns[id.0 as usize]
+    }
+}
+
+impl From<boxed::Program> for Ast {
+    fn from(mut program: boxed::Program) -> Self {
+        let mut ast = Ast::default();
+        ast.statements = ast.move_stmts(mem::take(&mut program.statements));
+        ast
+    }
+}
+
+// Builds the boxed tree back up, taking each node out of its arena once
+struct Unpack {
+    expressions: Vec<Option<Located<Expression>>>,
+    stmts: Vec<Option<Located<Stmt>>>,
+    patterns: Vec<Option<Located<Pattern>>>,
+}
+
+// Steps of a post-order walk over expressions, see Unpack::expression
+enum Step {
+    Enter(ExprId),
+    Build(Located<Expression>),
+}
+
+impl Unpack {
+    fn stmt(&mut self, id: StmtId) -> Located<boxed::Stmt> {
+        let Located { node, span } = self.stmts[id.0 as usize].take().expect("statement used twice in the tree");
+        let node = match node {
+            Stmt::Error => boxed::Stmt::Error,
+            Stmt::Assignment(target, value) => boxed::Stmt::Assignment(target, self.expression(value)),
+            Stmt::Expr(value) => boxed::Stmt::Expr(self.expression(value)),
+            Stmt::Pass => boxed::Stmt::Pass,
+            Stmt::Import(names) => boxed::Stmt::Import(names),
+            Stmt::ImportFrom { module, names, level } => boxed::Stmt::ImportFrom { module, names, level },
+            Stmt::Raise { exc, cause } => boxed::Stmt::Raise {
+                exc: exc.map(|e| self.expression(e)),
+                cause: cause.map(|e| self.expression(e)),
+            },
+            Stmt::Try { body, handlers, orelse, finalbody, star } => boxed::Stmt::Try {
+                body: self.stmts(body),
+                handlers: handlers.into_iter().map(|handler| {
+                    let Located { node, span } = handler;
+                    Located::new(boxed::ExceptHandler {
+                        exception: node.exception.map(|e| self.expression(e)),
+                        name: node.name,
+                        body: self.stmts(node.body),
+                    }, span)
+                }).collect(),
+                orelse: self.stmts(orelse),
+                finalbody: self.stmts(finalbody),
+                star,
+            },
+            Stmt::With { items, body, is_async } => boxed::Stmt::With {
+                items: items.into_iter().map(|item| boxed::WithItem {
+                    context_expr: self.expression(item.context_expr),
+                    optional_vars: item.optional_vars.map(|e| self.expression(e)),
+                }).collect(),
+                body: self.stmts(body),
+                is_async,
+            },
+            Stmt::FunctionDef { name, type_params, params, returns, body, decorator_list, is_async, is_generator } => boxed::Stmt::FunctionDef {
+                name,
+                type_params: self.type_params(type_params),
+                params: params.into_iter().map(|param| {
+                    let Located { node, span } = param;
+                    Located::new(boxed::Parameter {
+                        name: node.name,
+                        annotation: node.annotation.map(|e| self.expression(e)),
+                        default: node.default.map(|e| self.expression(e)),
+                    }, span)
+                }).collect(),
+                returns: returns.map(|e| self.expression(e)),
+                body: self.stmts(body),
+                decorator_list: self.expressions(decorator_list),
+                is_async,
+                is_generator,
+            },
+            Stmt::ClassDef { name, type_params, bases, body, decorator_list } => boxed::Stmt::ClassDef {
+                name,
+                type_params: self.type_params(type_params),
+                bases: self.expressions(bases),
+                body: self.stmts(body),
+                decorator_list: self.expressions(decorator_list),
+            },
+            Stmt::Return(value) => boxed::Stmt::Return(value.map(|e| self.expression(e))),
+            Stmt::AnnAssign { target, annotation, value } => boxed::Stmt::AnnAssign {
+                target,
+                annotation: self.expression(annotation),
+                value: value.map(|e| self.expression(e)),
+            },
+            Stmt::TypeAlias { name, type_params, value } => boxed::Stmt::TypeAlias {
+                name,
+                type_params: self.type_params(type_params),
+                value: self.expression(value),
+            },
+            Stmt::Match { subject, cases } => boxed::Stmt::Match {
+                subject: self.expression(subject),
+                cases: cases.into_iter().map(|case| boxed::MatchCase {
+                    pattern: self.pattern(case.pattern),
+                    guard: case.guard.map(|e| self.expression(e)),
+                    body: self.stmts(case.body),
+                }).collect(),
+            },
+            Stmt::For { target, iter, body, orelse, is_async } => boxed::Stmt::For {
+                target: self.expression(target),
+                iter: self.expression(iter),
+                body: self.stmts(body),
+                orelse: self.stmts(orelse),
+                is_async,
+            },
+        };
+        Located::new(node, span)
+    }
+
+    fn stmts(&mut self, ids: Vec<StmtId>) -> Vec<Located<boxed::Stmt>> {
+        ids.into_iter().map(|id| self.stmt(id)).collect()
+    }
+
+    fn expressions(&mut self, ids: Vec<ExprId>) -> Vec<Located<boxed::Expression>> {
+        ids.into_iter().map(|id| self.expression(id)).collect()
+    }
+
+    fn type_params(&mut self, type_params: Vec<Located<TypeParam>>) -> Vec<Located<boxed::TypeParam>> {
+        type_params.into_iter().map(|type_param| {
+            let Located { node, span } = type_param;
+            let node = match node {
+                TypeParam::TypeVar { name, bound } => boxed::TypeParam::TypeVar { name, bound: bound.map(|e| self.expression(e)) },
+                TypeParam::TypeVarTuple(name) => boxed::TypeParam::TypeVarTuple(name),
+                TypeParam::ParamSpec(name) => boxed::TypeParam::ParamSpec(name),
+            };
+            Located::new(node, span)
+        }).collect()
+    }
+
+    // A post-order walk with its own stack: children are built first, onto
+    // `built`, and their parent then takes them off the end
+    fn expression(&mut self, root: ExprId) -> Located<boxed::Expression> {
+        let mut steps = vec![Step::Enter(root)];
+        let mut built: Vec<Located<boxed::Expression>> = Vec::new();
+
+        while let Some(step) = steps.pop() {
+            match step {
+                Step::Enter(id) => {
+                    let expr = self.expressions[id.0 as usize].take().expect("expression used twice in the tree");
+                    let children = children(&expr.node);
+                    steps.push(Step::Build(expr));
+                    steps.extend(children.into_iter().rev().map(Step::Enter));
+                }
+                Step::Build(Located { node, span }) => {
+                    let count = children(&node).len();
+                    let mut children = built.split_off(built.len() - count).into_iter();
+                    let mut next = || Box::new(children.next().expect("a built child"));
+
+                    let node = match node {
+                        Expression::Number(n) => boxed::Expression::Number(n),
+                        Expression::String(value) => boxed::Expression::String(value),
+                        Expression::Variable(name) => boxed::Expression::Variable(name),
+                        Expression::Boolean(b) => boxed::Expression::Boolean(b),
+                        Expression::None => boxed::Expression::None,
+                        Expression::BinaryOp(_, op, _) => {
+                            let left = next();
+                            boxed::Expression::BinaryOp(left, op, next())
+                        }
+                        Expression::CompareOp(_, op, _) => {
+                            let left = next();
+                            boxed::Expression::CompareOp(left, op, next())
+                        }
+                        Expression::Compare(_, rest) => {
+                            let left = next();
+                            boxed::Expression::Compare(left, rest.into_iter().map(|(op, _)| op).zip(children).collect())
+                        }
+                        Expression::Tuple(_) => boxed::Expression::Tuple(children.collect()),
+                        Expression::Attribute(_, attr) => boxed::Expression::Attribute(next(), attr),
+                        Expression::Subscript(..) => {
+                            let value = next();
+                            boxed::Expression::Subscript(value, next())
+                        }
+                        Expression::Call(..) => {
+                            let func = next();
+                            boxed::Expression::Call(func, children.collect())
+                        }
+                        Expression::Await(_) => boxed::Expression::Await(next()),
+                        Expression::Yield(value) => boxed::Expression::Yield(value.map(|_| next())),
+                        Expression::YieldFrom(_) => boxed::Expression::YieldFrom(next()),
+                        Expression::Error => boxed::Expression::Error,
+                    };
+                    built.push(Located::new(node, span));
+                }
+            }
+        }
+
+        built.pop().expect("the root expression")
+    }
+
+    fn pattern(&mut self, id: PatternId) -> Located<boxed::Pattern> {
+        let Located { node, span } = self.patterns[id.0 as usize].take().expect("pattern used twice in the tree");
+        let node = match node {
+            Pattern::Value(value) => boxed::Pattern::Value(self.expression(value)),
+            Pattern::Singleton(value) => boxed::Pattern::Singleton(self.expression(value)),
+            Pattern::Sequence(patterns) => boxed::Pattern::Sequence(self.patterns(patterns)),
+            Pattern::Mapping { keys, patterns, rest } => boxed::Pattern::Mapping {
+                keys: self.expressions(keys),
+                patterns: self.patterns(patterns),
+                rest,
+            },
+            Pattern::Class { cls, patterns, kwd_attrs, kwd_patterns } => boxed::Pattern::Class {
+                cls: self.expression(cls),
+                patterns: self.patterns(patterns),
+                kwd_attrs,
+                kwd_patterns: self.patterns(kwd_patterns),
+            },
+            Pattern::Star(name) => boxed::Pattern::Star(name),
+            Pattern::As(pattern, name) => boxed::Pattern::As(pattern.map(|p| Box::new(self.pattern(p))), name),
+            Pattern::Or(patterns) => boxed::Pattern::Or(self.patterns(patterns)),
+        };
+        Located::new(node, span)
+    }
+
+    fn patterns(&mut self, ids: Vec<PatternId>) -> Vec<Located<boxed::Pattern>> {
+        ids.into_iter().map(|id| self.pattern(id)).collect()
+    }
+}
+
+// The direct subexpressions of an expression, in source order
+fn children(expr: &Expression) -> Vec<ExprId> {
+    match expr {
+        Expression::Number(_) | Expression::String(_) | Expression::Variable(_) | Expression::Boolean(_) | Expression::None | Expression::Error => Vec::new(),
+        Expression::BinaryOp(left, _, right) | Expression::CompareOp(left, _, right) | Expression::Subscript(left, right) => vec![*left, *right],
+        Expression::Tuple(items) => items.clone(),
+        Expression::Call(func, args) => std::iter::once(*func).chain(args.iter().copied()).collect(),
+        Expression::Compare(left, rest) => std::iter::once(*left).chain(rest.iter().map(|(_, right)| *right)).collect(),
+        Expression::Attribute(value, _) | Expression::Await(value) | Expression::YieldFrom(value) => vec![*value],
+        Expression::Yield(value) => value.iter().copied().collect(),
+    }
+}
+
+impl From<Ast> for boxed::Program {
+    fn from(ast: Ast) -> Self {
+        let mut unpack = Unpack {
+            expressions: ast.expressions.into_iter().map(Some).collect(),
+            stmts: ast.stmts.into_iter().map(Some).collect(),
+            patterns: ast.patterns.into_iter().map(Some).collect(),
+        };
+        boxed::Program { statements: unpack.stmts(ast.statements) }
+    }
+}
+
+// Parse into an arena, as parse_program_recovering does into boxes. Each
+// top-level statement is parsed into boxes and then moved into the arena, so
+// the boxed form of only one statement exists at a time. That makes building
+// no faster than parse_program_recovering, it is dropping the Ast that is.
+// Errors are ordered as parse_program_recovering orders them
+pub fn parse_program_arena(tokens: &[SpannedToken]) -> (Ast, Vec<ParseError>) {
+    crate::in_parse(DEFAULT_NESTING_LIMIT, || {
+        let mut ast = Ast::default();
+        let mut position = 0;
+        let mut errors = Vec::new();
+        let mut check_errors = Vec::new();
+
+        while position < tokens.len() && current_token(tokens, position) != &Token::EOF {
+            let stmt = parse_statement_or_recover(tokens, &mut position, &mut errors);
+            check_statements(std::slice::from_ref(&stmt), Scope::Module, &mut check_errors);
+            let id = ast.move_stmt(stmt);
+            ast.statements.push(id);
+        }
+
+        errors.append(&mut check_errors);
+        (ast, errors)
+    })
+}
@@ -0,0 +1,230 @@
+use std::cmp::Reverse;
+use std::fmt;
+
+use crate::visit::{left_spine, walk_chain, walk_except_handler, walk_parameter, walk_pattern, walk_stmt, walk_type_param, Visitor};
+use crate::{parse_program, parse_program_recovering, Alias, ExceptHandler, Expression, Located, Parameter, ParseError, Pattern, Program, Stmt, TypeParam};
+use tokenizer::{tokenize_with_trivia, Span, SpannedToken, Token, TokenizeError};
+
+// A lossless syntax tree. Every byte of the source belongs to exactly one
+// token, whitespace and comments included, and the nodes group those tokens
+// the way the AST nodes of the same source do. Printing a tree gives back the
+// source it was parsed from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CstKind {
+    Module,
+    Statement,
+    Expression,
+    Pattern,
+    ExceptHandler,
+    Parameter,
+    TypeParam,
+    Alias,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CstToken {
+    pub token: Token<'static>,
+    // The exact source text, e.g. with the quotes of a string
+    pub text: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CstElement {
+    Node(CstNode),
+    Token(CstToken),
+}
+
+// A chain like `1 + 1 + ...` nests its nodes as deeply as it is long. Listing
+// tokens, printing and dropping a node do not recurse, but Debug, Clone and
+// PartialEq do
+#[derive(Debug, Clone, PartialEq)]
+pub struct CstNode {
+    pub kind: CstKind,
+    pub span: Span,
+    pub children: Vec<CstElement>,
+}
+
+impl CstNode {
+    // Every token under the node in source order, trivia included
+    pub fn tokens(&self) -> Vec<&CstToken> {
+        let mut tokens = Vec::new();
+        self.collect_tokens(&mut tokens);
+        tokens
+    }
+
+    // Each node's children are pushed in reverse, so they pop off in order
+    fn collect_tokens<'a>(&'a self, tokens: &mut Vec<&'a CstToken>) {
+        let mut pending: Vec<&CstElement> = self.children.iter().rev().collect();
+        while let Some(element) = pending.pop() {
+            match element {
+                CstElement::Node(node) => pending.extend(node.children.iter().rev()),
+                CstElement::Token(token) => tokens.push(token),
+            }
+        }
+    }
+
+    // The tokens the parser sees, without trivia
+    fn significant_tokens(&self) -> Vec<SpannedToken<'_>> {
+        self.tokens()
+            .into_iter()
+            .filter(|t| !t.token.is_trivia())
+            .map(|t| SpannedToken { token: t.token.clone(), span: t.span })
+            .collect()
+    }
+
+    // The AST of the source under the node, parsed as a program. Spans in the
+    // AST point into the original source
+    pub fn to_ast(&self) -> Result<Program, ParseError> {
+        parse_program(&self.significant_tokens())
+    }
+
+    // Like to_ast, but keeps going after errors as parse_program_recovering does
+    pub fn to_ast_recovering(&self) -> (Program, Vec<ParseError>) {
+        parse_program_recovering(&self.significant_tokens())
+    }
+}
+
+// Moves the children of nested nodes out onto a list before dropping them, as
+// Expression's Drop does
+impl Drop for CstNode {
+    fn drop(&mut self) {
+        let mut pending = std::mem::take(&mut self.children);
+        while let Some(element) = pending.pop() {
+            if let CstElement::Node(mut node) = element {
+                pending.append(&mut node.children);
+            }
+        }
+    }
+}
+
+impl fmt::Display for CstNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for token in self.tokens() {
+            f.write_str(&token.text)?;
+        }
+        Ok(())
+    }
+}
+
+// Parse source into a lossless tree. Syntax errors do not stop the tree from
+// being built, broken statements become Statement nodes holding their tokens.
+// Only input the tokenizer rejects fails
+pub fn parse_cst(source: &str) -> Result<CstNode, TokenizeError> {
+    let tokens = tokenize_with_trivia(source)?;
+    let significant: Vec<SpannedToken> = tokens.iter().filter(|t| !t.token.is_trivia()).cloned().collect();
+    let (program, _) = parse_program_recovering(&significant);
+
+    let mut collector = Collector { tokens: &significant, spans: Vec::new() };
+    collector.visit_program(&program);
+
+    let tokens = tokens
+        .into_iter()
+        .map(|t| CstToken { text: source[t.span.start.offset..t.span.end.offset].to_string(), token: t.token.into_owned(), span: t.span })
+        .collect();
+
+    Ok(build(tokens, collector.spans))
+}
+
+// Nest the tokens into nodes. Node spans are either disjoint or nested, so
+// sorting them by start, outermost first, gives the order to open them in
+fn build(tokens: Vec<CstToken>, mut spans: Vec<(CstKind, Span)>) -> CstNode {
+    spans.sort_by_key(|(_, span)| (span.start.offset, Reverse(span.end.offset)));
+    let mut pending = spans.into_iter().peekable();
+
+    let root_span = match (tokens.first(), tokens.last()) {
+        (Some(first), Some(last)) => first.span.to(last.span),
+        _ => Span::default(),
+    };
+    let mut stack = vec![CstNode { kind: CstKind::Module, span: root_span, children: Vec::new() }];
+
+    for token in tokens {
+        let offset = token.span.start.offset;
+        let is_empty = token.span.end.offset == offset;
+        close_nodes(&mut stack, offset);
+
+        // An empty Dedent or EOF at the start of a node stays outside it
+        while let Some((kind, span)) = pending.next_if(|(_, span)| {
+            span.start.offset < offset || (span.start.offset == offset && (!is_empty || span.end.offset == offset))
+        }) {
+            stack.push(CstNode { kind, span, children: Vec::new() });
+            close_nodes(&mut stack, offset);
+        }
+
+        if let Some(node) = stack.last_mut() {
+            node.children.push(CstElement::Token(token));
+        }
+    }
+
+    close_nodes(&mut stack, usize::MAX);
+    stack.pop().unwrap_or(CstNode { kind: CstKind::Module, span: root_span, children: Vec::new() })
+}
+
+// Close the innermost nodes that end at or before offset
+fn close_nodes(stack: &mut Vec<CstNode>, offset: usize) {
+    while stack.len() > 1 && stack.last().is_some_and(|node| node.span.end.offset <= offset) {
+        if let (Some(node), Some(parent)) = (stack.pop(), stack.last_mut()) {
+            parent.children.push(CstElement::Node(node));
+        }
+    }
+}
+
+// Gathers the span of every AST node
+struct Collector<'a> {
+    tokens: &'a [SpannedToken<'a>],
+    spans: Vec<(CstKind, Span)>,
+}
+
+impl Collector<'_> {
+    // The AST locates a decorated definition at its `def` or `class`, the
+    // tree node also takes in the decorators and their `@`
+    fn with_decorators(&self, span: Span, decorator_list: &[Located<Expression>]) -> Span {
+        let Some(first) = decorator_list.first() else { return span };
+
+        self.tokens
+            .iter()
+            .rev()
+            .find(|t| t.token == Token::At && t.span.start.offset < first.span.start.offset)
+            .map_or(span, |at| at.span.to(span))
+    }
+}
+
+impl Visitor for Collector<'_> {
+    fn visit_stmt(&mut self, stmt: &Located<Stmt>) {
+        let span = match &stmt.node {
+            Stmt::FunctionDef { decorator_list, .. } | Stmt::ClassDef { decorator_list, .. } => self.with_decorators(stmt.span, decorator_list),
+            _ => stmt.span,
+        };
+        self.spans.push((CstKind::Statement, span));
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expression(&mut self, expr: &Located<Expression>) {
+        self.spans.extend(left_spine(expr).map(|expr| (CstKind::Expression, expr.span)));
+        walk_chain(self, expr);
+    }
+
+    fn visit_pattern(&mut self, pattern: &Located<Pattern>) {
+        self.spans.push((CstKind::Pattern, pattern.span));
+        walk_pattern(self, pattern);
+    }
+
+    fn visit_except_handler(&mut self, handler: &Located<ExceptHandler>) {
+        self.spans.push((CstKind::ExceptHandler, handler.span));
+        walk_except_handler(self, handler);
+    }
+
+    fn visit_parameter(&mut self, param: &Located<Parameter>) {
+        self.spans.push((CstKind::Parameter, param.span));
+        walk_parameter(self, param);
+    }
+
+    fn visit_type_param(&mut self, type_param: &Located<TypeParam>) {
+        self.spans.push((CstKind::TypeParam, type_param.span));
+        walk_type_param(self, type_param);
+    }
+
+    fn visit_alias(&mut self, alias: &Located<Alias>) {
+        self.spans.push((CstKind::Alias, alias.span));
+    }
+}
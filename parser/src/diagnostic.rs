@@ -0,0 +1,137 @@
+use crate::{ParseError, ParseErrorKind};
+use tokenizer::{Span, Token, TokenizeError, TokenizeErrorKind};
+
+// A report about a span of source, rendered the way rustc prints errors:
+//
+// error: '(' was never closed
+//  --> example.py:1:6
+//   |
+// 1 | print(x
+//   |      ^
+//   = help: add a closing ')'
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Span,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        Diagnostic { message: message.into(), span, help: None }
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    // Render the report against the source the span refers to, with ANSI
+    // colors when color is set
+    pub fn render(&self, file_name: &str, source: &str, color: bool) -> String {
+        let style = Style { color };
+        let start = self.span.start;
+        let line = source.lines().nth(start.line - 1).unwrap_or("");
+
+        // Spans over several lines are underlined up to the end of the first
+        let mut column = start.column.min(line.len());
+        let mut end = if self.span.end.line == start.line { self.span.end.column.clamp(column, line.len()) } else { line.len() };
+
+        // Source shown lossily, when it could not be decoded, can put a
+        // column inside a character
+        while !line.is_char_boundary(column) {
+            column -= 1;
+        }
+        while !line.is_char_boundary(end) {
+            end += 1;
+        }
+
+        let line_number = start.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+
+        // Keep tabs in the padding so the carets line up with the source
+        let padding: String = line[..column].chars().map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+        let carets = "^".repeat(line[column..end].chars().count().max(1));
+
+        let mut report = format!("{}{}\n", style.error("error"), style.bold(&format!(": {}", self.message)));
+        report += &format!("{}{} {}:{}:{}\n", gutter, style.blue("-->"), file_name, start.line, line[..column].chars().count() + 1);
+        report += &format!("{} {}\n", gutter, style.blue("|"));
+        report += &format!("{} {} {}\n", style.blue(&line_number), style.blue("|"), line);
+        report += &format!("{} {} {}{}\n", gutter, style.blue("|"), padding, style.error(&carets));
+
+        if let Some(help) = &self.help {
+            report += &format!("{} {} {}\n", gutter, style.blue("="), style.bold(&format!("help: {}", help)));
+        }
+
+        report
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Self {
+        let diagnostic = Diagnostic::new(error.to_string(), error.span);
+
+        let help = match &error.kind {
+            ParseErrorKind::UnexpectedToken(Token::Indent) => "remove the extra indentation".to_string(),
+            ParseErrorKind::MismatchParenthesis(open) => {
+                let close = match open {
+                    '[' => ']',
+                    '{' => '}',
+                    _ => ')',
+                };
+                format!("add a closing '{}'", close)
+            }
+            ParseErrorKind::InvalidAssignment => "only names, attributes and tuples can be assigned to".to_string(),
+            ParseErrorKind::DefaultExceptNotLast => "move the bare 'except:' after the other handlers".to_string(),
+            ParseErrorKind::DoubleStarNotLast => "move it after the other items".to_string(),
+            ParseErrorKind::AlternativesBindDifferentNames => "capture the same names in every alternative".to_string(),
+            ParseErrorKind::UnreachablePatterns(_) => "move it after the others".to_string(),
+            ParseErrorKind::OutsideAsyncFunction(_) => "move it into an 'async def' function".to_string(),
+            ParseErrorKind::YieldFromInsideAsyncFunction => "iterate with 'async for' and yield each item instead".to_string(),
+            _ => return diagnostic,
+        };
+
+        diagnostic.with_help(help)
+    }
+}
+
+impl From<&TokenizeError> for Diagnostic {
+    fn from(error: &TokenizeError) -> Self {
+        let diagnostic = Diagnostic::new(error.to_string(), error.span);
+
+        match error.kind {
+            TokenizeErrorKind::UnterminatedString => diagnostic.with_help("add a closing '\"'"),
+            TokenizeErrorKind::InvalidCharacter(_) => diagnostic,
+            TokenizeErrorKind::IntegerTooLarge => diagnostic.with_help("integers must fit in 64 bits"),
+            TokenizeErrorKind::UnknownEncoding(_) => diagnostic.with_help("declare utf-8, latin-1 or ascii"),
+            TokenizeErrorKind::EncodingWithBom(_) => diagnostic.with_help("a file with a byte order mark is utf-8"),
+            TokenizeErrorKind::InvalidEncoding(..) => diagnostic,
+        }
+    }
+}
+
+struct Style {
+    color: bool,
+}
+
+impl Style {
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.color {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn error(&self, text: &str) -> String {
+        self.paint("1;31", text)
+    }
+
+    fn blue(&self, text: &str) -> String {
+        self.paint("1;34", text)
+    }
+
+    fn bold(&self, text: &str) -> String {
+        self.paint("1", text)
+    }
+}
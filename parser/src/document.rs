@@ -0,0 +1,252 @@
+use std::convert::Infallible;
+use std::iter;
+use std::ops::Range;
+
+use tokenizer::{Lexer, Position, Span, TokenizeError};
+
+use crate::{
+    check_statements, in_parse, left_operand, walk_except_handler_mut, walk_expression_mut, walk_parameter_mut, walk_pattern_mut,
+    walk_stmt_mut, walk_type_param_mut, Alias, ExceptHandler, Expression, Located, Parameter, ParseError, Pattern,
+    Program, Scope, StatementReader, Stmt, TypeParam, VisitorMut, DEFAULT_NESTING_LIMIT,
+};
+
+// A source file kept parsed across edits, for editors. An edit re-lexes and
+// re-parses from the top-level statement it starts in until the statements
+// line up with the old ones again, and keeps the trees of the rest. The
+// program and errors always match parse_program_recovering on the whole
+// source, and the tokenize errors those Lexer gives for it
+pub struct Document {
+    source: String,
+    program: Program,
+    // One for each statement in the program
+    statements: Vec<Statement>,
+    tokenize_errors: Vec<TokenizeError>,
+}
+
+// What is kept about a top-level statement besides its tree
+struct Statement {
+    // Where its first token starts, decorators included
+    start: Position,
+    // Whether it starts a line with no bracket or block open, so lexing can
+    // start over from there
+    clean: bool,
+    errors: Vec<ParseError>,
+    // What check_statements found in it, which comes after every parse error
+    check_errors: Vec<ParseError>,
+}
+
+impl Document {
+    pub fn new(source: impl Into<String>) -> Self {
+        let source = source.into();
+        let (stmts, statements, tokenize_errors, _) = parse_from(&source, Position::default(), |_| None);
+        Document { source, program: Program { statements: stmts }, statements, tokenize_errors }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    // The errors parse_program_recovering gives for the source, in its order:
+    // every statement's syntax errors, then every statement's check errors
+    pub fn errors(&self) -> impl Iterator<Item = &ParseError> {
+        let errors = self.statements.iter().flat_map(|s| &s.errors);
+        errors.chain(self.statements.iter().flat_map(|s| &s.check_errors))
+    }
+
+    // Invalid characters and the like, which are left out of the tokens parsed
+    pub fn tokenize_errors(&self) -> &[TokenizeError] {
+        &self.tokenize_errors
+    }
+
+    // Replace the bytes in `range` of the source with `text`, returning which
+    // statements of the new program were parsed again. Panics if the range is
+    // out of bounds or not on character boundaries
+    pub fn edit(&mut self, range: Range<usize>, text: &str) -> Range<usize> {
+        let mut shift = Shift {
+            lines: newlines(text) as isize - newlines(&self.source[range.clone()]) as isize,
+            offset: text.len() as isize - range.len() as isize,
+        };
+        self.source.replace_range(range.clone(), text);
+
+        // The statement the edit starts in may now carry on the one before it,
+        // as when its first line becomes `else:`, so parsing starts from that one
+        let mut first = self.statements.partition_point(|s| s.start.offset <= range.start).saturating_sub(2);
+        while first > 0 && !self.statements[first].clean {
+            first -= 1;
+        }
+        let start = match first {
+            0 => Position::default(),
+            _ => self.statements[first].start,
+        };
+
+        // Once a statement starts where one after the edit used to, the rest of
+        // the source is as it was and so are the statements
+        let unchanged = self.statements.partition_point(|s| s.start.offset < range.end);
+        let old = &self.statements[unchanged..];
+        let (stmts, statements, tokenize_errors, resumed) = parse_from(&self.source, start, |next| {
+            let offset = next.offset.checked_sub(range.start + text.len())? + range.end;
+            let index = old.binary_search_by_key(&offset, |s| s.start.offset).ok()?;
+            old[index].clean.then_some(unchanged + index)
+        });
+
+        let end = resumed.unwrap_or(self.statements.len());
+        let parsed = first..first + statements.len();
+        for (stmt, statement) in self.program.statements[end..].iter_mut().zip(&mut self.statements[end..]) {
+            shift.visit_stmt_mut(stmt);
+            shift.position(&mut statement.start);
+            for error in statement.errors.iter_mut().chain(&mut statement.check_errors) {
+                shift.span(&mut error.span);
+            }
+        }
+
+        // Tokenize errors go by where they are. Those from `start` up to where
+        // the old statements pick up again come from lexing it again
+        let resume = self.statements.get(end).map(|s| s.start.offset);
+        let before = self.tokenize_errors.partition_point(|e| e.span.start.offset < start.offset);
+        let after = resume.map_or(self.tokenize_errors.len(), |resume| {
+            let old = resume.wrapping_add_signed(-shift.offset);
+            self.tokenize_errors.partition_point(|e| e.span.start.offset < old)
+        });
+        for error in &mut self.tokenize_errors[after..] {
+            shift.span(&mut error.span);
+        }
+        let lexed = tokenize_errors.into_iter().filter(|e| resume.is_none_or(|resume| e.span.start.offset < resume));
+        self.tokenize_errors.splice(before..after, lexed);
+
+        self.statements.splice(first..end, statements);
+
+        self.program.statements.splice(first..end, stmts);
+
+        parsed
+    }
+}
+
+fn newlines(text: &str) -> usize {
+    text.bytes().filter(|&b| b == b'\n').count()
+}
+
+// Lex and parse the source from `start`, the start of the input or of a clean
+// statement, to its end or to the first statement `resume` gives the index of
+// an old statement for. Returns the statements before that one and the
+// tokenize errors found, some of which may be past it
+fn parse_from(
+    source: &str,
+    start: Position,
+    resume: impl Fn(Position) -> Option<usize>,
+) -> (Vec<Located<Stmt>>, Vec<Statement>, Vec<TokenizeError>, Option<usize>) {
+    let mut tokenize_errors = Vec::new();
+    let mut lexer = Lexer::starting_at(source, start);
+    let tokens = iter::from_fn(|| loop {
+        match lexer.next()? {
+            Ok(token) => return Some(Ok::<_, Infallible>(token)),
+            Err(error) => tokenize_errors.push(error),
+        }
+    });
+
+    let mut stmts = Vec::new();
+    let mut statements = Vec::new();
+    let resumed = in_parse(DEFAULT_NESTING_LIMIT, || {
+        let mut reader = StatementReader::new(tokens);
+        loop {
+            let Ok(Some(start)) = reader.next_start() else { return None };
+            let Ok(clean) = reader.at_clean_start();
+            if clean {
+                if let Some(index) = resume(start) {
+                    return Some(index);
+                }
+            }
+
+            let mut errors = Vec::new();
+            let Ok(Some(stmt)) = reader.next_statement(&mut errors) else { return None };
+            let mut check_errors = Vec::new();
+            check_statements(std::slice::from_ref(&stmt), Scope::Module, &mut check_errors);
+            stmts.push(stmt);
+            statements.push(Statement { start, clean, errors, check_errors });
+        }
+    });
+
+    (stmts, statements, tokenize_errors, resumed)
+}
+
+// How positions after an edit move
+#[derive(Clone, Copy)]
+struct Shift {
+    lines: isize,
+    offset: isize,
+}
+
+impl Shift {
+    // Only for positions on lines after the edit, whose columns stay the same
+    fn position(&self, position: &mut Position) {
+        position.line = position.line.wrapping_add_signed(self.lines);
+        position.offset = position.offset.wrapping_add_signed(self.offset);
+    }
+
+    fn span(&self, span: &mut Span) {
+        self.position(&mut span.start);
+        self.position(&mut span.end);
+    }
+}
+
+impl VisitorMut for Shift {
+    fn visit_stmt_mut(&mut self, stmt: &mut Located<Stmt>) {
+        self.span(&mut stmt.span);
+        walk_stmt_mut(self, stmt)
+    }
+
+    // Down chains like `1 + 1 + ...` in a loop rather than recursively
+    fn visit_expression_mut(&mut self, mut expr: &mut Located<Expression>) {
+        loop {
+            self.span(&mut expr.span);
+            if left_operand(expr).is_none() {
+                return walk_expression_mut(self, expr);
+            }
+            expr = match &mut expr.node {
+                Expression::BinaryOp(left, _, right)
+                | Expression::CompareOp(left, _, right)
+                | Expression::Subscript(left, right) => {
+                    self.visit_expression_mut(right);
+                    left
+                }
+                Expression::Compare(left, rest) => {
+                    rest.iter_mut().for_each(|(_, right)| self.visit_expression_mut(right));
+                    left
+                }
+                Expression::Attribute(left, _) => left,
+                Expression::Call(left, args) => {
+                    args.iter_mut().for_each(|arg| self.visit_expression_mut(arg));
+                    left
+                }
+                _ => unreachable!(),
+            };
+        }
+    }
+
+    fn visit_pattern_mut(&mut self, pattern: &mut Located<Pattern>) {
+        self.span(&mut pattern.span);
+        walk_pattern_mut(self, pattern)
+    }
+
+    fn visit_except_handler_mut(&mut self, handler: &mut Located<ExceptHandler>) {
+        self.span(&mut handler.span);
+        walk_except_handler_mut(self, handler)
+    }
+
+    fn visit_parameter_mut(&mut self, param: &mut Located<Parameter>) {
+        self.span(&mut param.span);
+        walk_parameter_mut(self, param)
+    }
+
+    fn visit_type_param_mut(&mut self, type_param: &mut Located<TypeParam>) {
+        self.span(&mut type_param.span);
+        walk_type_param_mut(self, type_param)
+    }
+
+    fn visit_alias_mut(&mut self, alias: &mut Located<Alias>) {
+        self.span(&mut alias.span);
+    }
+}
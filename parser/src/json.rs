@@ -0,0 +1,80 @@
+use serde::de::DeserializeOwned;
+use serde::ser::Error as _;
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+
+use crate::{from_python_ast, to_python_ast, Program};
+
+// Programs as JSON text, either in the shape of our own types or in that of
+// CPython's ast module. A chain like `1 + 1 + ...` nests as deeply as it is
+// long, but serde_json refuses to read more than 128 levels and reads, writes
+// and drops a Value recursively. These functions lift the limit and run on a
+// stack that grows as it needs to, so chains of any length round-trip through
+// them
+
+type Error = serde_json::Error;
+
+// The program as serde writes it, spans included
+pub fn program_to_json(program: &Program) -> Result<String, Error> {
+    write(program)
+}
+
+// Read back a program written by program_to_json
+pub fn program_from_json(text: &str) -> Result<Program, Error> {
+    read(text)
+}
+
+// The program as to_python_ast gives it, written out as text
+pub fn python_ast_to_json(program: &Program) -> Result<String, Error> {
+    let value = to_python_ast(program);
+    let text = write(&Deep(&value));
+    dismantle(value);
+    text
+}
+
+// Read back a Module as from_python_ast does, from text
+pub fn python_ast_from_json(text: &str) -> Result<Program, Error> {
+    let value: Value = read(text)?;
+    let program = from_python_ast(&value);
+    dismantle(value);
+    program
+}
+
+fn write<T: Serialize>(value: &T) -> Result<String, Error> {
+    let mut text = Vec::new();
+    value.serialize(&mut serde_json::Serializer::new(&mut text))?;
+    String::from_utf8(text).map_err(Error::custom)
+}
+
+fn read<T: DeserializeOwned>(text: &str) -> Result<T, Error> {
+    let mut deserializer = serde_json::Deserializer::from_str(text);
+    deserializer.disable_recursion_limit();
+    let value = T::deserialize(serde_stacker::Deserializer::new(&mut deserializer))?;
+    deserializer.end()?;
+    Ok(value)
+}
+
+// A Value serialized on a stack that grows when it runs low, as Located is
+struct Deep<'a>(&'a Value);
+
+impl Serialize for Deep<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        stacker::maybe_grow(128 << 10, 2 << 20, || match self.0 {
+            Value::Array(values) => serializer.collect_seq(values.iter().map(Deep)),
+            Value::Object(fields) => serializer.collect_map(fields.iter().map(|(key, value)| (key, Deep(value)))),
+            value => value.serialize(serializer),
+        })
+    }
+}
+
+// serde_json drops a Value recursively, so a deep one is taken apart first
+fn dismantle(value: Value) {
+    let mut pending = vec![value];
+    while let Some(value) = pending.pop() {
+        match value {
+            Value::Array(values) => pending.extend(values),
+            Value::Object(fields) => pending.extend(fields.into_iter().map(|(_, value)| value)),
+            _ => {}
+        }
+    }
+}
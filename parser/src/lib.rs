@@ -1,17 +1,105 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::fmt;
+use std::iter;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize, Serializer};
 use tokenizer::*;
 
-#[derive(Debug, PartialEq)]
+pub mod arena;
+mod cst;
+mod diagnostic;
+mod document;
+mod json;
+mod python_ast;
+mod unparse;
+mod visit;
+pub use arena::{parse_program_arena, Ast};
+pub use cst::{parse_cst, CstElement, CstKind, CstNode, CstToken};
+pub use diagnostic::Diagnostic;
+pub use document::Document;
+pub use json::{program_from_json, program_to_json, python_ast_from_json, python_ast_to_json};
+pub use python_ast::{from_python_ast, to_python_ast};
+pub use unparse::{unparse, unparse_expression};
+pub use visit::*;
+
+// A node together with the source range it was parsed from. The span gives
+// CPython's lineno, col_offset, end_lineno and end_col_offset, plus byte
+// offsets. Spans are ignored when comparing nodes, so trees compare by shape
+#[derive(Debug, Deserialize)]
+pub struct Located<T> {
+    pub node: T,
+    pub span: Span,
+}
+
+impl<T> Located<T> {
+    pub fn new(node: T, span: Span) -> Self {
+        Located { node, span }
+    }
+}
+
+impl<T: PartialEq> PartialEq for Located<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.node == other.node
+    }
+}
+
+// Serialized as derived, but on a stack that grows when it runs low. A chain
+// like `1 + 1 + ...` nests a Located per operand, and serializing those
+// recursively could otherwise overflow the stack
+impl<T: Serialize> Serialize for Located<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        #[serde(rename = "Located")]
+        struct Fields<'a, T> {
+            node: &'a T,
+            span: &'a Span,
+        }
+
+        stacker::maybe_grow(128 << 10, 2 << 20, || Fields { node: &self.node, span: &self.span }.serialize(serializer))
+    }
+}
+
+// A node with an empty span, for building trees by hand
+impl<T> From<T> for Located<T> {
+    fn from(node: T) -> Self {
+        Located::new(node, Span::default())
+    }
+}
+
+// An identifier in the AST. The names of one parse are interned, so every
+// occurrence of a name shares one allocation
+pub type Symbol = Arc<str>;
+
+// Debug formats an expression recursively, so it can overflow the stack on a
+// chain like `1 + 1 + ...` many thousands of operands long. Comparing and
+// dropping expressions do not recurse
+#[derive(Debug, Serialize, Deserialize)]
 pub enum Expression {
     Number(i64),
     String(String),
-    Variable(String),
+    Variable(Symbol),
     Boolean(bool),
-    BinaryOp(Box<Expression>, BinaryOperator, Box<Expression>),
-    CompareOp(Box<Expression>, CompareOperator, Box<Expression>),
-
+    None,
+    BinaryOp(Box<Located<Expression>>, BinaryOperator, Box<Located<Expression>>),
+    // A single comparison, or `&&` or `||`
+    CompareOp(Box<Located<Expression>>, CompareOperator, Box<Located<Expression>>),
+    // Two or more comparisons chained, `a < b == c`, which compares each
+    // operand with the next. `(a < b) == c` is a CompareOp instead
+    Compare(Box<Located<Expression>>, Vec<(CompareOperator, Located<Expression>)>),
+    Tuple(Vec<Located<Expression>>),
+    Attribute(Box<Located<Expression>>, Symbol),
+    Subscript(Box<Located<Expression>>, Box<Located<Expression>>),
+    Call(Box<Located<Expression>>, Vec<Located<Expression>>),
+    Await(Box<Located<Expression>>),
+    // `yield` and `yield value`, a bare yield has no value
+    Yield(Option<Box<Located<Expression>>>),
+    YieldFrom(Box<Located<Expression>>),
+    // Placeholder for an expression that failed to parse
+    Error,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum BinaryOperator {
     Add,
     Subtract, 
@@ -22,7 +110,7 @@ pub enum BinaryOperator {
     Power,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum CompareOperator {
     Equal,
     NotEqual,
@@ -42,69 +130,648 @@ pub enum PrecedenceLevel {
     Power
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub enum Stmt {
-    Assignment(String, Expression)
+    // Placeholder for a statement that failed to parse
+    Error,
+    Assignment(Symbol, Located<Expression>),
+    Expr(Located<Expression>),
+    Pass,
+    Import(Vec<Located<Alias>>),
+    // `from module import names`, level counts the leading dots of a relative import
+    ImportFrom {
+        module: Option<Symbol>,
+        names: Vec<Located<Alias>>,
+        level: usize,
+    },
+    // `raise exc from cause`
+    Raise {
+        exc: Option<Located<Expression>>,
+        cause: Option<Located<Expression>>,
+    },
+    // try statement, star is set for PEP 654 `except*` handlers
+    Try {
+        body: Vec<Located<Stmt>>,
+        handlers: Vec<Located<ExceptHandler>>,
+        orelse: Vec<Located<Stmt>>,
+        finalbody: Vec<Located<Stmt>>,
+        star: bool,
+    },
+    // `with a as b, c:` and `async with`
+    With {
+        items: Vec<WithItem>,
+        body: Vec<Located<Stmt>>,
+        is_async: bool,
+    },
+    // is_generator is set when the body contains `yield`, an async def
+    // with it set is an async generator
+    FunctionDef {
+        name: Symbol,
+        type_params: Vec<Located<TypeParam>>,
+        params: Vec<Located<Parameter>>,
+        returns: Option<Located<Expression>>,
+        body: Vec<Located<Stmt>>,
+        decorator_list: Vec<Located<Expression>>,
+        is_async: bool,
+        is_generator: bool,
+    },
+    ClassDef {
+        name: Symbol,
+        type_params: Vec<Located<TypeParam>>,
+        bases: Vec<Located<Expression>>,
+        body: Vec<Located<Stmt>>,
+        decorator_list: Vec<Located<Expression>>,
+    },
+    Return(Option<Located<Expression>>),
+    // `target: annotation = value`, the value is optional
+    AnnAssign {
+        target: Symbol,
+        annotation: Located<Expression>,
+        value: Option<Located<Expression>>,
+    },
+    // `type Alias[T] = value` (PEP 695)
+    TypeAlias {
+        name: Symbol,
+        type_params: Vec<Located<TypeParam>>,
+        value: Located<Expression>,
+    },
+    Match {
+        subject: Located<Expression>,
+        cases: Vec<MatchCase>,
+    },
+    // `for target in iter:` with an optional `else:` block
+    For {
+        target: Located<Expression>,
+        iter: Located<Expression>,
+        body: Vec<Located<Stmt>>,
+        orelse: Vec<Located<Stmt>>,
+        is_async: bool,
+    },
 }
 
-#[derive(Debug)]
-pub enum ParseError {
-    UnexpectedToken(String),
-    MismatchParenthesis,
+// PEP 634 patterns used by the cases of a match statement
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub enum Pattern {
+    // Literals and dotted names such as `Color.RED`
+    Value(Located<Expression>),
+    // True, False and None, which are compared by identity
+    Singleton(Located<Expression>),
+    Sequence(Vec<Located<Pattern>>),
+    // `{key: pattern, **rest}`
+    Mapping {
+        keys: Vec<Located<Expression>>,
+        patterns: Vec<Located<Pattern>>,
+        rest: Option<Symbol>,
+    },
+    // `Point(x, y=0)`
+    Class {
+        cls: Located<Expression>,
+        patterns: Vec<Located<Pattern>>,
+        kwd_attrs: Vec<Symbol>,
+        kwd_patterns: Vec<Located<Pattern>>,
+    },
+    // `*rest` inside a sequence, `*_` has no name
+    Star(Option<Symbol>),
+    // `pattern as name`, a capture is As(None, Some(name)) and `_` is As(None, None)
+    As(Option<Box<Located<Pattern>>>, Option<Symbol>),
+    Or(Vec<Located<Pattern>>),
+}
+
+// `case pattern if guard:`
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct MatchCase {
+    pub pattern: Located<Pattern>,
+    pub guard: Option<Located<Expression>>,
+    pub body: Vec<Located<Stmt>>,
+}
+
+// A function parameter with an optional annotation and default value
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Parameter {
+    pub name: Symbol,
+    pub annotation: Option<Located<Expression>>,
+    pub default: Option<Located<Expression>>,
+}
+
+// A PEP 695 type parameter of a generic function, class or type alias
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub enum TypeParam {
+    // `T` or `T: bound`
+    TypeVar {
+        name: Symbol,
+        bound: Option<Located<Expression>>,
+    },
+    // `*Ts`
+    TypeVarTuple(Symbol),
+    // `**P`
+    ParamSpec(Symbol),
+}
+
+// A context manager of a with statement and its optional `as` target
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct WithItem {
+    pub context_expr: Located<Expression>,
+    pub optional_vars: Option<Located<Expression>>,
+}
+
+// `except exception as name:` clause, a bare `except:` has no exception
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExceptHandler {
+    pub exception: Option<Located<Expression>>,
+    pub name: Option<Symbol>,
+    pub body: Vec<Located<Stmt>>,
+}
+
+// An imported name with an optional `as` rename, `*` for star imports
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Alias {
+    pub name: Symbol,
+    pub asname: Option<Symbol>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ParseErrorKind {
+    UnexpectedToken(Token<'static>),
+    // An opening bracket that was never closed
+    MismatchParenthesis(char),
     UnexpectedEOF,
     InvalidAssignment,
     InvalidIdentifier,
+    DefaultExceptNotLast,
+    // A `**rest` item of a mapping pattern with items after it
+    DoubleStarNotLast,
+    // Alternatives of an or-pattern that capture different names
+    AlternativesBindDifferentNames,
+    // An irrefutable case or alternative before others, "wildcard" or "name capture 'x'"
+    UnreachablePatterns(String),
+    // `C(x=1, x=2)`
+    RepeatedKeywordPattern(String),
+    MixedExceptStar,
+    OutsideAsyncFunction(String),
+    OutsideFunction(String),
+    YieldFromInsideAsyncFunction,
+    ReturnInsideAsyncGenerator,
+    // More nested brackets, blocks or patterns than the nesting limit allows
+    TooDeeplyNested,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub span: Span,
+    // Descriptions of what would have been accepted instead, e.g. "')'"
+    pub expected: Vec<String>,
+}
+
+impl ParseError {
+    pub fn new(kind: ParseErrorKind, span: Span) -> Self {
+        ParseError { kind, span, expected: Vec::new() }
+    }
+
+    fn expecting(mut self, expected: &[&str]) -> Self {
+        self.expected = expected.iter().map(|e| e.to_string()).collect();
+        self
+    }
+}
+
+// Messages follow CPython's SyntaxError wording where there is an equivalent
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let expected = match self.expected.as_slice() {
+            [] => None,
+            [only] => Some(only.clone()),
+            [rest @ .., last] => Some(format!("{} or {}", rest.join(", "), last)),
+        };
+
+        match (&self.kind, expected) {
+            (ParseErrorKind::UnexpectedToken(Token::Indent), _) => write!(f, "unexpected indent"),
+            (ParseErrorKind::UnexpectedToken(_) | ParseErrorKind::UnexpectedEOF | ParseErrorKind::InvalidIdentifier, Some(expected)) => {
+                write!(f, "expected {}", expected)
+            }
+            (ParseErrorKind::UnexpectedToken(_), None) => write!(f, "invalid syntax"),
+            (ParseErrorKind::UnexpectedEOF, None) => write!(f, "unexpected EOF while parsing"),
+            (ParseErrorKind::MismatchParenthesis(open), _) => write!(f, "'{}' was never closed", open),
+            (ParseErrorKind::InvalidAssignment, _) => write!(f, "cannot assign to expression"),
+            (ParseErrorKind::InvalidIdentifier, None) => write!(f, "invalid identifier"),
+            (ParseErrorKind::DefaultExceptNotLast, _) => write!(f, "default 'except:' must be last"),
+            (ParseErrorKind::DoubleStarNotLast, _) => write!(f, "'**' pattern must be last"),
+            (ParseErrorKind::AlternativesBindDifferentNames, _) => write!(f, "alternative patterns bind different names"),
+            (ParseErrorKind::UnreachablePatterns(pattern), _) => write!(f, "{} makes remaining patterns unreachable", pattern),
+            (ParseErrorKind::RepeatedKeywordPattern(attr), _) => write!(f, "attribute name repeated in class pattern: {}", attr),
+            (ParseErrorKind::MixedExceptStar, _) => {
+                write!(f, "cannot have both 'except' and 'except*' on the same 'try'")
+            }
+            (ParseErrorKind::OutsideAsyncFunction(construct), _) => write!(f, "'{}' outside async function", construct),
+            (ParseErrorKind::OutsideFunction(construct), _) => write!(f, "'{}' outside function", construct),
+            (ParseErrorKind::YieldFromInsideAsyncFunction, _) => write!(f, "'yield from' inside async function"),
+            (ParseErrorKind::ReturnInsideAsyncGenerator, _) => write!(f, "'return' with value in async generator"),
+            (ParseErrorKind::TooDeeplyNested, _) => write!(f, "too deeply nested"),
+        }
+    }
 }
 
-#[derive(Debug, PartialEq)]
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Program {
-    pub statements: Vec<Stmt>,
+    pub statements: Vec<Located<Stmt>>,
+}
+
+// Dropping a chain like `1 + 1 + ...` recursively could overflow the stack, so
+// an expression moves its subexpressions out onto a list and drops them one at
+// a time, each after moving out its own
+impl Drop for Expression {
+    fn drop(&mut self) {
+        let mut pending = Vec::new();
+        take_subexpressions(self, &mut pending);
+        while let Some(mut expr) = pending.pop() {
+            take_subexpressions(&mut expr.node, &mut pending);
+        }
+    }
+}
+
+// Comparing chains recursively could overflow the stack as dropping them
+// could, so pairs of subexpressions to compare are kept on a list
+impl PartialEq for Expression {
+    fn eq(&self, other: &Self) -> bool {
+        let mut pending = vec![(self, other)];
+        let (mut left, mut right) = (Vec::new(), Vec::new());
+        while let Some((a, b)) = pending.pop() {
+            if !same_node(a, b) {
+                return false;
+            }
+            subexpressions(a, &mut left);
+            subexpressions(b, &mut right);
+            if left.len() != right.len() {
+                return false;
+            }
+            pending.extend(left.drain(..).zip(right.drain(..)).map(|(a, b)| (&a.node, &b.node)));
+        }
+        true
+    }
+}
+
+// Whether two expressions are of the same kind and agree on all but their
+// subexpressions
+fn same_node(a: &Expression, b: &Expression) -> bool {
+    match (a, b) {
+        (Expression::Number(a), Expression::Number(b)) => a == b,
+        (Expression::String(a), Expression::String(b)) => a == b,
+        (Expression::Variable(a), Expression::Variable(b)) | (Expression::Attribute(_, a), Expression::Attribute(_, b)) => a == b,
+        (Expression::Boolean(a), Expression::Boolean(b)) => a == b,
+        (Expression::BinaryOp(_, a, _), Expression::BinaryOp(_, b, _)) => a == b,
+        (Expression::CompareOp(_, a, _), Expression::CompareOp(_, b, _)) => a == b,
+        (Expression::Compare(_, a), Expression::Compare(_, b)) => a.iter().map(|(op, _)| op).eq(b.iter().map(|(op, _)| op)),
+        _ => std::mem::discriminant(a) == std::mem::discriminant(b),
+    }
+}
+
+// Adds the subexpressions of an expression to `out`, in source order
+fn subexpressions<'e>(expr: &'e Expression, out: &mut Vec<&'e Located<Expression>>) {
+    match expr {
+        Expression::BinaryOp(left, _, right) | Expression::CompareOp(left, _, right) | Expression::Subscript(left, right) => {
+            out.extend([&**left, &**right]);
+        }
+        Expression::Compare(left, rest) => {
+            out.push(left);
+            out.extend(rest.iter().map(|(_, e)| e));
+        }
+        Expression::Tuple(elements) => out.extend(elements),
+        Expression::Call(func, args) => {
+            out.push(func);
+            out.extend(args);
+        }
+        Expression::Attribute(value, _) | Expression::Await(value) | Expression::YieldFrom(value) | Expression::Yield(Some(value)) => out.push(value),
+        Expression::Number(_) | Expression::String(_) | Expression::Variable(_) | Expression::Boolean(_) | Expression::None | Expression::Yield(None) | Expression::Error => {}
+    }
+}
+
+// Moves an expression out of the tree, leaving Error in its place
+fn take_expression(expr: &mut Located<Expression>) -> Located<Expression> {
+    std::mem::replace(expr, Expression::Error.into())
+}
+
+// Moves the subexpressions of an expression to `out`
+fn take_subexpressions(expr: &mut Expression, out: &mut Vec<Located<Expression>>) {
+    let mut take = |expr: &mut Located<Expression>| out.push(take_expression(expr));
+    match expr {
+        Expression::BinaryOp(left, _, right) | Expression::CompareOp(left, _, right) | Expression::Subscript(left, right) => {
+            take(left);
+            take(right);
+        }
+        Expression::Compare(left, rest) => {
+            take(left);
+            rest.iter_mut().for_each(|(_, e)| take(e));
+        }
+        Expression::Tuple(elements) => elements.iter_mut().for_each(take),
+        Expression::Call(func, args) => {
+            take(func);
+            args.iter_mut().for_each(take);
+        }
+        Expression::Attribute(value, _) | Expression::Await(value) | Expression::YieldFrom(value) | Expression::Yield(Some(value)) => take(value),
+        Expression::Number(_) | Expression::String(_) | Expression::Variable(_) | Expression::Boolean(_) | Expression::None | Expression::Yield(None) | Expression::Error => {}
+    }
+}
+
+fn current_token<'t, 'a>(tokens: &'t [SpannedToken<'a>], position: usize) -> &'t Token<'a> {
+    tokens.get(position).map_or(&Token::EOF, |t| &t.token)
+}
+
+// Span of the token at position, or of the end of input past the last token
+fn current_span(tokens: &[SpannedToken], position: usize) -> Span {
+    tokens.get(position).or(tokens.last()).map_or(Span::default(), |t| t.span)
+}
+
+// Span from the token at start to the last token consumed before position.
+// The newlines and indentation that end a block are not part of it
+fn span_since(tokens: &[SpannedToken], start: usize, position: usize) -> Span {
+    let mut end = position;
+    while end > start && matches!(current_token(tokens, end - 1), Token::Newline | Token::Indent | Token::Dedent) {
+        end -= 1;
+    }
+
+    let first = current_span(tokens, start);
+    if end > start {
+        first.to(current_span(tokens, end - 1))
+    } else {
+        Span::new(first.start, first.start)
+    }
 }
 
-fn current_token(tokens: &[Token], position: usize) -> &Token {
-    tokens.get(position).unwrap_or(&Token::EOF)
+fn located<T>(tokens: &[SpannedToken], start: usize, position: usize, node: T) -> Located<T> {
+    Located::new(node, span_since(tokens, start, position))
 }
 
 fn advance(position: &mut usize) {
     *position += 1;
 }
 
-// Parse Variables (identifiers)
-fn parse_identifier(tokens: &[Token], position: &mut usize) -> Result<Expression, ParseError> {
+fn unexpected(tokens: &[SpannedToken], position: usize, expected: &[&str]) -> ParseError {
+    let kind = match current_token(tokens, position) {
+        Token::EOF => ParseErrorKind::UnexpectedEOF,
+        token => ParseErrorKind::UnexpectedToken(token.clone().into_owned()),
+    };
+    ParseError::new(kind, current_span(tokens, position)).expecting(expected)
+}
+
+fn expect_token(tokens: &[SpannedToken], position: &mut usize, token: Token, description: &str) -> Result<(), ParseError> {
+    if *current_token(tokens, *position) == token {
+        advance(position);
+        Ok(())
+    } else {
+        Err(unexpected(tokens, *position, &[description]))
+    }
+}
+
+// Expect the bracket closing the one at open. Running out of input reports the
+// opening bracket as never closed, anything else is unexpected where the
+// closing bracket or one of the alternatives could go
+fn expect_closing(tokens: &[SpannedToken], position: &mut usize, open: usize, alternatives: &[&str]) -> Result<(), ParseError> {
+    let (open_char, close, description) = match current_token(tokens, open) {
+        Token::OpenBracket => ('[', Token::CloseBracket, "']'"),
+        Token::OpenBrace => ('{', Token::CloseBrace, "'}'"),
+        _ => ('(', Token::CloseParen, "')'"),
+    };
+
+    if *current_token(tokens, *position) == close {
+        advance(position);
+        return Ok(());
+    }
+
+    let mut expected = vec![description];
+    expected.extend_from_slice(alternatives);
+
+    if let Token::EOF = current_token(tokens, *position) {
+        let kind = ParseErrorKind::MismatchParenthesis(open_char);
+        Err(ParseError::new(kind, current_span(tokens, open)).expecting(&expected))
+    } else {
+        Err(unexpected(tokens, *position, &expected))
+    }
+}
+
+// How deeply brackets, blocks and patterns may nest in parse_program, the
+// limit CPython puts on nested parentheses
+pub const DEFAULT_NESTING_LIMIT: usize = 200;
+
+// The most a nesting limit can be, larger limits are lowered to it. It keeps
+// the stack a parse may need under about 320MB
+pub const MAX_NESTING_LIMIT: usize = 10_000;
+
+// The parser recurses once per level of nesting, and a level of parentheses
+// takes about 20KB of stack in a debug build
+const PARSE_LEVEL_STACK: usize = 32 << 10;
+
+// The stack a parse needs, enough for the levels of `limit` and for what the
+// parser needs outside of them
+fn parse_stack_size(limit: usize) -> usize {
+    limit * PARSE_LEVEL_STACK + (1 << 20)
+}
+
+thread_local! {
+    // Levels left before the parse in progress fails with TooDeeplyNested
+    static NESTING_LEFT: Cell<usize> = const { Cell::new(DEFAULT_NESTING_LIMIT) };
+}
+
+// Held while parsing one level of nesting, gives the level back when dropped
+struct Nesting;
+
+impl Drop for Nesting {
+    fn drop(&mut self) {
+        NESTING_LEFT.with(|left| left.set(left.get() + 1));
+    }
+}
+
+fn nest(tokens: &[SpannedToken], position: usize) -> Result<Nesting, ParseError> {
+    NESTING_LEFT.with(|left| match left.get() {
+        0 => Err(ParseError::new(ParseErrorKind::TooDeeplyNested, current_span(tokens, position))),
+        n => {
+            left.set(n - 1);
+            Ok(Nesting)
+        }
+    })
+}
+
+thread_local! {
+    // The names of the parse in progress
+    static SYMBOLS: RefCell<HashSet<Symbol>> = RefCell::new(HashSet::new());
+}
+
+fn intern(name: &str) -> Symbol {
+    SYMBOLS.with(|symbols| {
+        let mut symbols = symbols.borrow_mut();
+        if let Some(symbol) = symbols.get(name) {
+            return symbol.clone();
+        }
+        let symbol = Symbol::from(name);
+        symbols.insert(symbol.clone());
+        symbol
+    })
+}
+
+fn is_keyword(tokens: &[SpannedToken], position: usize, keyword: &str) -> bool {
+    matches!(current_token(tokens, position), Token::Keyword(k) if k == keyword)
+}
+
+fn expect_keyword(tokens: &[SpannedToken], position: &mut usize, keyword: &str) -> Result<(), ParseError> {
+    if is_keyword(tokens, *position, keyword) {
+        advance(position);
+        Ok(())
+    } else {
+        Err(unexpected(tokens, *position, &[&format!("'{}'", keyword)]))
+    }
+}
 
+fn parse_name(tokens: &[SpannedToken], position: &mut usize) -> Result<Symbol, ParseError> {
     if let Token::Identifier(name) = current_token(tokens, *position) {
         advance(position);
-        Ok(Expression::Variable(name.clone()))
+        Ok(intern(name))
     } else {
-        Err(ParseError::InvalidIdentifier)
+        let error = ParseError::new(ParseErrorKind::InvalidIdentifier, current_span(tokens, *position));
+        Err(error.expecting(&["an identifier"]))
     }
 }
 
-fn parse_power(tokens: &[Token], position: &mut usize) -> Result<Expression, ParseError> {
+// Parse a dotted module path such as `os.path`
+fn parse_dotted_name(tokens: &[SpannedToken], position: &mut usize) -> Result<Symbol, ParseError> {
+    let first = parse_name(tokens, position)?;
+    if current_token(tokens, *position) != &Token::Dot {
+        return Ok(first);
+    }
 
-    let mut expr = parse_primary(tokens, position)?;
+    let mut name = first.to_string();
+    while let Token::Dot = current_token(tokens, *position) {
+        advance(position);
+        name.push('.');
+        name.push_str(&parse_name(tokens, position)?);
+    }
 
+    Ok(intern(&name))
+}
 
-    while let Token::Operator(op) = current_token(tokens, *position) {
-        if op == "**" {
+// Parse an optional `as name` rename
+fn parse_asname(tokens: &[SpannedToken], position: &mut usize) -> Result<Option<Symbol>, ParseError> {
+    if is_keyword(tokens, *position, "as") {
+        advance(position);
+        Ok(Some(parse_name(tokens, position)?))
+    } else {
+        Ok(None)
+    }
+}
+
+// import a.b as c, d
+fn parse_import(tokens: &[SpannedToken], position: &mut usize) -> Result<Stmt, ParseError> {
+    expect_keyword(tokens, position, "import")?;
+
+    let mut names = Vec::new();
+    loop {
+        let start = *position;
+        let name = parse_dotted_name(tokens, position)?;
+        let asname = parse_asname(tokens, position)?;
+        names.push(located(tokens, start, *position, Alias { name, asname }));
+
+        if let Token::Comma = current_token(tokens, *position) {
             advance(position);
-            let right =  parse_primary(tokens, position)?;
-            expr = Expression::BinaryOp(Box::new(expr), BinaryOperator::Power, Box::new(right));
         } else {
             break;
         }
     }
 
+    Ok(Stmt::Import(names))
+}
+
+// from ..pkg import (a, b as c)
+fn parse_import_from(tokens: &[SpannedToken], position: &mut usize) -> Result<Stmt, ParseError> {
+    expect_keyword(tokens, position, "from")?;
+
+    let mut level = 0;
+    while let Token::Dot = current_token(tokens, *position) {
+        advance(position);
+        level += 1;
+    }
+
+    let module = if level > 0 && is_keyword(tokens, *position, "import") {
+        None
+    } else {
+        Some(parse_dotted_name(tokens, position)?)
+    };
+
+    expect_keyword(tokens, position, "import")?;
+
+    let mut names = Vec::new();
+    match current_token(tokens, *position) {
+        Token::Operator(op) if op == "*" => {
+            advance(position);
+            names.push(located(tokens, *position - 1, *position, Alias { name: intern("*"), asname: None }));
+        }
+        Token::OpenParen => {
+            let open = *position;
+            advance(position);
+            // A trailing comma is only allowed inside parentheses
+            while let Token::Identifier(_) = current_token(tokens, *position) {
+                let start = *position;
+                let name = parse_name(tokens, position)?;
+                let asname = parse_asname(tokens, position)?;
+                names.push(located(tokens, start, *position, Alias { name, asname }));
+
+                if let Token::Comma = current_token(tokens, *position) {
+                    advance(position);
+                } else {
+                    break;
+                }
+            }
+
+            if names.is_empty() {
+                return Err(unexpected(tokens, *position, &["an identifier"]));
+            }
+
+            expect_closing(tokens, position, open, &["','"])?;
+        }
+        _ => loop {
+            let start = *position;
+            let name = parse_name(tokens, position)?;
+            let asname = parse_asname(tokens, position)?;
+            names.push(located(tokens, start, *position, Alias { name, asname }));
+
+            if let Token::Comma = current_token(tokens, *position) {
+                advance(position);
+            } else {
+                break;
+            }
+        },
+    }
+
+    Ok(Stmt::ImportFrom { module, names, level })
+}
+
+// Parse Variables (identifiers)
+fn parse_identifier(tokens: &[SpannedToken], position: &mut usize) -> Result<Located<Expression>, ParseError> {
+    let start = *position;
+    let name = parse_name(tokens, position)?;
+    Ok(located(tokens, start, *position, Expression::Variable(name)))
+}
+
+fn parse_power(tokens: &[SpannedToken], position: &mut usize) -> Result<Located<Expression>, ParseError> {
+    let start = *position;
+    let expr = parse_await(tokens, position)?;
+
+    // `**` groups to the right, `2 ** 3 ** 2` is `2 ** (3 ** 2)`
+    if is_operator(tokens, *position, "**") {
+        advance(position);
+        let _nesting = nest(tokens, *position)?;
+        let right = parse_power(tokens, position)?;
+        return Ok(located(tokens, start, *position, Expression::BinaryOp(Box::new(expr), BinaryOperator::Power, Box::new(right))));
+    }
+
     Ok(expr)
-   
 }
 
 // parse for factors
-fn parse_factor(tokens: &[Token], position: &mut usize) -> Result<Expression, ParseError> {
+fn parse_factor(tokens: &[SpannedToken], position: &mut usize) -> Result<Located<Expression>, ParseError> {
+    let start = *position;
     let mut expr = parse_power(tokens, position)?;
 
     while let Token::Operator(op) = current_token(tokens, *position) {
-        let operator = match op.as_str() {
+        let operator = match op.as_ref() {
             "*" => BinaryOperator::Multiply, 
             "/" => BinaryOperator::Divide,
             "//" => BinaryOperator::FloorDivide,
@@ -113,91 +780,299 @@ fn parse_factor(tokens: &[Token], position: &mut usize) -> Result<Expression, Pa
         };
         advance(position);
         let right = parse_power(tokens, position)?;
-        expr = Expression::BinaryOp(Box::new(expr), operator, Box::new(right));
+        expr = located(tokens, start, *position, Expression::BinaryOp(Box::new(expr), operator, Box::new(right)));
     }
 
     Ok(expr)
 }
 
 
-//handles expressions wrapped in parentheses 
-fn parse_primary(tokens: &[Token], position: &mut usize) -> Result<Expression, ParseError> {
-    match current_token(tokens, *position) {
-        Token::Number(n) => {
-            advance(position);
-            Ok(Expression::Number(*n))
-        }, 
-        Token::Identifier(name) => {
-            advance(position);
-            Ok(Expression::Variable(name.clone()))
-        },
-        Token::String(value) => {
+// `yield from iterable`, `yield` or `yield a, b`, only valid on its own as a
+// statement, as the value of an assignment or inside parentheses
+fn parse_yield(tokens: &[SpannedToken], position: &mut usize) -> Result<Located<Expression>, ParseError> {
+    let start = *position;
+    expect_keyword(tokens, position, "yield")?;
+
+    if is_keyword(tokens, *position, "from") {
+        advance(position);
+        let value = parse_expression(tokens, position)?;
+        return Ok(located(tokens, start, *position, Expression::YieldFrom(Box::new(value))));
+    }
+
+    if matches!(current_token(tokens, *position), Token::Newline | Token::Dedent | Token::EOF | Token::CloseParen) {
+        return Ok(located(tokens, start, *position, Expression::Yield(None)));
+    }
+
+    let value_start = *position;
+    let mut value = parse_expression(tokens, position)?;
+    if let Token::Comma = current_token(tokens, *position) {
+        let mut elements = vec![value];
+        while let Token::Comma = current_token(tokens, *position) {
             advance(position);
-            Ok(Expression::String(value.clone()))
+            if matches!(current_token(tokens, *position), Token::Newline | Token::Dedent | Token::EOF | Token::CloseParen) {
+                break;
+            }
+            elements.push(parse_expression(tokens, position)?);
         }
-        Token::Boolean(value) => {
-            advance(position);
-            Ok(Expression::Boolean(*value))
+        value = located(tokens, value_start, *position, Expression::Tuple(elements));
+    }
+
+    Ok(located(tokens, start, *position, Expression::Yield(Some(Box::new(value)))))
+}
+
+// `await` binds tighter than `**` but looser than calls
+fn parse_await(tokens: &[SpannedToken], position: &mut usize) -> Result<Located<Expression>, ParseError> {
+    if is_keyword(tokens, *position, "await") {
+        let start = *position;
+        advance(position);
+        let expr = parse_postfix(tokens, position)?;
+        Ok(located(tokens, start, *position, Expression::Await(Box::new(expr))))
+    } else {
+        parse_postfix(tokens, position)
+    }
+}
+
+// Attribute access and calls following a primary, e.g. `mod.func(a, b)`
+fn parse_postfix(tokens: &[SpannedToken], position: &mut usize) -> Result<Located<Expression>, ParseError> {
+    let start = *position;
+    let mut expr = parse_primary(tokens, position)?;
+
+    loop {
+        match current_token(tokens, *position) {
+            Token::Dot => {
+                advance(position);
+                let name = parse_name(tokens, position)?;
+                expr = located(tokens, start, *position, Expression::Attribute(Box::new(expr), name));
+            }
+            Token::OpenParen => {
+                let open = *position;
+                advance(position);
+                let mut args = Vec::new();
+                while !matches!(current_token(tokens, *position), Token::CloseParen) {
+                    args.push(parse_expression(tokens, position)?);
+
+                    if let Token::Comma = current_token(tokens, *position) {
+                        advance(position);
+                    } else {
+                        break;
+                    }
+                }
+
+                expect_closing(tokens, position, open, &["','"])?;
+                expr = located(tokens, start, *position, Expression::Call(Box::new(expr), args));
+            }
+            Token::OpenBracket => {
+                let open = *position;
+                advance(position);
+                let index_start = *position;
+                let mut index = parse_expression(tokens, position)?;
+
+                // `a[1, 2]` indexes with a tuple
+                if let Token::Comma = current_token(tokens, *position) {
+                    let mut elements = vec![index];
+                    while let Token::Comma = current_token(tokens, *position) {
+                        advance(position);
+                        if let Token::CloseBracket = current_token(tokens, *position) {
+                            break;
+                        }
+                        elements.push(parse_expression(tokens, position)?);
+                    }
+                    index = located(tokens, index_start, *position, Expression::Tuple(elements));
+                }
+
+                expect_closing(tokens, position, open, &["','"])?;
+                expr = located(tokens, start, *position, Expression::Subscript(Box::new(expr), Box::new(index)));
+            }
+            _ => break,
         }
+    }
+
+    Ok(expr)
+}
+
+//handles expressions wrapped in parentheses 
+fn parse_primary(tokens: &[SpannedToken], position: &mut usize) -> Result<Located<Expression>, ParseError> {
+    let start = *position;
+    let expr = match current_token(tokens, *position) {
+        Token::Number(n) => Expression::Number(*n),
+        // The tokenizer has reported it
+        Token::Invalid(_) => Expression::Error,
+        Token::Identifier(name) => Expression::Variable(intern(name)),
+        Token::String(value) => Expression::String(value.to_string()),
+        Token::Boolean(value) => Expression::Boolean(*value),
+        Token::Keyword(k) if k == "None" => Expression::None,
         Token::OpenParen => {
+            let open = *position;
             advance(position);
-            let expr = parse_expression(tokens, position)?;
 
+            // `()` is the empty tuple
             if let Token::CloseParen = current_token(tokens, *position) {
                 advance(position);
-                Ok(expr)
-            } else {
-                Err(ParseError::MismatchParenthesis)
+                return Ok(located(tokens, start, *position, Expression::Tuple(Vec::new())));
             }
+
+            // Parentheses are not part of the span of the expression they
+            // wrap, except for tuples
+            if is_keyword(tokens, *position, "yield") {
+                let expr = parse_yield(tokens, position)?;
+                expect_closing(tokens, position, open, &[])?;
+                return Ok(expr);
+            }
+
+            let expr = parse_expression(tokens, position)?;
+
+            // A comma turns the parenthesized expression into a tuple
+            if let Token::Comma = current_token(tokens, *position) {
+                let mut elements = vec![expr];
+                while let Token::Comma = current_token(tokens, *position) {
+                    advance(position);
+                    if let Token::CloseParen = current_token(tokens, *position) {
+                        break;
+                    }
+                    elements.push(parse_expression(tokens, position)?);
+                }
+                expect_closing(tokens, position, open, &["','"])?;
+                return Ok(located(tokens, start, *position, Expression::Tuple(elements)));
+            }
+
+            expect_closing(tokens, position, open, &["','"])?;
+            return Ok(expr);
         }, 
 
-        _ => Err(ParseError::UnexpectedToken(format!("{:?}", current_token(tokens, *position)))),
-    }
+        _ => return Err(unexpected(tokens, *position, &["an expression"])),
+    };
+
+    advance(position);
+    Ok(located(tokens, start, *position, expr))
 }
 
 
-fn parse_expression(tokens: &[Token], position: &mut usize) -> Result<Expression, ParseError> {
+// Every bracket nests through here
+fn parse_expression(tokens: &[SpannedToken], position: &mut usize) -> Result<Located<Expression>, ParseError> {
+    let _nesting = nest(tokens, *position)?;
     parse_boolean(tokens, position)
 }
 
-fn parse_add_sub(tokens: &[Token], position: &mut usize) -> Result<Expression, ParseError> {
-    let mut expr = parse_comparison(tokens, position)?;
+fn parse_add_sub(tokens: &[SpannedToken], position: &mut usize) -> Result<Located<Expression>, ParseError> {
+    let start = *position;
+    let mut expr = parse_factor(tokens, position)?;
 
     while let Token::Operator(op) = current_token(tokens, *position) {
-        let operator = match op.as_str() {
+        let operator = match op.as_ref() {
             "+" => BinaryOperator::Add,
             "-" => BinaryOperator::Subtract,
             _ => break,
         };
         advance(position);
         let right =  parse_factor(tokens, position)?;
-        expr = Expression::BinaryOp(Box::new(expr), operator, Box::new(right));
+        expr = located(tokens, start, *position, Expression::BinaryOp(Box::new(expr), operator, Box::new(right)));
     }
 
     Ok(expr)
 }
 
-fn parse_assignment(tokens: &[Token], position: &mut usize) -> Result<Stmt, ParseError> {
-    
-    if let Expression::Variable(var) = parse_identifier(tokens, position)? {
-        if let Token::Assign = current_token(tokens, *position) {
-            advance(position);
-            let expr = parse_expression(tokens, position)?;
-            return Ok(Stmt::Assignment(var, expr));
-            
-        } else {
-            return Err(ParseError::InvalidAssignment);
+// Skip the rest of a broken statement: up to the end of its logical line,
+// including any indented block that follows it. Stops before the Dedent that
+// closes the enclosing block so the block still ends normally
+fn synchronize(tokens: &[SpannedToken], position: &mut usize) {
+    let start = *position;
+    let mut depth = 0;
+
+    loop {
+        match current_token(tokens, *position) {
+            Token::EOF => break,
+            Token::Dedent if depth == 0 => break,
+            Token::Dedent => {
+                depth -= 1;
+                advance(position);
+                if depth == 0 {
+                    break;
+                }
+                continue;
+            }
+            Token::Indent => depth += 1,
+            Token::Newline if depth == 0 => {
+                advance(position);
+                if let Token::Indent = current_token(tokens, *position) {
+                    continue;
+                }
+                break;
+            }
+            _ => {}
         }
-    } 
-    
-    Err(ParseError::InvalidIdentifier)  
-}
+        advance(position);
+    }
 
-pub fn parse_comparison(tokens: &[Token], position: &mut usize) -> Result<Expression, ParseError> {
-    let mut expr = parse_factor(tokens, position)?;
+    // Always make progress so recovery cannot loop forever
+    if *position == start && *position < tokens.len() {
+        advance(position);
+    }
+}
 
-    while let Token::Operator(op) = current_token(tokens, *position) {
-        let operator = match op.as_str() {
+// Parse one statement, on failure record the error, skip the statement and
+// return a Stmt::Error in its place
+fn parse_statement_or_recover(tokens: &[SpannedToken], position: &mut usize, errors: &mut Vec<ParseError>) -> Located<Stmt> {
+    let start = *position;
+    match parse_statement(tokens, position, errors) {
+        Ok(stmt) => stmt,
+        Err(e) => {
+            errors.push(e);
+            synchronize(tokens, position);
+            located(tokens, start, *position, Stmt::Error)
+        }
+    }
+}
+
+// Replace an expression that failed to parse with Expression::Error, recording
+// the error and skipping to the end of the line
+fn recover_expression(result: Result<Located<Expression>, ParseError>, tokens: &[SpannedToken], position: &mut usize, errors: &mut Vec<ParseError>) -> Located<Expression> {
+    match result {
+        Ok(expr) => expr,
+        Err(e) => {
+            // The placeholder covers everything from the error to the end of the line
+            let mut span = e.span;
+            errors.push(e);
+            while !matches!(current_token(tokens, *position), Token::Newline | Token::Dedent | Token::EOF) {
+                span = span.to(current_span(tokens, *position));
+                advance(position);
+            }
+            Located::new(Expression::Error, span)
+        }
+    }
+}
+
+// The right hand side of an assignment, which may also be a yield expression
+fn parse_assigned_value(tokens: &[SpannedToken], position: &mut usize) -> Result<Located<Expression>, ParseError> {
+    if is_keyword(tokens, *position, "yield") {
+        parse_yield(tokens, position)
+    } else {
+        parse_expression(tokens, position)
+    }
+}
+
+fn parse_assignment(tokens: &[SpannedToken], position: &mut usize, errors: &mut Vec<ParseError>) -> Result<Stmt, ParseError> {
+    
+    if let Expression::Variable(var) = &parse_identifier(tokens, position)?.node {
+        if let Token::Assign = current_token(tokens, *position) {
+            advance(position);
+            let expr = recover_expression(parse_assigned_value(tokens, position), tokens, position, errors);
+            return Ok(Stmt::Assignment(var.clone(), expr));
+            
+        } else {
+            return Err(unexpected(tokens, *position, &["'='"]));
+        }
+    } 
+    
+    Err(unexpected(tokens, *position, &["an identifier"]))
+}
+
+pub fn parse_comparison(tokens: &[SpannedToken], position: &mut usize) -> Result<Located<Expression>, ParseError> {
+    let start = *position;
+    let left = parse_add_sub(tokens, position)?;
+    let mut rest = Vec::new();
+
+    while let Token::Operator(op) = current_token(tokens, *position) {
+        let operator = match op.as_ref() {
             "==" => CompareOperator::Equal,
             "!=" => CompareOperator::NotEqual,
             "<" => CompareOperator::LessThan,
@@ -208,19 +1083,26 @@ pub fn parse_comparison(tokens: &[Token], position: &mut usize) -> Result<Expres
         };
 
         advance(position);
-
-        let right = parse_factor(tokens, position)?;
-        expr = Expression::CompareOp(Box::new(expr), operator, Box::new(right));
+        rest.push((operator, parse_add_sub(tokens, position)?));
     }
 
-    Ok(expr)
+    let node = match rest.len() {
+        0 => return Ok(left),
+        1 => {
+            let (operator, right) = rest.remove(0);
+            Expression::CompareOp(Box::new(left), operator, Box::new(right))
+        }
+        _ => Expression::Compare(Box::new(left), rest),
+    };
+    Ok(located(tokens, start, *position, node))
 }
 
-pub fn parse_boolean(tokens: &[Token], position: &mut usize) -> Result<Expression, ParseError> {
-    let mut expr = parse_add_sub(tokens, position)?;
+pub fn parse_boolean(tokens: &[SpannedToken], position: &mut usize) -> Result<Located<Expression>, ParseError> {
+    let start = *position;
+    let mut expr = parse_comparison(tokens, position)?;
 
     while let Token::Operator(op) = current_token(tokens, *position) {
-        let operator = match op.as_str() {
+        let operator = match op.as_ref() {
             "&&" => CompareOperator::And,
             "||" => CompareOperator::Or,
             _ => break,
@@ -228,29 +1110,1354 @@ pub fn parse_boolean(tokens: &[Token], position: &mut usize) -> Result<Expressio
 
         advance(position);
 
-        let right = parse_add_sub(tokens, position)?;
-        expr = Expression::CompareOp(Box::new(expr), operator, Box::new(right));
+        let right = parse_comparison(tokens, position)?;
+        expr = located(tokens, start, *position, Expression::CompareOp(Box::new(expr), operator, Box::new(right)));
     }
 
     Ok(expr)
 }
 
-pub fn parse_program(tokens: &[Token]) -> Result<Program, ParseError> {
+// raise, raise exc, raise exc from cause
+fn parse_raise(tokens: &[SpannedToken], position: &mut usize) -> Result<Stmt, ParseError> {
+    expect_keyword(tokens, position, "raise")?;
+
+    let mut exc = None;
+    let mut cause = None;
+
+    if !matches!(current_token(tokens, *position), Token::Newline | Token::Dedent | Token::EOF) {
+        exc = Some(parse_expression(tokens, position)?);
+
+        if is_keyword(tokens, *position, "from") {
+            advance(position);
+            cause = Some(parse_expression(tokens, position)?);
+        }
+    }
+
+    Ok(Stmt::Raise { exc, cause })
+}
+
+// Parse the `:` and suite of a compound statement, either an indented block
+// or a single statement on the same line
+fn parse_block(tokens: &[SpannedToken], position: &mut usize, errors: &mut Vec<ParseError>) -> Result<Vec<Located<Stmt>>, ParseError> {
+    expect_token(tokens, position, Token::Colon, "':'")?;
+
+    if !matches!(current_token(tokens, *position), Token::Newline) {
+        return Ok(vec![parse_statement_or_recover(tokens, position, errors)]);
+    }
+    advance(position);
+    expect_token(tokens, position, Token::Indent, "an indented block")?;
+    let _nesting = nest(tokens, *position)?;
+
+    let mut body = Vec::new();
+    loop {
+        match current_token(tokens, *position) {
+            Token::Dedent => {
+                advance(position);
+                break;
+            }
+            Token::EOF => break,
+            _ => body.push(parse_statement_or_recover(tokens, position, errors)),
+        }
+    }
+
+    Ok(body)
+}
+
+fn parse_try(tokens: &[SpannedToken], position: &mut usize, errors: &mut Vec<ParseError>) -> Result<Stmt, ParseError> {
+    expect_keyword(tokens, position, "try")?;
+    let body = parse_block(tokens, position, errors)?;
+
+    let mut handlers: Vec<Located<ExceptHandler>> = Vec::new();
+    let mut star = false;
+
+    while is_keyword(tokens, *position, "except") {
+        let start = *position;
+        advance(position);
+
+        if handlers.last().is_some_and(|h| h.node.exception.is_none()) {
+            return Err(ParseError::new(ParseErrorKind::DefaultExceptNotLast, current_span(tokens, start)));
+        }
+
+        let is_star = matches!(current_token(tokens, *position), Token::Operator(op) if op == "*");
+        if is_star {
+            advance(position);
+        }
+        if !handlers.is_empty() && is_star != star {
+            return Err(ParseError::new(ParseErrorKind::MixedExceptStar, span_since(tokens, start, *position)));
+        }
+        star = is_star;
+
+        let mut exception = None;
+        let mut name = None;
+        // `except*` always needs an exception type
+        if is_star || !matches!(current_token(tokens, *position), Token::Colon) {
+            exception = Some(parse_expression(tokens, position)?);
+            name = parse_asname(tokens, position)?;
+        }
+
+        let body = parse_block(tokens, position, errors)?;
+        handlers.push(located(tokens, start, *position, ExceptHandler { exception, name, body }));
+    }
+
+    let mut orelse = Vec::new();
+    if !handlers.is_empty() && is_keyword(tokens, *position, "else") {
+        advance(position);
+        orelse = parse_block(tokens, position, errors)?;
+    }
+
+    let mut finalbody = Vec::new();
+    if is_keyword(tokens, *position, "finally") {
+        advance(position);
+        finalbody = parse_block(tokens, position, errors)?;
+    }
+
+    // A try needs at least one except or a finally
+    if handlers.is_empty() && finalbody.is_empty() {
+        return Err(unexpected(tokens, *position, &["'except'", "'finally'"]));
+    }
+
+    Ok(Stmt::Try { body, handlers, orelse, finalbody, star })
+}
+
+fn parse_with_item(tokens: &[SpannedToken], position: &mut usize) -> Result<WithItem, ParseError> {
+    let context_expr = parse_expression(tokens, position)?;
+
+    let mut optional_vars = None;
+    if is_keyword(tokens, *position, "as") {
+        advance(position);
+        let target = parse_postfix(tokens, position)?;
+        if !matches!(target.node, Expression::Variable(_) | Expression::Attribute(..) | Expression::Tuple(_)) {
+            return Err(ParseError::new(ParseErrorKind::InvalidAssignment, target.span));
+        }
+        optional_vars = Some(target);
+    }
+
+    Ok(WithItem { context_expr, optional_vars })
+}
+
+// The parenthesized form `with (a as b, c as d,):`
+fn parse_parenthesized_with_items(tokens: &[SpannedToken], position: &mut usize) -> Result<Vec<WithItem>, ParseError> {
+    expect_token(tokens, position, Token::OpenParen, "'('")?;
+
+    let mut items = vec![parse_with_item(tokens, position)?];
+    while let Token::Comma = current_token(tokens, *position) {
+        advance(position);
+        if let Token::CloseParen = current_token(tokens, *position) {
+            break;
+        }
+        items.push(parse_with_item(tokens, position)?);
+    }
+
+    if let (Token::CloseParen, Token::Colon) = (current_token(tokens, *position), current_token(tokens, *position + 1)) {
+        advance(position);
+        Ok(items)
+    } else {
+        Err(unexpected(tokens, *position, &["')'"]))
+    }
+}
+
+fn parse_with(tokens: &[SpannedToken], position: &mut usize, is_async: bool, errors: &mut Vec<ParseError>) -> Result<Stmt, ParseError> {
+    expect_keyword(tokens, position, "with")?;
+
+    // Try the parenthesized form first, otherwise the parentheses belong to
+    // the first context expression, as in `with (yield x) as y:`
+    let start = *position;
+    let items = match parse_parenthesized_with_items(tokens, position) {
+        Ok(items) => items,
+        Err(_) => {
+            *position = start;
+            let mut items = vec![parse_with_item(tokens, position)?];
+            while let Token::Comma = current_token(tokens, *position) {
+                advance(position);
+                items.push(parse_with_item(tokens, position)?);
+            }
+            items
+        }
+    };
+
+    let body = parse_block(tokens, position, errors)?;
+
+    Ok(Stmt::With { items, body, is_async })
+}
+
+// Optional `[T: bound, *Ts, **P]` after a function, class or alias name
+fn parse_type_params(tokens: &[SpannedToken], position: &mut usize) -> Result<Vec<Located<TypeParam>>, ParseError> {
+    let mut type_params = Vec::new();
+
+    if !matches!(current_token(tokens, *position), Token::OpenBracket) {
+        return Ok(type_params);
+    }
+    let open = *position;
+    advance(position);
+
+    while !matches!(current_token(tokens, *position), Token::CloseBracket) {
+        let start = *position;
+        let type_param = if is_operator(tokens, *position, "*") {
+            advance(position);
+            TypeParam::TypeVarTuple(parse_name(tokens, position)?)
+        } else if is_operator(tokens, *position, "**") {
+            advance(position);
+            TypeParam::ParamSpec(parse_name(tokens, position)?)
+        } else {
+            let name = parse_name(tokens, position)?;
+            let mut bound = None;
+            if let Token::Colon = current_token(tokens, *position) {
+                advance(position);
+                bound = Some(parse_expression(tokens, position)?);
+            }
+            TypeParam::TypeVar { name, bound }
+        };
+        type_params.push(located(tokens, start, *position, type_param));
+
+        if let Token::Comma = current_token(tokens, *position) {
+            advance(position);
+        } else {
+            break;
+        }
+    }
+
+    // An empty `[]` is not allowed
+    if type_params.is_empty() {
+        return Err(unexpected(tokens, *position, &["a type parameter"]));
+    }
+
+    expect_closing(tokens, position, open, &["','"])?;
+    Ok(type_params)
+}
+
+// type Alias[T] = dict[str, T]
+fn parse_type_alias(tokens: &[SpannedToken], position: &mut usize, errors: &mut Vec<ParseError>) -> Result<Stmt, ParseError> {
+    advance(position);
+    let name = parse_name(tokens, position)?;
+    let type_params = parse_type_params(tokens, position)?;
+    expect_token(tokens, position, Token::Assign, "'='")?;
+
+    let value = recover_expression(parse_expression(tokens, position), tokens, position, errors);
+
+    Ok(Stmt::TypeAlias { name, type_params, value })
+}
+
+// def name(a, b=1):
+fn parse_function_def(tokens: &[SpannedToken], position: &mut usize, decorator_list: Vec<Located<Expression>>, is_async: bool, errors: &mut Vec<ParseError>) -> Result<Stmt, ParseError> {
+    expect_keyword(tokens, position, "def")?;
+    let name = parse_name(tokens, position)?;
+    let type_params = parse_type_params(tokens, position)?;
+
+    let open = *position;
+    expect_token(tokens, position, Token::OpenParen, "'('")?;
+
+    let mut params = Vec::new();
+    while let Token::Identifier(_) = current_token(tokens, *position) {
+        let start = *position;
+        let name = parse_name(tokens, position)?;
+
+        let mut annotation = None;
+        if let Token::Colon = current_token(tokens, *position) {
+            advance(position);
+            annotation = Some(parse_expression(tokens, position)?);
+        }
+        // As in CPython the default value is not part of the parameter's span
+        let span = span_since(tokens, start, *position);
+
+        let mut default = None;
+        if let Token::Assign = current_token(tokens, *position) {
+            advance(position);
+            default = Some(parse_expression(tokens, position)?);
+        }
+        params.push(Located::new(Parameter { name, annotation, default }, span));
+
+        if let Token::Comma = current_token(tokens, *position) {
+            advance(position);
+        } else {
+            break;
+        }
+    }
+
+    expect_closing(tokens, position, open, &["','"])?;
+
+    let mut returns = None;
+    if let Token::Arrow = current_token(tokens, *position) {
+        advance(position);
+        returns = Some(parse_expression(tokens, position)?);
+    }
+
+    let body = parse_block(tokens, position, errors)?;
+    let is_generator = statements_yield(&body);
+
+    Ok(Stmt::FunctionDef { name, type_params, params, returns, body, decorator_list, is_async, is_generator })
+}
+
+// class Name(Base, mixins.Other):
+fn parse_class_def(tokens: &[SpannedToken], position: &mut usize, decorator_list: Vec<Located<Expression>>, errors: &mut Vec<ParseError>) -> Result<Stmt, ParseError> {
+    expect_keyword(tokens, position, "class")?;
+    let name = parse_name(tokens, position)?;
+    let type_params = parse_type_params(tokens, position)?;
+
+    let mut bases = Vec::new();
+    if let Token::OpenParen = current_token(tokens, *position) {
+        let open = *position;
+        advance(position);
+        while !matches!(current_token(tokens, *position), Token::CloseParen) {
+            bases.push(parse_expression(tokens, position)?);
+
+            if let Token::Comma = current_token(tokens, *position) {
+                advance(position);
+            } else {
+                break;
+            }
+        }
+
+        expect_closing(tokens, position, open, &["','"])?;
+    }
+
+    let body = parse_block(tokens, position, errors)?;
+
+    Ok(Stmt::ClassDef { name, type_params, bases, body, decorator_list })
+}
+
+// One or more `@decorator` lines followed by the function or class they apply to.
+// Any expression is allowed after the `@` (PEP 614). As in CPython the
+// definition's span starts at its `def` or `class`, not at the decorators
+fn parse_decorated(tokens: &[SpannedToken], position: &mut usize, errors: &mut Vec<ParseError>) -> Result<Located<Stmt>, ParseError> {
+    let mut decorator_list = Vec::new();
+
+    while let Token::At = current_token(tokens, *position) {
+        advance(position);
+        decorator_list.push(parse_expression(tokens, position)?);
+        expect_token(tokens, position, Token::Newline, "a newline")?;
+    }
+
+    let start = *position;
+    let stmt = match current_token(tokens, *position) {
+        Token::Keyword(k) if k == "def" => parse_function_def(tokens, position, decorator_list, false, errors)?,
+        Token::Keyword(k) if k == "class" => parse_class_def(tokens, position, decorator_list, errors)?,
+        Token::Keyword(k) if k == "async" => {
+            advance(position);
+            parse_function_def(tokens, position, decorator_list, true, errors)?
+        }
+        _ => return Err(unexpected(tokens, *position, &["'def'", "'class'", "'async'"])),
+    };
+
+    Ok(located(tokens, start, *position, stmt))
+}
+
+fn parse_return(tokens: &[SpannedToken], position: &mut usize, errors: &mut Vec<ParseError>) -> Result<Stmt, ParseError> {
+    expect_keyword(tokens, position, "return")?;
+
+    if matches!(current_token(tokens, *position), Token::Newline | Token::Dedent | Token::EOF) {
+        Ok(Stmt::Return(None))
+    } else {
+        let value = recover_expression(parse_expression(tokens, position), tokens, position, errors);
+        Ok(Stmt::Return(Some(value)))
+    }
+}
+
+// for k, v in items:
+fn parse_for(tokens: &[SpannedToken], position: &mut usize, is_async: bool, errors: &mut Vec<ParseError>) -> Result<Stmt, ParseError> {
+    expect_keyword(tokens, position, "for")?;
+
+    let start = *position;
+    let mut target = parse_postfix(tokens, position)?;
+    if let Token::Comma = current_token(tokens, *position) {
+        let mut targets = vec![target];
+        while let Token::Comma = current_token(tokens, *position) {
+            advance(position);
+            if is_keyword(tokens, *position, "in") {
+                break;
+            }
+            targets.push(parse_postfix(tokens, position)?);
+        }
+        target = located(tokens, start, *position, Expression::Tuple(targets));
+    }
+
+    expect_keyword(tokens, position, "in")?;
+    let iter = parse_expression(tokens, position)?;
+    let body = parse_block(tokens, position, errors)?;
+
+    let mut orelse = Vec::new();
+    if is_keyword(tokens, *position, "else") {
+        advance(position);
+        orelse = parse_block(tokens, position, errors)?;
+    }
+
+    Ok(Stmt::For { target, iter, body, orelse, is_async })
+}
+
+fn is_operator(tokens: &[SpannedToken], position: usize, operator: &str) -> bool {
+    matches!(current_token(tokens, position), Token::Operator(op) if op == operator)
+}
+
+fn is_soft_keyword(tokens: &[SpannedToken], position: usize, keyword: &str) -> bool {
+    matches!(current_token(tokens, position), Token::Identifier(name) if name == keyword)
+}
+
+// Literal patterns, including negative numbers written as `- 1`
+fn parse_literal_pattern(tokens: &[SpannedToken], position: &mut usize) -> Result<Located<Pattern>, ParseError> {
+    let start = *position;
+    let (value, singleton) = match current_token(tokens, *position) {
+        Token::Number(n) => (Expression::Number(*n), false),
+        Token::Invalid(_) => (Expression::Error, false),
+        Token::String(value) => (Expression::String(value.to_string()), false),
+        Token::Boolean(value) => (Expression::Boolean(*value), true),
+        Token::Keyword(k) if k == "None" => (Expression::None, true),
+        Token::Operator(op) if op == "-" => {
+            advance(position);
+            // Only before a number written without its own sign, `- -5` is
+            // not a pattern
+            match current_token(tokens, *position) {
+                Token::Number(n) if *n >= 0 => (Expression::Number(-n), false),
+                Token::Invalid(_) => (Expression::Error, false),
+                _ => return Err(unexpected(tokens, *position, &["a number"])),
+            }
+        }
+        _ => return Err(unexpected(tokens, *position, &["a pattern"])),
+    };
+
+    advance(position);
+    let value = located(tokens, start, *position, value);
+    let pattern = if singleton { Pattern::Singleton(value) } else { Pattern::Value(value) };
+    Ok(located(tokens, start, *position, pattern))
+}
+
+// Capture target of a star or as pattern, `_` binds nothing
+fn parse_capture_name(tokens: &[SpannedToken], position: &mut usize) -> Result<Option<Symbol>, ParseError> {
+    let name = parse_name(tokens, position)?;
+    Ok(if &*name == "_" { None } else { Some(name) })
+}
+
+// Arguments of a class pattern, positional patterns before `attr=pattern`
+fn parse_class_pattern(tokens: &[SpannedToken], position: &mut usize, start: usize, cls: Located<Expression>) -> Result<Located<Pattern>, ParseError> {
+    let open = *position;
+    advance(position);
+
+    let mut patterns = Vec::new();
+    let mut kwd_attrs = Vec::new();
+    let mut kwd_patterns = Vec::new();
+
+    while !matches!(current_token(tokens, *position), Token::CloseParen) {
+        if let (Token::Identifier(attr), Token::Assign) = (current_token(tokens, *position), current_token(tokens, *position + 1)) {
+            // Each attribute may be matched only once
+            if kwd_attrs.iter().any(|kwd: &Symbol| &**kwd == attr) {
+                let kind = ParseErrorKind::RepeatedKeywordPattern(attr.to_string());
+                return Err(ParseError::new(kind, current_span(tokens, *position)));
+            }
+            kwd_attrs.push(intern(attr));
+            advance(position);
+            advance(position);
+            kwd_patterns.push(parse_as_pattern(tokens, position)?);
+        } else if kwd_attrs.is_empty() {
+            patterns.push(parse_as_pattern(tokens, position)?);
+        } else {
+            // Positional patterns may not follow keyword patterns
+            return Err(unexpected(tokens, *position, &["a keyword pattern"]));
+        }
+
+        if let Token::Comma = current_token(tokens, *position) {
+            advance(position);
+        } else {
+            break;
+        }
+    }
+
+    expect_closing(tokens, position, open, &["','"])?;
+
+    Ok(located(tokens, start, *position, Pattern::Class { cls, patterns, kwd_attrs, kwd_patterns }))
+}
+
+// `{"key": pattern, Color.RED: pattern, **rest}`
+fn parse_mapping_pattern(tokens: &[SpannedToken], position: &mut usize) -> Result<Located<Pattern>, ParseError> {
+    let open = *position;
+    advance(position);
+
+    let mut keys = Vec::new();
+    let mut patterns = Vec::new();
+    let mut rest = None;
+
+    while !matches!(current_token(tokens, *position), Token::CloseBrace) {
+        if is_operator(tokens, *position, "**") {
+            let start = *position;
+            advance(position);
+            // `**_` is not allowed, the rest must bind a name
+            let span = current_span(tokens, *position);
+            rest = match parse_capture_name(tokens, position)? {
+                Some(name) => Some(name),
+                None => return Err(ParseError::new(ParseErrorKind::InvalidIdentifier, span)),
+            };
+            // `**rest` must be the last item
+            let end = *position;
+            if let Token::Comma = current_token(tokens, *position) {
+                advance(position);
+            }
+            if !matches!(current_token(tokens, *position), Token::CloseBrace) {
+                return Err(ParseError::new(ParseErrorKind::DoubleStarNotLast, span_since(tokens, start, end)));
+            }
+            break;
+        }
+
+        let key = parse_closed_pattern(tokens, position)?;
+        let key = match key.node {
+            Pattern::Value(expr) | Pattern::Singleton(expr) => expr,
+            _ => {
+                let error = ParseError::new(ParseErrorKind::InvalidIdentifier, key.span);
+                return Err(error.expecting(&["a literal or dotted name"]));
+            }
+        };
+
+        expect_token(tokens, position, Token::Colon, "':'")?;
+
+        keys.push(key);
+        patterns.push(parse_as_pattern(tokens, position)?);
+
+        if let Token::Comma = current_token(tokens, *position) {
+            advance(position);
+        } else {
+            break;
+        }
+    }
+
+    expect_closing(tokens, position, open, &["','"])?;
+
+    Ok(located(tokens, open, *position, Pattern::Mapping { keys, patterns, rest }))
+}
+
+// Items of a `[...]` or `(...)` sequence pattern up to the bracket closing
+// the one at open
+fn parse_sequence_items(tokens: &[SpannedToken], position: &mut usize, open: usize, mut items: Vec<Located<Pattern>>) -> Result<Located<Pattern>, ParseError> {
+    let close = match current_token(tokens, open) {
+        Token::OpenBracket => Token::CloseBracket,
+        _ => Token::CloseParen,
+    };
+
+    while *current_token(tokens, *position) != close {
+        items.push(parse_maybe_star_pattern(tokens, position)?);
+
+        if let Token::Comma = current_token(tokens, *position) {
+            advance(position);
+        } else {
+            break;
+        }
+    }
+
+    expect_closing(tokens, position, open, &["','"])?;
+    Ok(located(tokens, open, *position, Pattern::Sequence(items)))
+}
+
+fn parse_closed_pattern(tokens: &[SpannedToken], position: &mut usize) -> Result<Located<Pattern>, ParseError> {
+    let start = *position;
+    let _nesting = nest(tokens, start)?;
+    match current_token(tokens, *position) {
+        Token::Identifier(name) => {
+            advance(position);
+            let mut value = located(tokens, start, *position, Expression::Variable(intern(name)));
+            let mut dotted = false;
+
+            while let Token::Dot = current_token(tokens, *position) {
+                advance(position);
+                let attr = parse_name(tokens, position)?;
+                value = located(tokens, start, *position, Expression::Attribute(Box::new(value), attr));
+                dotted = true;
+            }
+
+            let pattern = if let Token::OpenParen = current_token(tokens, *position) {
+                return parse_class_pattern(tokens, position, start, value);
+            } else if dotted {
+                Pattern::Value(value)
+            } else if name == "_" {
+                Pattern::As(None, None)
+            } else {
+                Pattern::As(None, Some(intern(name)))
+            };
+            Ok(located(tokens, start, *position, pattern))
+        }
+        Token::OpenParen => {
+            let open = *position;
+            advance(position);
+            if let Token::CloseParen = current_token(tokens, *position) {
+                advance(position);
+                return Ok(located(tokens, start, *position, Pattern::Sequence(Vec::new())));
+            }
+
+            let first = parse_maybe_star_pattern(tokens, position)?;
+
+            // A group pattern `(p)` is just p, a comma makes it a sequence
+            if let Token::Comma = current_token(tokens, *position) {
+                advance(position);
+                return parse_sequence_items(tokens, position, open, vec![first]);
+            }
+
+            if matches!(first.node, Pattern::Star(_)) {
+                return Err(unexpected(tokens, *position, &["','"]));
+            }
+
+            expect_closing(tokens, position, open, &["','"])?;
+            Ok(first)
+        }
+        Token::OpenBracket => {
+            let open = *position;
+            advance(position);
+            parse_sequence_items(tokens, position, open, Vec::new())
+        }
+        Token::OpenBrace => parse_mapping_pattern(tokens, position),
+        _ => parse_literal_pattern(tokens, position),
+    }
+}
+
+// `a | b | c`
+fn parse_or_pattern(tokens: &[SpannedToken], position: &mut usize) -> Result<Located<Pattern>, ParseError> {
+    let start = *position;
+    let mut alternatives = vec![parse_closed_pattern(tokens, position)?];
+
+    while is_operator(tokens, *position, "|") {
+        advance(position);
+        alternatives.push(parse_closed_pattern(tokens, position)?);
+    }
+
+    if alternatives.len() == 1 {
+        return Ok(alternatives.remove(0));
+    }
+
+    // Only the last alternative may match anything
+    for alternative in &alternatives[..alternatives.len() - 1] {
+        if let Some(pattern) = irrefutable(&alternative.node) {
+            return Err(ParseError::new(ParseErrorKind::UnreachablePatterns(pattern), alternative.span));
+        }
+    }
+
+    // Every alternative must bind the same names
+    let names = bound_names(&alternatives[0].node);
+    if alternatives[1..].iter().any(|alternative| bound_names(&alternative.node) != names) {
+        return Err(ParseError::new(ParseErrorKind::AlternativesBindDifferentNames, span_since(tokens, start, *position)));
+    }
+
+    Ok(located(tokens, start, *position, Pattern::Or(alternatives)))
+}
+
+// How a pattern that matches any subject is described in errors, None when
+// the pattern can fail
+fn irrefutable(pattern: &Pattern) -> Option<String> {
+    match pattern {
+        Pattern::As(None, None) => Some("wildcard".to_string()),
+        Pattern::As(None, Some(name)) => Some(format!("name capture '{}'", name)),
+        Pattern::As(Some(pattern), _) => irrefutable(&pattern.node),
+        Pattern::Or(alternatives) => alternatives.last().and_then(|alternative| irrefutable(&alternative.node)),
+        _ => None,
+    }
+}
+
+// Names a pattern captures when it matches
+fn bound_names(pattern: &Pattern) -> HashSet<Symbol> {
+    let mut names = HashSet::new();
+    let mut pending = vec![pattern];
+    while let Some(pattern) = pending.pop() {
+        match pattern {
+            Pattern::Value(_) | Pattern::Singleton(_) => {}
+            Pattern::Sequence(items) => pending.extend(items.iter().map(|item| &item.node)),
+            // Alternatives bind the same names, checked as they are parsed
+            Pattern::Or(alternatives) => pending.extend(alternatives.first().map(|alternative| &alternative.node)),
+            Pattern::Mapping { patterns, rest, .. } => {
+                pending.extend(patterns.iter().map(|pattern| &pattern.node));
+                names.extend(rest.clone());
+            }
+            Pattern::Class { patterns, kwd_patterns, .. } => {
+                pending.extend(patterns.iter().chain(kwd_patterns).map(|pattern| &pattern.node));
+            }
+            Pattern::Star(name) => names.extend(name.clone()),
+            Pattern::As(pattern, name) => {
+                pending.extend(pattern.as_deref().map(|pattern| &pattern.node));
+                names.extend(name.clone());
+            }
+        }
+    }
+    names
+}
+
+fn parse_as_pattern(tokens: &[SpannedToken], position: &mut usize) -> Result<Located<Pattern>, ParseError> {
+    let start = *position;
+    let pattern = parse_or_pattern(tokens, position)?;
+
+    if is_keyword(tokens, *position, "as") {
+        advance(position);
+        // `as _` is not allowed, the target must bind a name
+        let span = current_span(tokens, *position);
+        match parse_capture_name(tokens, position)? {
+            Some(name) => Ok(located(tokens, start, *position, Pattern::As(Some(Box::new(pattern)), Some(name)))),
+            None => Err(ParseError::new(ParseErrorKind::InvalidIdentifier, span)),
+        }
+    } else {
+        Ok(pattern)
+    }
+}
+
+fn parse_maybe_star_pattern(tokens: &[SpannedToken], position: &mut usize) -> Result<Located<Pattern>, ParseError> {
+    if is_operator(tokens, *position, "*") {
+        let start = *position;
+        advance(position);
+        let name = parse_capture_name(tokens, position)?;
+        Ok(located(tokens, start, *position, Pattern::Star(name)))
+    } else {
+        parse_as_pattern(tokens, position)
+    }
+}
+
+// The pattern of a case, where `case a, *b:` is an open sequence
+fn parse_case_pattern(tokens: &[SpannedToken], position: &mut usize) -> Result<Located<Pattern>, ParseError> {
+    let start = *position;
+    let first = parse_maybe_star_pattern(tokens, position)?;
 
+    if !matches!(current_token(tokens, *position), Token::Comma) {
+        if matches!(first.node, Pattern::Star(_)) {
+            return Err(unexpected(tokens, *position, &["','"]));
+        }
+        return Ok(first);
+    }
+
+    let mut items = vec![first];
+    while let Token::Comma = current_token(tokens, *position) {
+        advance(position);
+        if matches!(current_token(tokens, *position), Token::Colon) || is_keyword(tokens, *position, "if") {
+            break;
+        }
+        items.push(parse_maybe_star_pattern(tokens, position)?);
+    }
+
+    Ok(located(tokens, start, *position, Pattern::Sequence(items)))
+}
+
+// Whether the statement at position is `match subject:` followed by an
+// indented `case`, from its tokens alone. Newlines inside brackets are not
+// tokens, so the header ends at the first one
+fn starts_match(tokens: &[SpannedToken], position: usize) -> bool {
+    let mut end = position + 1;
+    while !matches!(current_token(tokens, end), Token::Newline | Token::EOF) {
+        end += 1;
+    }
+
+    end > position + 2
+        && matches!(current_token(tokens, end - 1), Token::Colon)
+        && matches!(current_token(tokens, end + 1), Token::Indent)
+        && is_soft_keyword(tokens, end + 2, "case")
+}
+
+// The subject of `match subject:`, a tuple when there are commas
+fn parse_match_subject(tokens: &[SpannedToken], position: &mut usize) -> Result<Located<Expression>, ParseError> {
+    let start = *position;
+    let subject = parse_expression(tokens, position)?;
+    if !matches!(current_token(tokens, *position), Token::Comma) {
+        return Ok(subject);
+    }
+
+    let mut elements = vec![subject];
+    while let Token::Comma = current_token(tokens, *position) {
+        advance(position);
+        if let Token::Colon = current_token(tokens, *position) {
+            break;
+        }
+        elements.push(parse_expression(tokens, position)?);
+    }
+    Ok(located(tokens, start, *position, Expression::Tuple(elements)))
+}
+
+fn parse_match(tokens: &[SpannedToken], position: &mut usize, errors: &mut Vec<ParseError>) -> Result<Stmt, ParseError> {
+    advance(position);
+    let subject = parse_match_subject(tokens, position)?;
+
+    expect_token(tokens, position, Token::Colon, "':'")?;
+    expect_token(tokens, position, Token::Newline, "a newline")?;
+    expect_token(tokens, position, Token::Indent, "an indented block")?;
+
+    let mut cases = Vec::new();
+    while is_soft_keyword(tokens, *position, "case") {
+        advance(position);
+        let pattern = parse_case_pattern(tokens, position)?;
+
+        let mut guard = None;
+        if is_keyword(tokens, *position, "if") {
+            advance(position);
+            guard = Some(parse_expression(tokens, position)?);
+        }
+
+        let body = parse_block(tokens, position, errors)?;
+        cases.push(MatchCase { pattern, guard, body });
+    }
+
+    if cases.is_empty() {
+        return Err(unexpected(tokens, *position, &["'case'"]));
+    }
+
+    // Only the last case may match anything without a guard
+    for case in &cases[..cases.len() - 1] {
+        if let (None, Some(pattern)) = (&case.guard, irrefutable(&case.pattern.node)) {
+            return Err(ParseError::new(ParseErrorKind::UnreachablePatterns(pattern), case.pattern.span));
+        }
+    }
+
+    match current_token(tokens, *position) {
+        Token::Dedent => advance(position),
+        Token::EOF => {}
+        _ => return Err(unexpected(tokens, *position, &["'case'"])),
+    }
+
+    Ok(Stmt::Match { subject, cases })
+}
+
+// x: int = 1
+fn parse_annotated_assignment(tokens: &[SpannedToken], position: &mut usize, errors: &mut Vec<ParseError>) -> Result<Stmt, ParseError> {
+    let target = parse_name(tokens, position)?;
+    advance(position);
+    let annotation = parse_expression(tokens, position)?;
+
+    let mut value = None;
+    if let Token::Assign = current_token(tokens, *position) {
+        advance(position);
+        value = Some(recover_expression(parse_assigned_value(tokens, position), tokens, position, errors));
+    }
+
+    Ok(Stmt::AnnAssign { target, annotation, value })
+}
+
+// Expression statements such as calls, or an assignment to a name
+fn parse_expression_statement(tokens: &[SpannedToken], position: &mut usize, errors: &mut Vec<ParseError>) -> Result<Stmt, ParseError> {
+    match (current_token(tokens, *position), current_token(tokens, *position + 1)) {
+        (Token::Identifier(_), Token::Assign) => return parse_assignment(tokens, position, errors),
+        (Token::Identifier(_), Token::Colon) => return parse_annotated_assignment(tokens, position, errors),
+        _ => {}
+    }
+
+    let expr = if is_keyword(tokens, *position, "yield") {
+        parse_yield(tokens, position)?
+    } else {
+        parse_expression(tokens, position)?
+    };
+
+    if let Token::Assign = current_token(tokens, *position) {
+        return Err(ParseError::new(ParseErrorKind::InvalidAssignment, expr.span));
+    }
+
+    Ok(Stmt::Expr(expr))
+}
+
+fn parse_statement(tokens: &[SpannedToken], position: &mut usize, errors: &mut Vec<ParseError>) -> Result<Located<Stmt>, ParseError> {
+    if let Token::At = current_token(tokens, *position) {
+        return parse_decorated(tokens, position, errors);
+    }
+
+    let start = *position;
+    let stmt = parse_undecorated_statement(tokens, position, errors)?;
+    Ok(located(tokens, start, *position, stmt))
+}
+
+fn parse_undecorated_statement(tokens: &[SpannedToken], position: &mut usize, errors: &mut Vec<ParseError>) -> Result<Stmt, ParseError> {
+    let stmt = match current_token(tokens, *position) {
+        Token::Keyword(k) if k == "import" => parse_import(tokens, position)?,
+        Token::Keyword(k) if k == "from" => parse_import_from(tokens, position)?,
+        Token::Keyword(k) if k == "raise" => parse_raise(tokens, position)?,
+        Token::Keyword(k) if k == "return" => parse_return(tokens, position, errors)?,
+        Token::Keyword(k) if k == "pass" => {
+            advance(position);
+            Stmt::Pass
+        }
+        // Compound statements end with their block
+        Token::Keyword(k) if k == "try" => return parse_try(tokens, position, errors),
+        Token::Keyword(k) if k == "with" => return parse_with(tokens, position, false, errors),
+        Token::Keyword(k) if k == "def" => return parse_function_def(tokens, position, Vec::new(), false, errors),
+        Token::Keyword(k) if k == "class" => return parse_class_def(tokens, position, Vec::new(), errors),
+        // match is a soft keyword, `match = 1` and `match(x)` remain ordinary statements
+        Token::Identifier(name) if name == "match" && starts_match(tokens, *position) => return parse_match(tokens, position, errors),
+        // type is a soft keyword too, `type = 1` and `type(x)` are not aliases
+        Token::Identifier(name) if name == "type" && matches!(
+            (current_token(tokens, *position + 1), current_token(tokens, *position + 2)),
+            (Token::Identifier(_), Token::Assign | Token::OpenBracket)
+        ) => parse_type_alias(tokens, position, errors)?,
+        Token::Keyword(k) if k == "for" => return parse_for(tokens, position, false, errors),
+        Token::Keyword(k) if k == "async" => {
+            advance(position);
+            return match current_token(tokens, *position) {
+                Token::Keyword(k) if k == "with" => parse_with(tokens, position, true, errors),
+                Token::Keyword(k) if k == "def" => parse_function_def(tokens, position, Vec::new(), true, errors),
+                Token::Keyword(k) if k == "for" => parse_for(tokens, position, true, errors),
+                _ => Err(unexpected(tokens, *position, &["'def'", "'with'", "'for'"])),
+            };
+        }
+        _ => parse_expression_statement(tokens, position, errors)?,
+    };
+
+    // Simple statements end at a newline, the end of a block or the end of input
+    match current_token(tokens, *position) {
+        Token::Newline => advance(position),
+        Token::Dedent | Token::EOF => {}
+        _ => return Err(unexpected(tokens, *position, &[])),
+    }
+
+    Ok(stmt)
+}
+
+// The kind of code a statement belongs to, for the checks CPython's compiler
+// applies after parsing
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scope {
+    Module,
+    Function,
+    AsyncFunction,
+    AsyncGenerator,
+}
+
+impl Scope {
+    fn is_async(self) -> bool {
+        matches!(self, Scope::AsyncFunction | Scope::AsyncGenerator)
+    }
+}
+
+// The left operand of `a + b`, `a < b`, `a.b`, `a[b]` or `a(b)`. Chains of these
+// like `1 + 1 + ...` are not limited in length, so they are walked in a loop
+// rather than recursively
+fn left_operand(expr: &Located<Expression>) -> Option<&Located<Expression>> {
+    match &expr.node {
+        Expression::BinaryOp(left, _, _)
+        | Expression::CompareOp(left, _, _)
+        | Expression::Compare(left, _)
+        | Expression::Attribute(left, _)
+        | Expression::Subscript(left, _)
+        | Expression::Call(left, _) => Some(left),
+        _ => None,
+    }
+}
+
+// `await`, `async for` and `async with` are only valid directly inside an
+// `async def`, `yield` only inside a function
+fn check_expression(expr: &Located<Expression>, scope: Scope, errors: &mut Vec<ParseError>) {
+    // Down the chain to its first operand, then back up checking the other
+    // operands, so errors come out in source order
+    let mut chain = Vec::new();
+    let mut expr = expr;
+    while let Some(left) = left_operand(expr) {
+        chain.push(expr);
+        expr = left;
+    }
+
+    for expr in std::iter::once(expr).chain(chain.into_iter().rev()) {
+        check_operands(expr, scope, errors);
+    }
+}
+
+// Checks an expression apart from its left operand
+fn check_operands(expr: &Located<Expression>, scope: Scope, errors: &mut Vec<ParseError>) {
+    match &expr.node {
+        Expression::Await(value) => {
+            if !scope.is_async() {
+                errors.push(ParseError::new(ParseErrorKind::OutsideAsyncFunction("await".to_string()), expr.span));
+            }
+            check_expression(value, scope, errors)
+        }
+        Expression::Yield(value) => {
+            if scope == Scope::Module {
+                errors.push(ParseError::new(ParseErrorKind::OutsideFunction("yield".to_string()), expr.span));
+            }
+            value.iter().for_each(|e| check_expression(e, scope, errors))
+        }
+        Expression::YieldFrom(value) => {
+            if scope == Scope::Module {
+                errors.push(ParseError::new(ParseErrorKind::OutsideFunction("yield from".to_string()), expr.span));
+            }
+            if scope.is_async() {
+                errors.push(ParseError::new(ParseErrorKind::YieldFromInsideAsyncFunction, expr.span));
+            }
+            check_expression(value, scope, errors)
+        }
+        Expression::BinaryOp(_, _, right) | Expression::CompareOp(_, _, right) | Expression::Subscript(_, right) => {
+            check_expression(right, scope, errors)
+        }
+        Expression::Compare(_, rest) => rest.iter().for_each(|(_, e)| check_expression(e, scope, errors)),
+        Expression::Tuple(elements) | Expression::Call(_, elements) => {
+            elements.iter().for_each(|e| check_expression(e, scope, errors))
+        }
+        Expression::Attribute(..) | Expression::Number(_) | Expression::String(_) | Expression::Variable(_) | Expression::Boolean(_) | Expression::None | Expression::Error => {}
+    }
+}
+
+fn check_type_params(type_params: &[Located<TypeParam>], scope: Scope, errors: &mut Vec<ParseError>) {
+    for type_param in type_params {
+        if let TypeParam::TypeVar { bound: Some(bound), .. } = &type_param.node {
+            check_expression(bound, scope, errors);
+        }
+    }
+}
+
+fn check_statements(statements: &[Located<Stmt>], scope: Scope, errors: &mut Vec<ParseError>) {
+    for stmt in statements {
+        match &stmt.node {
+            Stmt::Assignment(_, value) | Stmt::Expr(value) => check_expression(value, scope, errors),
+            Stmt::AnnAssign { annotation, value, .. } => {
+                check_expression(annotation, scope, errors);
+                if let Some(value) = value {
+                    check_expression(value, scope, errors);
+                }
+            }
+            Stmt::TypeAlias { type_params, value, .. } => {
+                check_type_params(type_params, scope, errors);
+                check_expression(value, scope, errors);
+            }
+            Stmt::Error | Stmt::Pass | Stmt::Import(_) | Stmt::ImportFrom { .. } => {}
+            Stmt::Return(value) => {
+                if let Some(value) = value {
+                    if scope == Scope::AsyncGenerator {
+                        errors.push(ParseError::new(ParseErrorKind::ReturnInsideAsyncGenerator, stmt.span));
+                    }
+                    check_expression(value, scope, errors);
+                }
+            }
+            Stmt::Raise { exc, cause } => {
+                for expr in exc.iter().chain(cause) {
+                    check_expression(expr, scope, errors);
+                }
+            }
+            Stmt::Try { body, handlers, orelse, finalbody, .. } => {
+                check_statements(body, scope, errors);
+                for handler in handlers {
+                    if let Some(exception) = &handler.node.exception {
+                        check_expression(exception, scope, errors);
+                    }
+                    check_statements(&handler.node.body, scope, errors);
+                }
+                check_statements(orelse, scope, errors);
+                check_statements(finalbody, scope, errors);
+            }
+            Stmt::With { items, body, is_async } => {
+                if *is_async && !scope.is_async() {
+                    errors.push(ParseError::new(ParseErrorKind::OutsideAsyncFunction("async with".to_string()), stmt.span));
+                }
+                for item in items {
+                    for expr in iter::once(&item.context_expr).chain(&item.optional_vars) {
+                        check_expression(expr, scope, errors);
+                    }
+                }
+                check_statements(body, scope, errors);
+            }
+            Stmt::FunctionDef { type_params, params, returns, body, decorator_list, is_async, is_generator, .. } => {
+                // Decorators, annotations and defaults are evaluated in the enclosing scope
+                for decorator in decorator_list {
+                    check_expression(decorator, scope, errors);
+                }
+                check_type_params(type_params, scope, errors);
+                for param in params {
+                    for expr in param.node.annotation.iter().chain(&param.node.default) {
+                        check_expression(expr, scope, errors);
+                    }
+                }
+                if let Some(returns) = returns {
+                    check_expression(returns, scope, errors);
+                }
+                let inner = match (*is_async, *is_generator) {
+                    (false, _) => Scope::Function,
+                    (true, false) => Scope::AsyncFunction,
+                    (true, true) => Scope::AsyncGenerator,
+                };
+                check_statements(body, inner, errors);
+            }
+            Stmt::ClassDef { type_params, bases, body, decorator_list, .. } => {
+                for expr in decorator_list.iter().chain(bases) {
+                    check_expression(expr, scope, errors);
+                }
+                check_type_params(type_params, scope, errors);
+                // A class body is not a function, even inside one
+                check_statements(body, Scope::Module, errors);
+            }
+            Stmt::Match { subject, cases } => {
+                check_expression(subject, scope, errors);
+                for case in cases {
+                    if let Some(guard) = &case.guard {
+                        check_expression(guard, scope, errors);
+                    }
+                    check_statements(&case.body, scope, errors);
+                }
+            }
+            Stmt::For { target, iter, body, orelse, is_async } => {
+                if *is_async && !scope.is_async() {
+                    errors.push(ParseError::new(ParseErrorKind::OutsideAsyncFunction("async for".to_string()), stmt.span));
+                }
+                check_expression(target, scope, errors);
+                check_expression(iter, scope, errors);
+                check_statements(body, scope, errors);
+                check_statements(orelse, scope, errors);
+            }
+        }
+    }
+}
+
+// Whether an expression yields, which makes the enclosing function a generator
+fn expression_yields(expr: &Located<Expression>) -> bool {
+    let mut expr = expr;
+    loop {
+        let operands_yield = match &expr.node {
+            Expression::Yield(_) | Expression::YieldFrom(_) => true,
+            Expression::Await(value) => expression_yields(value),
+            Expression::BinaryOp(_, _, right) | Expression::CompareOp(_, _, right) | Expression::Subscript(_, right) => {
+                expression_yields(right)
+            }
+            Expression::Compare(_, rest) => rest.iter().any(|(_, e)| expression_yields(e)),
+            Expression::Tuple(elements) | Expression::Call(_, elements) => elements.iter().any(expression_yields),
+            Expression::Attribute(..) | Expression::Number(_) | Expression::String(_) | Expression::Variable(_) | Expression::Boolean(_) | Expression::None | Expression::Error => false,
+        };
+        if operands_yield {
+            return true;
+        }
+
+        match left_operand(expr) {
+            Some(left) => expr = left,
+            None => return false,
+        }
+    }
+}
+
+// Whether a function body yields, not counting nested function bodies
+fn statements_yield(statements: &[Located<Stmt>]) -> bool {
+    statements.iter().any(|stmt| match &stmt.node {
+        Stmt::Assignment(_, value) | Stmt::Expr(value) | Stmt::TypeAlias { value, .. } => expression_yields(value),
+        Stmt::AnnAssign { annotation, value, .. } => {
+            expression_yields(annotation) || value.as_ref().is_some_and(expression_yields)
+        }
+        Stmt::Error | Stmt::Pass | Stmt::Import(_) | Stmt::ImportFrom { .. } => false,
+        Stmt::Return(value) => value.as_ref().is_some_and(expression_yields),
+        Stmt::Raise { exc, cause } => exc.iter().chain(cause).any(expression_yields),
+        Stmt::Try { body, handlers, orelse, finalbody, .. } => {
+            statements_yield(body)
+                || handlers.iter().any(|h| h.node.exception.as_ref().is_some_and(expression_yields) || statements_yield(&h.node.body))
+                || statements_yield(orelse)
+                || statements_yield(finalbody)
+        }
+        Stmt::With { items, body, .. } => {
+            items.iter().any(|item| iter::once(&item.context_expr).chain(&item.optional_vars).any(expression_yields))
+                || statements_yield(body)
+        }
+        Stmt::FunctionDef { params, returns, decorator_list, .. } => {
+            decorator_list.iter().chain(returns).any(expression_yields)
+                || params.iter().any(|p| p.node.annotation.iter().chain(&p.node.default).any(expression_yields))
+        }
+        Stmt::ClassDef { bases, decorator_list, .. } => {
+            decorator_list.iter().chain(bases).any(expression_yields)
+        }
+        Stmt::Match { subject, cases } => {
+            expression_yields(subject)
+                || cases.iter().any(|c| c.guard.as_ref().is_some_and(expression_yields) || statements_yield(&c.body))
+        }
+        Stmt::For { target, iter, body, orelse, .. } => {
+            expression_yields(target) || expression_yields(iter) || statements_yield(body) || statements_yield(orelse)
+        }
+    })
+}
+
+// Parse a program whose brackets, blocks and patterns nest at most
+// DEFAULT_NESTING_LIMIT deep. Like every parse, it runs on a stack of its own
+// when the thread's has too little left, see parse_program_with_limit
+pub fn parse_program(tokens: &[SpannedToken]) -> Result<Program, ParseError> {
+    parse_program_with_limit(tokens, DEFAULT_NESTING_LIMIT)
+}
+
+// Like parse_program, failing with TooDeeplyNested when brackets, blocks and
+// patterns nest deeper than `limit`, at most MAX_NESTING_LIMIT. A parse needs
+// about 32KB of stack per level of `limit`, and when the thread has less than
+// that left it allocates a stack that big and parses on it. For the default
+// limit that is about 7MB, more than the 2MB a spawned thread gets, so there
+// every parse pays for allocating and freeing it
+pub fn parse_program_with_limit(tokens: &[SpannedToken], limit: usize) -> Result<Program, ParseError> {
+    let (program, errors) = parse_program_recovering_with_limit(tokens, limit);
+
+    match errors.into_iter().next() {
+        Some(e) => Err(e),
+        None => Ok(program),
+    }
+}
+
+// Parse as much of the program as possible. Broken statements are replaced by
+// Stmt::Error and Expression::Error placeholders and every error is returned
+// alongside the partial program. Syntax errors come first, in source order,
+// then those checking the statements finds, such as `yield` outside a
+// function, in source order too
+pub fn parse_program_recovering(tokens: &[SpannedToken]) -> (Program, Vec<ParseError>) {
+    parse_program_recovering_with_limit(tokens, DEFAULT_NESTING_LIMIT)
+}
+
+// parse_program_recovering with the nesting limit of parse_program_with_limit
+pub fn parse_program_recovering_with_limit(tokens: &[SpannedToken], limit: usize) -> (Program, Vec<ParseError>) {
+    in_parse(limit, || parse_statements(tokens))
+}
+
+// Run a parse with its own nesting budget and names, restoring those of the
+// enclosing one afterwards. When the thread has too little stack left for
+// `limit` levels the parse runs on a new stack allocated for it
+fn in_parse<T>(limit: usize, parse: impl FnOnce() -> T) -> T {
+    let limit = limit.min(MAX_NESTING_LIMIT);
+    let stack = parse_stack_size(limit);
+    stacker::maybe_grow(stack, stack, || {
+        let outer_limit = NESTING_LEFT.with(|left| left.replace(limit));
+        let outer_symbols = SYMBOLS.take();
+        let result = parse();
+        NESTING_LEFT.with(|left| left.set(outer_limit));
+        SYMBOLS.set(outer_symbols);
+        result
+    })
+}
+
+fn parse_statements(tokens: &[SpannedToken]) -> (Program, Vec<ParseError>) {
     let mut position = 0; 
     let mut statements = Vec::new();
+    let mut errors = Vec::new();
 
     while position < tokens.len() {
         if let Token::EOF = current_token(tokens, position) {
             break;
         }
 
-        match parse_assignment(tokens, &mut position) {
-            Ok(stmt) => statements.push(stmt),
-            Err(e) => return Err(e),
+        statements.push(parse_statement_or_recover(tokens, &mut position, &mut errors));
+    }
+
+    check_statements(&statements, Scope::Module, &mut errors);
+
+    (Program { statements }, errors)
+}
+
+// Parse straight from a Lexer, one top-level statement at a time, so only the
+// tokens of the statement being parsed are held at once. Gives the same
+// result as parse_program_recovering, failing instead on the first error the
+// tokenizer reports
+pub fn parse_lexer(lexer: Lexer) -> Result<(Program, Vec<ParseError>), TokenizeError> {
+    in_parse(DEFAULT_NESTING_LIMIT, || parse_lexed_statements(lexer))
+}
+
+fn parse_lexed_statements(lexer: Lexer) -> Result<(Program, Vec<ParseError>), TokenizeError> {
+    let mut reader = StatementReader::new(lexer);
+    let mut statements = Vec::new();
+    let mut errors = Vec::new();
+
+    while let Some(statement) = reader.next_statement(&mut errors)? {
+        statements.push(statement);
+    }
+
+    check_statements(&statements, Scope::Module, &mut errors);
+
+    Ok((Program { statements }, errors))
+}
+
+// Reads top-level statements one at a time from a stream of tokens, holding on
+// only to the tokens of the statement being parsed
+struct StatementReader<'a, I: Iterator> {
+    tokens: std::iter::Peekable<I>,
+    buffer: Vec<SpannedToken<'a>>,
+    // Whether the last statement read ended a line
+    line_ended: bool,
+}
+
+impl<'a, E, I: Iterator<Item = Result<SpannedToken<'a>, E>>> StatementReader<'a, I> {
+    // The tokens have to start at the start of the input or of a top-level
+    // statement
+    fn new(tokens: I) -> Self {
+        StatementReader { tokens: tokens.peekable(), buffer: Vec::new(), line_ended: true }
+    }
+
+    // Where the next statement starts, None at the end of input
+    fn next_start(&mut self) -> Result<Option<Position>, E> {
+        if self.buffer.is_empty() {
+            if let Some(token) = self.tokens.next() {
+                self.buffer.push(token?);
+            }
         }
+        Ok(self.buffer.first().filter(|t| t.token != Token::EOF).map(|t| t.span.start))
     }
 
-    Ok(Program { statements })
-    
+    // Whether the next statement starts a line with no bracket or block open,
+    // so lexing can start over from there
+    fn at_clean_start(&mut self) -> Result<bool, E> {
+        self.next_start()?;
+        Ok(self.line_ended && self.buffer.first().is_some_and(|t| {
+            t.span.start.column == 0 && !matches!(t.token, Token::Newline | Token::Indent | Token::Dedent | Token::EOF)
+        }))
+    }
+
+    // The next statement, None at the end of input. Errors parsing it are added
+    // to `errors`
+    fn next_statement(&mut self, errors: &mut Vec<ParseError>) -> Result<Option<Located<Stmt>>, E> {
+        let mut end = StatementEnd::default();
+        let (statement, position) = loop {
+            while !end.found(&self.buffer) {
+                match self.tokens.next() {
+                    Some(token) => self.buffer.push(token?),
+                    None => break,
+                }
+            }
+
+            let mut position = 0;
+            let mut statement_errors = Vec::new();
+            let statement = match current_token(&self.buffer, 0) {
+                Token::EOF => None,
+                _ => Some(parse_statement_or_recover(&self.buffer, &mut position, &mut statement_errors)),
+            };
+
+            // Skipping a broken statement can run on into the next one, in
+            // which case parse again with that one read in as well
+            let at_eof = self.buffer.last().is_some_and(|t| t.token == Token::EOF);
+            if position < self.buffer.len() || at_eof || self.tokens.peek().is_none() {
+                errors.append(&mut statement_errors);
+                break (statement, position);
+            }
+        };
+
+        let position = position.min(self.buffer.len());
+        if position > 0 {
+            self.line_ended = matches!(self.buffer[position - 1].token, Token::Newline | Token::Dedent);
+        }
+        self.buffer.drain(..position);
+        Ok(statement)
+    }
+}
+
+// Scans a growing buffer of tokens for the end of the top-level statement at
+// its start, picking up where the last scan stopped
+#[derive(Default)]
+struct StatementEnd {
+    scanned: usize,
+    depth: usize,
+    decorators: bool,
+}
+
+impl StatementEnd {
+    // Whether the tokens hold the whole statement and the token after it,
+    // which is as far as parsing the statement looks
+    fn found(&mut self, tokens: &[SpannedToken]) -> bool {
+        if self.scanned == 0 {
+            self.decorators = matches!(current_token(tokens, 0), Token::At);
+        }
+
+        while self.scanned < tokens.len() {
+            let i = self.scanned;
+            match tokens[i].token {
+                Token::EOF => return true,
+                Token::Indent => self.depth += 1,
+                Token::Dedent => self.depth = self.depth.saturating_sub(1),
+                _ => {}
+            }
+            if self.depth > 0 || !matches!(tokens[i].token, Token::Newline | Token::Dedent) {
+                self.scanned += 1;
+                continue;
+            }
+            if i + 1 == tokens.len() {
+                // Come back to this token once the next one is in, without
+                // counting it twice
+                if let Token::Dedent = tokens[i].token {
+                    self.depth += 1;
+                }
+                return false;
+            }
+            self.scanned += 1;
+
+            // An indented block, a further clause or the definition after a
+            // decorator all belong to the same statement
+            match &tokens[i + 1].token {
+                Token::Indent => {}
+                Token::Keyword(k) if matches!(k.as_ref(), "elif" | "else" | "except" | "finally") => {}
+                next if self.decorators => self.decorators = matches!(next, Token::At),
+                _ => return true,
+            }
+        }
+
+        false
+    }
 }
\ No newline at end of file
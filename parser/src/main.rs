@@ -1,16 +1,57 @@
-use parser::parse_program;
-use tokenizer::tokenize;
-fn main() {
-    let input = "x = False\n y = 1 + 2 - 3 * 4\nz = (1 < 2)";//"x = 1 / 2 ** -3\ny = 4 // 5 ** 2\nz = (2 - 1) + 1";
+use std::io::IsTerminal;
+use std::process::ExitCode;
 
-    let tokens = tokenize(input);
-    println!("Tokens: {:#?}",  tokens);
-    
-    let program = parse_program(&tokens);
-    println!("Parsed Program: {:#?}", program);
+use parser::{parse_lexer, to_python_ast, Diagnostic};
+use tokenizer::{decode_source, Lexer};
 
-}
+// Parse the file given as the first argument, or a small demo program, and
+// report every error. Files are decoded as their coding declaration says.
+// With --json the program is printed as JSON in the shape of Python's ast
+// module
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let json = args.iter().any(|arg| arg == "--json");
+    args.retain(|arg| arg != "--json");
 
+    let (file_name, bytes) = match args.first().cloned() {
+        Some(path) => match std::fs::read(&path) {
+            Ok(bytes) => (path, bytes),
+            Err(e) => {
+                eprintln!("error: cannot read {}: {}", path, e);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => ("<demo>".to_string(), b"x = False\ny = 1 + 2 - 3 * 4\nz = (1 < 2)".to_vec()),
+    };
+    let color = std::io::stderr().is_terminal();
 
+    let input = match decode_source(&bytes) {
+        Ok(input) => input,
+        Err(e) => {
+            let shown = String::from_utf8_lossy(bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(&bytes));
+            eprint!("{}", Diagnostic::from(&e).render(&file_name, &shown, color));
+            return ExitCode::FAILURE;
+        }
+    };
 
+    let (program, errors) = match parse_lexer(Lexer::new(&input)) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprint!("{}", Diagnostic::from(&e).render(&file_name, &input, color));
+            return ExitCode::FAILURE;
+        }
+    };
+    if errors.is_empty() && json {
+        println!("{:#}", to_python_ast(&program));
+        return ExitCode::SUCCESS;
+    }
+    if errors.is_empty() {
+        println!("Parsed Program: {:#?}", program);
+        return ExitCode::SUCCESS;
+    }
 
+    for error in &errors {
+        eprintln!("{}", Diagnostic::from(error).render(&file_name, &input, color));
+    }
+    ExitCode::FAILURE
+}
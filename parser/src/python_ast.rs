@@ -0,0 +1,843 @@
+use serde::de::Error as _;
+use serde_json::{json, Map, Value};
+use tokenizer::{Position, Span};
+
+use crate::{statements_yield, Alias, BinaryOperator, CompareOperator, ExceptHandler, Expression, Located, MatchCase, Parameter, Pattern, Program, Stmt, Symbol, TypeParam, WithItem};
+
+// Programs as JSON shaped like CPython's ast module: every node is an object
+// whose "_type" is the ast class name and whose other keys are the class's
+// fields, e.g. {"_type": "Name", "id": "x", "ctx": {"_type": "Load"}}.
+// Nodes that have a location in CPython also get lineno, col_offset,
+// end_lineno and end_col_offset.
+//
+// Our AST is smaller than CPython's, so a few shapes differ: `&&` and `||`
+// become BoolOp and a negative number becomes UnaryOp(USub, Constant). Error
+// placeholders left by recovery have no ast counterpart and come out as
+// {"_type": "Error"}
+
+type Error = serde_json::Error;
+
+// Builds an object the way json! does, but moves the values into it. json!
+// serializes each value, which copies a Value recursively, and the Value of a
+// long chain is as deep as the chain is long
+macro_rules! fields {
+    ({ $($key:literal: $value:expr),* $(,)? }) => {{
+        let mut fields = Map::new();
+        $(fields.insert($key.to_string(), Value::from($value));)*
+        Value::Object(fields)
+    }};
+}
+
+// The Value nests as deeply as the program's expressions, and serde_json
+// drops, prints and compares Values recursively, so those can overflow the
+// stack for a long chain like `1 + 1 + ...` even though building it does not.
+// python_ast_to_json and python_ast_from_json handle such chains as text
+pub fn to_python_ast(program: &Program) -> Value {
+    fields!({
+        "_type": "Module",
+        "body": statements(&program.statements),
+        "type_ignores": json!([]),
+    })
+}
+
+// Read back a Module written by to_python_ast, or produced by Python from
+// ast nodes of the same kinds. Byte offsets are not part of CPython's ast, so
+// the spans read back have lines and columns but zero offsets
+pub fn from_python_ast(value: &Value) -> Result<Program, Error> {
+    expect_type(value, "Module")?;
+    Ok(Program { statements: list(value, "body", read_stmt)? })
+}
+
+fn node(kind: &str, fields: Value) -> Value {
+    let mut object = Map::new();
+    object.insert("_type".to_string(), json!(kind));
+    if let Value::Object(fields) = fields {
+        object.extend(fields);
+    }
+    Value::Object(object)
+}
+
+fn located_node(kind: &str, span: Span, fields: Value) -> Value {
+    let mut value = node(kind, fields);
+    if let Value::Object(object) = &mut value {
+        object.insert("lineno".to_string(), json!(span.start.line));
+        object.insert("col_offset".to_string(), json!(span.start.column));
+        object.insert("end_lineno".to_string(), json!(span.end.line));
+        object.insert("end_col_offset".to_string(), json!(span.end.column));
+    }
+    value
+}
+
+fn statements(statements: &[Located<Stmt>]) -> Value {
+    statements.iter().map(stmt).collect()
+}
+
+fn expressions(exprs: &[Located<Expression>], ctx: &str) -> Value {
+    exprs.iter().map(|e| expression(e, ctx)).collect()
+}
+
+fn optional_expression(expr: &Option<Located<Expression>>) -> Value {
+    expr.as_ref().map_or(Value::Null, |e| expression(e, "Load"))
+}
+
+// The span of a name written at the start of a statement, as in `x = 1`
+fn leading_name_span(start: Position, name: &str) -> Span {
+    let end = Position { line: start.line, column: start.column + name.len(), offset: start.offset + name.len() };
+    Span::new(start, end)
+}
+
+fn name(id: &str, span: Span, ctx: &str) -> Value {
+    located_node("Name", span, fields!({ "id": id, "ctx": node(ctx, json!({})) }))
+}
+
+fn stmt(stmt: &Located<Stmt>) -> Value {
+    let span = stmt.span;
+
+    match &stmt.node {
+        Stmt::Error => node("Error", json!({})),
+        Stmt::Assignment(target, value) => located_node("Assign", span, fields!({
+            "targets": [name(target, leading_name_span(span.start, target), "Store")],
+            "value": expression(value, "Load"),
+            "type_comment": Value::Null,
+        })),
+        Stmt::Expr(value) => located_node("Expr", span, fields!({ "value": expression(value, "Load") })),
+        Stmt::Pass => located_node("Pass", span, json!({})),
+        Stmt::Import(names) => located_node("Import", span, fields!({ "names": aliases(names) })),
+        Stmt::ImportFrom { module, names, level } => located_node("ImportFrom", span, fields!({
+            "module": json!(module),
+            "names": aliases(names),
+            "level": json!(level),
+        })),
+        Stmt::Raise { exc, cause } => located_node("Raise", span, fields!({
+            "exc": optional_expression(exc),
+            "cause": optional_expression(cause),
+        })),
+        Stmt::Try { body, handlers, orelse, finalbody, star } => located_node(if *star { "TryStar" } else { "Try" }, span, fields!({
+            "body": statements(body),
+            "handlers": handlers.iter().map(except_handler).collect::<Value>(),
+            "orelse": statements(orelse),
+            "finalbody": statements(finalbody),
+        })),
+        Stmt::With { items, body, is_async } => located_node(if *is_async { "AsyncWith" } else { "With" }, span, fields!({
+            "items": items.iter().map(with_item).collect::<Value>(),
+            "body": statements(body),
+            "type_comment": Value::Null,
+        })),
+        Stmt::FunctionDef { name, type_params, params, returns, body, decorator_list, is_async, .. } => {
+            located_node(if *is_async { "AsyncFunctionDef" } else { "FunctionDef" }, span, fields!({
+                "name": json!(name),
+                "args": arguments(params),
+                "body": statements(body),
+                "decorator_list": expressions(decorator_list, "Load"),
+                "returns": optional_expression(returns),
+                "type_comment": Value::Null,
+                "type_params": type_params.iter().map(type_param).collect::<Value>(),
+            }))
+        }
+        Stmt::ClassDef { name, type_params, bases, body, decorator_list } => located_node("ClassDef", span, fields!({
+            "name": json!(name),
+            "bases": expressions(bases, "Load"),
+            "keywords": json!([]),
+            "body": statements(body),
+            "decorator_list": expressions(decorator_list, "Load"),
+            "type_params": type_params.iter().map(type_param).collect::<Value>(),
+        })),
+        Stmt::Return(value) => located_node("Return", span, fields!({ "value": optional_expression(value) })),
+        Stmt::AnnAssign { target, annotation, value } => located_node("AnnAssign", span, fields!({
+            "target": name(target, leading_name_span(span.start, target), "Store"),
+            "annotation": expression(annotation, "Load"),
+            "value": optional_expression(value),
+            "simple": 1,
+        })),
+        Stmt::TypeAlias { name: alias, type_params, value } => {
+            // The name follows `type `, assuming a single space
+            let start = Position { line: span.start.line, column: span.start.column + 5, offset: span.start.offset + 5 };
+            located_node("TypeAlias", span, fields!({
+                "name": name(alias, leading_name_span(start, alias), "Store"),
+                "type_params": type_params.iter().map(type_param).collect::<Value>(),
+                "value": expression(value, "Load"),
+            }))
+        }
+        Stmt::Match { subject, cases } => located_node("Match", span, fields!({
+            "subject": expression(subject, "Load"),
+            "cases": cases.iter().map(match_case).collect::<Value>(),
+        })),
+        Stmt::For { target, iter, body, orelse, is_async } => located_node(if *is_async { "AsyncFor" } else { "For" }, span, fields!({
+            "target": expression(target, "Store"),
+            "iter": expression(iter, "Load"),
+            "body": statements(body),
+            "orelse": statements(orelse),
+            "type_comment": Value::Null,
+        })),
+    }
+}
+
+fn aliases(names: &[Located<Alias>]) -> Value {
+    names.iter().map(|alias| located_node("alias", alias.span, fields!({
+        "name": json!(alias.node.name),
+        "asname": json!(alias.node.asname),
+    }))).collect()
+}
+
+fn except_handler(handler: &Located<ExceptHandler>) -> Value {
+    located_node("ExceptHandler", handler.span, fields!({
+        "type": optional_expression(&handler.node.exception),
+        "name": json!(handler.node.name),
+        "body": statements(&handler.node.body),
+    }))
+}
+
+fn with_item(item: &WithItem) -> Value {
+    node("withitem", fields!({
+        "context_expr": expression(&item.context_expr, "Load"),
+        "optional_vars": item.optional_vars.as_ref().map_or(Value::Null, |e| expression(e, "Store")),
+    }))
+}
+
+// Defaults belong to the last parameters in CPython's arguments node
+fn arguments(params: &[Located<Parameter>]) -> Value {
+    let args: Value = params.iter().map(|param| located_node("arg", param.span, fields!({
+        "arg": json!(param.node.name),
+        "annotation": optional_expression(&param.node.annotation),
+        "type_comment": Value::Null,
+    }))).collect();
+    let defaults: Value = params.iter().filter_map(|param| param.node.default.as_ref()).map(|e| expression(e, "Load")).collect();
+
+    node("arguments", fields!({
+        "posonlyargs": json!([]),
+        "args": args,
+        "vararg": Value::Null,
+        "kwonlyargs": json!([]),
+        "kw_defaults": json!([]),
+        "kwarg": Value::Null,
+        "defaults": defaults,
+    }))
+}
+
+fn type_param(type_param: &Located<TypeParam>) -> Value {
+    match &type_param.node {
+        TypeParam::TypeVar { name, bound } => located_node("TypeVar", type_param.span, fields!({
+            "name": json!(name),
+            "bound": optional_expression(bound),
+        })),
+        TypeParam::TypeVarTuple(name) => located_node("TypeVarTuple", type_param.span, fields!({ "name": json!(name) })),
+        TypeParam::ParamSpec(name) => located_node("ParamSpec", type_param.span, fields!({ "name": json!(name) })),
+    }
+}
+
+fn match_case(case: &MatchCase) -> Value {
+    node("match_case", fields!({
+        "pattern": pattern(&case.pattern),
+        "guard": optional_expression(&case.guard),
+        "body": statements(&case.body),
+    }))
+}
+
+fn binary_operator(operator: &BinaryOperator) -> &'static str {
+    match operator {
+        BinaryOperator::Add => "Add",
+        BinaryOperator::Subtract => "Sub",
+        BinaryOperator::Multiply => "Mult",
+        BinaryOperator::Divide => "Div",
+        BinaryOperator::FloorDivide => "FloorDiv",
+        BinaryOperator::Modulus => "Mod",
+        BinaryOperator::Power => "Pow",
+    }
+}
+
+fn compare_operator(operator: &CompareOperator) -> &'static str {
+    match operator {
+        CompareOperator::Equal => "Eq",
+        CompareOperator::NotEqual => "NotEq",
+        CompareOperator::LessThan => "Lt",
+        CompareOperator::LessEqual => "LtE",
+        CompareOperator::GreaterThan => "Gt",
+        CompareOperator::GreaterEqual => "GtE",
+        CompareOperator::And => "And",
+        CompareOperator::Or => "Or",
+    }
+}
+
+fn is_boolean(operator: &CompareOperator) -> bool {
+    matches!(operator, CompareOperator::And | CompareOperator::Or)
+}
+
+// The operands of a left nested run of `&&` or `||` with the same operator,
+// the first and then the ones after it
+fn bool_operands(expr: &Located<Expression>) -> (&Located<Expression>, Vec<&Located<Expression>>) {
+    let mut rest = Vec::new();
+    let mut first = expr;
+    if let Expression::CompareOp(_, operator, _) = &expr.node {
+        while let Expression::CompareOp(left, op, right) = &first.node {
+            if op != operator {
+                break;
+            }
+            rest.push(right.as_ref());
+            first = left;
+        }
+    }
+    rest.reverse();
+    (first, rest)
+}
+
+// The first operand of an operator or postfix expression, the one its node
+// nests around
+fn first_operand(expr: &Located<Expression>) -> Option<&Located<Expression>> {
+    match &expr.node {
+        Expression::CompareOp(_, operator, _) if is_boolean(operator) => Some(bool_operands(expr).0),
+        Expression::BinaryOp(left, _, _)
+        | Expression::CompareOp(left, _, _)
+        | Expression::Compare(left, _)
+        | Expression::Attribute(left, _)
+        | Expression::Subscript(left, _)
+        | Expression::Call(left, _) => Some(left),
+        _ => None,
+    }
+}
+
+fn constant(value: Value, span: Span) -> Value {
+    located_node("Constant", span, fields!({ "value": value, "kind": Value::Null }))
+}
+
+// Chains like `1 + 1 + ...` nest as deeply as they are long, so they are
+// walked down to their first operand in a loop and built back up from it
+fn expression(expr: &Located<Expression>, ctx: &str) -> Value {
+    let mut chain = Vec::new();
+    let mut first = expr;
+    while let Some(operand) = first_operand(first) {
+        chain.push(first);
+        first = operand;
+    }
+
+    let mut value = operand_free(first, if chain.is_empty() { ctx } else { "Load" });
+    for (i, expr) in chain.into_iter().enumerate().rev() {
+        value = around_first_operand(expr, value, if i == 0 { ctx } else { "Load" });
+    }
+    value
+}
+
+// An expression whose node nests around its first operand, given that
+// operand as JSON
+fn around_first_operand(expr: &Located<Expression>, first: Value, ctx: &str) -> Value {
+    let span = expr.span;
+
+    match &expr.node {
+        Expression::BinaryOp(_, operator, right) => located_node("BinOp", span, fields!({
+            "left": first,
+            "op": node(binary_operator(operator), json!({})),
+            "right": expression(right, "Load"),
+        })),
+        Expression::CompareOp(_, operator, _) if is_boolean(operator) => {
+            let rest = bool_operands(expr).1.into_iter().map(|e| expression(e, "Load"));
+            located_node("BoolOp", span, fields!({
+                "op": node(compare_operator(operator), json!({})),
+                "values": std::iter::once(first).chain(rest).collect::<Value>(),
+            }))
+        }
+        Expression::CompareOp(_, operator, right) => located_node("Compare", span, fields!({
+            "left": first,
+            "ops": [node(compare_operator(operator), json!({}))],
+            "comparators": [expression(right, "Load")],
+        })),
+        Expression::Compare(_, rest) => located_node("Compare", span, fields!({
+            "left": first,
+            "ops": rest.iter().map(|(op, _)| node(compare_operator(op), json!({}))).collect::<Value>(),
+            "comparators": rest.iter().map(|(_, e)| expression(e, "Load")).collect::<Value>(),
+        })),
+        Expression::Attribute(_, attr) => located_node("Attribute", span, fields!({
+            "value": first,
+            "attr": json!(attr),
+            "ctx": node(ctx, json!({})),
+        })),
+        Expression::Subscript(_, index) => located_node("Subscript", span, fields!({
+            "value": first,
+            "slice": expression(index, "Load"),
+            "ctx": node(ctx, json!({})),
+        })),
+        Expression::Call(_, args) => located_node("Call", span, fields!({
+            "func": first,
+            "args": expressions(args, "Load"),
+            "keywords": json!([]),
+        })),
+        _ => unreachable!(),
+    }
+}
+
+// An expression with no first operand to nest around
+fn operand_free(expr: &Located<Expression>, ctx: &str) -> Value {
+    let span = expr.span;
+
+    match &expr.node {
+        Expression::Number(n) if *n < 0 => {
+            // The digits end the literal, whether written `-1` or `- 1`
+            let digits = n.unsigned_abs().to_string().len();
+            let start = Position { line: span.end.line, column: span.end.column.saturating_sub(digits), offset: span.end.offset.saturating_sub(digits) };
+            located_node("UnaryOp", span, fields!({
+                "op": node("USub", json!({})),
+                "operand": constant(json!(n.unsigned_abs()), Span::new(start, span.end)),
+            }))
+        }
+        Expression::Number(n) => constant(json!(n), span),
+        Expression::String(value) => constant(json!(value), span),
+        Expression::Boolean(value) => constant(json!(value), span),
+        Expression::None => constant(Value::Null, span),
+        Expression::Variable(id) => name(id, span, ctx),
+        Expression::Tuple(elements) => located_node("Tuple", span, fields!({
+            "elts": expressions(elements, ctx),
+            "ctx": node(ctx, json!({})),
+        })),
+        Expression::Await(value) => located_node("Await", span, fields!({ "value": expression(value, "Load") })),
+        Expression::Yield(value) => located_node("Yield", span, fields!({
+            "value": value.as_ref().map_or(Value::Null, |e| expression(e, "Load")),
+        })),
+        Expression::YieldFrom(value) => located_node("YieldFrom", span, fields!({ "value": expression(value, "Load") })),
+        Expression::Error => node("Error", json!({})),
+        Expression::BinaryOp(..)
+        | Expression::CompareOp(..)
+        | Expression::Compare(..)
+        | Expression::Attribute(..)
+        | Expression::Subscript(..)
+        | Expression::Call(..) => unreachable!(),
+    }
+}
+
+fn patterns(patterns: &[Located<Pattern>]) -> Value {
+    patterns.iter().map(pattern).collect()
+}
+
+fn pattern(pattern: &Located<Pattern>) -> Value {
+    let span = pattern.span;
+
+    match &pattern.node {
+        Pattern::Value(value) => located_node("MatchValue", span, fields!({ "value": expression(value, "Load") })),
+        // MatchSingleton holds the constant itself rather than a node
+        Pattern::Singleton(value) => {
+            let value = match value.node {
+                Expression::Boolean(value) => json!(value),
+                _ => Value::Null,
+            };
+            located_node("MatchSingleton", span, fields!({ "value": value }))
+        }
+        Pattern::Sequence(items) => located_node("MatchSequence", span, fields!({ "patterns": patterns(items) })),
+        Pattern::Mapping { keys, patterns: values, rest } => located_node("MatchMapping", span, fields!({
+            "keys": expressions(keys, "Load"),
+            "patterns": patterns(values),
+            "rest": json!(rest),
+        })),
+        Pattern::Class { cls, patterns: positional, kwd_attrs, kwd_patterns } => located_node("MatchClass", span, fields!({
+            "cls": expression(cls, "Load"),
+            "patterns": patterns(positional),
+            "kwd_attrs": json!(kwd_attrs),
+            "kwd_patterns": patterns(kwd_patterns),
+        })),
+        Pattern::Star(name) => located_node("MatchStar", span, fields!({ "name": json!(name) })),
+        Pattern::As(inner, name) => located_node("MatchAs", span, fields!({
+            "pattern": inner.as_ref().map_or(Value::Null, |p| self::pattern(p)),
+            "name": json!(name),
+        })),
+        Pattern::Or(alternatives) => located_node("MatchOr", span, fields!({ "patterns": patterns(alternatives) })),
+    }
+}
+
+// Reading back
+
+fn kind(value: &Value) -> Result<&str, Error> {
+    value.get("_type").and_then(Value::as_str).ok_or_else(|| Error::custom(format!("expected an ast node, found {}", value)))
+}
+
+fn expect_type(value: &Value, expected: &str) -> Result<(), Error> {
+    match kind(value)? {
+        found if found == expected => Ok(()),
+        found => Err(Error::custom(format!("expected {}, found {}", expected, found))),
+    }
+}
+
+fn unsupported(value: &Value, what: &str) -> Error {
+    Error::custom(format!("{} are not supported: {}", what, value))
+}
+
+fn field<'a>(value: &'a Value, name: &str) -> Result<&'a Value, Error> {
+    value.get(name).ok_or_else(|| Error::custom(format!("{} has no field {}", kind(value).unwrap_or("node"), name)))
+}
+
+fn string(value: &Value, name: &str) -> Result<Symbol, Error> {
+    field(value, name)?.as_str().map(Symbol::from).ok_or_else(|| Error::custom(format!("field {} should be a string", name)))
+}
+
+fn optional_string(value: &Value, name: &str) -> Result<Option<Symbol>, Error> {
+    match value.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(_) => string(value, name).map(Some),
+    }
+}
+
+fn usize_field(value: &Value, name: &str, default: usize) -> Result<usize, Error> {
+    match value.get(name) {
+        None | Some(Value::Null) => Ok(default),
+        Some(field) => field.as_u64().map(|n| n as usize).ok_or_else(|| Error::custom(format!("field {} should be a number", name))),
+    }
+}
+
+fn list<T>(value: &Value, name: &str, read: impl Fn(&Value) -> Result<T, Error>) -> Result<Vec<T>, Error> {
+    match value.get(name) {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(Value::Array(items)) => items.iter().map(read).collect(),
+        Some(_) => Err(Error::custom(format!("field {} should be a list", name))),
+    }
+}
+
+fn optional<T>(value: &Value, name: &str, read: impl Fn(&Value) -> Result<T, Error>) -> Result<Option<T>, Error> {
+    match value.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(field) => read(field).map(Some),
+    }
+}
+
+fn expect_empty(value: &Value, name: &str) -> Result<(), Error> {
+    match value.get(name) {
+        None | Some(Value::Null) => Ok(()),
+        Some(Value::Array(items)) if items.is_empty() => Ok(()),
+        Some(field) => Err(unsupported(field, name)),
+    }
+}
+
+fn read_span(value: &Value) -> Span {
+    let number = |name: &str| value.get(name).and_then(Value::as_u64).unwrap_or(0) as usize;
+    Span::new(
+        Position { line: number("lineno").max(1), column: number("col_offset"), offset: 0 },
+        Position { line: number("end_lineno").max(1), column: number("end_col_offset"), offset: 0 },
+    )
+}
+
+fn read_located<T>(value: &Value, node: T) -> Located<T> {
+    Located::new(node, read_span(value))
+}
+
+fn read_name(value: &Value) -> Result<Symbol, Error> {
+    expect_type(value, "Name")?;
+    string(value, "id")
+}
+
+fn read_stmt(value: &Value) -> Result<Located<Stmt>, Error> {
+    let stmt = match kind(value)? {
+        "Error" => Stmt::Error,
+        "Assign" => match field(value, "targets")?.as_array().map(Vec::as_slice) {
+            Some([target]) => Stmt::Assignment(read_name(target)?, read_expression(field(value, "value")?)?),
+            _ => return Err(unsupported(value, "assignments to anything but a single name")),
+        },
+        "Expr" => Stmt::Expr(read_expression(field(value, "value")?)?),
+        "Pass" => Stmt::Pass,
+        "Import" => Stmt::Import(list(value, "names", read_alias)?),
+        "ImportFrom" => Stmt::ImportFrom {
+            module: optional_string(value, "module")?,
+            names: list(value, "names", read_alias)?,
+            level: usize_field(value, "level", 0)?,
+        },
+        "Raise" => Stmt::Raise {
+            exc: optional(value, "exc", read_expression)?,
+            cause: optional(value, "cause", read_expression)?,
+        },
+        kind @ ("Try" | "TryStar") => Stmt::Try {
+            body: list(value, "body", read_stmt)?,
+            handlers: list(value, "handlers", read_except_handler)?,
+            orelse: list(value, "orelse", read_stmt)?,
+            finalbody: list(value, "finalbody", read_stmt)?,
+            star: kind == "TryStar",
+        },
+        kind @ ("With" | "AsyncWith") => Stmt::With {
+            items: list(value, "items", read_with_item)?,
+            body: list(value, "body", read_stmt)?,
+            is_async: kind == "AsyncWith",
+        },
+        kind @ ("FunctionDef" | "AsyncFunctionDef") => {
+            let body = list(value, "body", read_stmt)?;
+            let is_generator = statements_yield(&body);
+            Stmt::FunctionDef {
+                name: string(value, "name")?,
+                type_params: list(value, "type_params", read_type_param)?,
+                params: read_arguments(field(value, "args")?)?,
+                returns: optional(value, "returns", read_expression)?,
+                body,
+                decorator_list: list(value, "decorator_list", read_expression)?,
+                is_async: kind == "AsyncFunctionDef",
+                is_generator,
+            }
+        }
+        "ClassDef" => {
+            expect_empty(value, "keywords")?;
+            Stmt::ClassDef {
+                name: string(value, "name")?,
+                type_params: list(value, "type_params", read_type_param)?,
+                bases: list(value, "bases", read_expression)?,
+                body: list(value, "body", read_stmt)?,
+                decorator_list: list(value, "decorator_list", read_expression)?,
+            }
+        }
+        "Return" => Stmt::Return(optional(value, "value", read_expression)?),
+        "AnnAssign" => Stmt::AnnAssign {
+            target: read_name(field(value, "target")?)?,
+            annotation: read_expression(field(value, "annotation")?)?,
+            value: optional(value, "value", read_expression)?,
+        },
+        "TypeAlias" => Stmt::TypeAlias {
+            name: read_name(field(value, "name")?)?,
+            type_params: list(value, "type_params", read_type_param)?,
+            value: read_expression(field(value, "value")?)?,
+        },
+        "Match" => Stmt::Match {
+            subject: read_expression(field(value, "subject")?)?,
+            cases: list(value, "cases", read_match_case)?,
+        },
+        kind @ ("For" | "AsyncFor") => Stmt::For {
+            target: read_expression(field(value, "target")?)?,
+            iter: read_expression(field(value, "iter")?)?,
+            body: list(value, "body", read_stmt)?,
+            orelse: list(value, "orelse", read_stmt)?,
+            is_async: kind == "AsyncFor",
+        },
+        _ => return Err(unsupported(value, "statements of this kind")),
+    };
+
+    Ok(read_located(value, stmt))
+}
+
+fn read_alias(value: &Value) -> Result<Located<Alias>, Error> {
+    expect_type(value, "alias")?;
+    Ok(read_located(value, Alias { name: string(value, "name")?, asname: optional_string(value, "asname")? }))
+}
+
+fn read_except_handler(value: &Value) -> Result<Located<ExceptHandler>, Error> {
+    expect_type(value, "ExceptHandler")?;
+    let handler = ExceptHandler {
+        exception: optional(value, "type", read_expression)?,
+        name: optional_string(value, "name")?,
+        body: list(value, "body", read_stmt)?,
+    };
+    Ok(read_located(value, handler))
+}
+
+fn read_with_item(value: &Value) -> Result<WithItem, Error> {
+    expect_type(value, "withitem")?;
+    Ok(WithItem {
+        context_expr: read_expression(field(value, "context_expr")?)?,
+        optional_vars: optional(value, "optional_vars", read_expression)?,
+    })
+}
+
+fn read_arguments(value: &Value) -> Result<Vec<Located<Parameter>>, Error> {
+    expect_type(value, "arguments")?;
+    for name in ["posonlyargs", "vararg", "kwonlyargs", "kw_defaults", "kwarg"] {
+        expect_empty(value, name)?;
+    }
+
+    let args = field(value, "args")?.as_array().cloned().unwrap_or_default();
+    let mut defaults = list(value, "defaults", read_expression)?.into_iter();
+    if defaults.len() > args.len() {
+        return Err(Error::custom("more defaults than parameters"));
+    }
+
+    let first_default = args.len() - defaults.len();
+    args.iter().enumerate().map(|(i, arg)| {
+        expect_type(arg, "arg")?;
+        let param = Parameter {
+            name: string(arg, "arg")?,
+            annotation: optional(arg, "annotation", read_expression)?,
+            default: if i >= first_default { defaults.next() } else { None },
+        };
+        Ok(read_located(arg, param))
+    }).collect()
+}
+
+fn read_type_param(value: &Value) -> Result<Located<TypeParam>, Error> {
+    let type_param = match kind(value)? {
+        "TypeVar" => TypeParam::TypeVar { name: string(value, "name")?, bound: optional(value, "bound", read_expression)? },
+        "TypeVarTuple" => TypeParam::TypeVarTuple(string(value, "name")?),
+        "ParamSpec" => TypeParam::ParamSpec(string(value, "name")?),
+        _ => return Err(unsupported(value, "type parameters of this kind")),
+    };
+    Ok(read_located(value, type_param))
+}
+
+fn read_match_case(value: &Value) -> Result<MatchCase, Error> {
+    expect_type(value, "match_case")?;
+    Ok(MatchCase {
+        pattern: read_pattern(field(value, "pattern")?)?,
+        guard: optional(value, "guard", read_expression)?,
+        body: list(value, "body", read_stmt)?,
+    })
+}
+
+fn read_binary_operator(value: &Value) -> Result<BinaryOperator, Error> {
+    Ok(match kind(value)? {
+        "Add" => BinaryOperator::Add,
+        "Sub" => BinaryOperator::Subtract,
+        "Mult" => BinaryOperator::Multiply,
+        "Div" => BinaryOperator::Divide,
+        "FloorDiv" => BinaryOperator::FloorDivide,
+        "Mod" => BinaryOperator::Modulus,
+        "Pow" => BinaryOperator::Power,
+        _ => return Err(unsupported(value, "operators of this kind")),
+    })
+}
+
+fn read_compare_operator(value: &Value) -> Result<CompareOperator, Error> {
+    Ok(match kind(value)? {
+        "Eq" => CompareOperator::Equal,
+        "NotEq" => CompareOperator::NotEqual,
+        "Lt" => CompareOperator::LessThan,
+        "LtE" => CompareOperator::LessEqual,
+        "Gt" => CompareOperator::GreaterThan,
+        "GtE" => CompareOperator::GreaterEqual,
+        "And" => CompareOperator::And,
+        "Or" => CompareOperator::Or,
+        _ => return Err(unsupported(value, "operators of this kind")),
+    })
+}
+
+fn read_constant(value: &Value) -> Result<Expression, Error> {
+    Ok(match field(value, "value")? {
+        Value::Null => Expression::None,
+        Value::Bool(value) => Expression::Boolean(*value),
+        Value::String(value) => Expression::String(value.clone()),
+        Value::Number(n) => Expression::Number(n.as_i64().ok_or_else(|| unsupported(value, "constants that are not 64-bit integers"))?),
+        _ => return Err(unsupported(value, "constants of this kind")),
+    })
+}
+
+// Our `&&` and `||` chains nest to the left, `a && b && c` is `(a && b) && c`
+fn nest(first: Located<Expression>, rest: Vec<(CompareOperator, Located<Expression>)>, span: Span) -> Expression {
+    let mut expr = first;
+    for (operator, right) in rest {
+        expr = Located::new(Expression::CompareOp(Box::new(expr), operator, Box::new(right)), span);
+    }
+    expr.node
+}
+
+// Chains like `1 + 1 + ...` nest as deeply as they are long, so they are read
+// down to their first operand in a loop and built back up from it, as
+// to_python_ast writes them
+fn read_expression(value: &Value) -> Result<Located<Expression>, Error> {
+    let mut chain = Vec::new();
+    let mut first = value;
+    while let Some(operand) = first_operand_value(first)? {
+        chain.push(first);
+        first = operand;
+    }
+
+    let mut expr = read_operand_free(first)?;
+    for value in chain.into_iter().rev() {
+        expr = read_around_first_operand(value, expr)?;
+    }
+    Ok(expr)
+}
+
+// The first operand of a node that nests around it, as first_operand
+fn first_operand_value(value: &Value) -> Result<Option<&Value>, Error> {
+    Ok(match kind(value)? {
+        "BinOp" | "Compare" => Some(field(value, "left")?),
+        "Attribute" | "Subscript" => Some(field(value, "value")?),
+        "Call" => Some(field(value, "func")?),
+        "BoolOp" => match field(value, "values")? {
+            Value::Array(values) => Some(values.first().ok_or_else(|| Error::custom("BoolOp without values"))?),
+            _ => return Err(Error::custom("field values should be a list")),
+        },
+        _ => None,
+    })
+}
+
+// A node that nests around its first operand, given that operand
+fn read_around_first_operand(value: &Value, first: Located<Expression>) -> Result<Located<Expression>, Error> {
+    let span = read_span(value);
+    let expr = match kind(value)? {
+        "BinOp" => Expression::BinaryOp(
+            Box::new(first),
+            read_binary_operator(field(value, "op")?)?,
+            Box::new(read_expression(field(value, "right")?)?),
+        ),
+        "BoolOp" => {
+            let op = read_compare_operator(field(value, "op")?)?;
+            let rest = list(value, "values", read_expression)?.into_iter().skip(1).map(|e| (op, e)).collect();
+            nest(first, rest, span)
+        }
+        "Compare" => {
+            let ops = list(value, "ops", read_compare_operator)?;
+            let comparators = list(value, "comparators", read_expression)?;
+            if ops.len() != comparators.len() {
+                return Err(Error::custom("Compare with mismatched ops and comparators"));
+            }
+            // A single comparison is a CompareOp, only chains are a Compare
+            let rest: Vec<_> = ops.into_iter().zip(comparators).collect();
+            if rest.len() > 1 {
+                Expression::Compare(Box::new(first), rest)
+            } else {
+                nest(first, rest, span)
+            }
+        }
+        "Attribute" => Expression::Attribute(Box::new(first), string(value, "attr")?),
+        "Subscript" => Expression::Subscript(Box::new(first), Box::new(read_expression(field(value, "slice")?)?)),
+        "Call" => {
+            expect_empty(value, "keywords")?;
+            Expression::Call(Box::new(first), list(value, "args", read_expression)?)
+        }
+        _ => unreachable!(),
+    };
+
+    Ok(Located::new(expr, span))
+}
+
+// An expression with no first operand to nest around
+fn read_operand_free(value: &Value) -> Result<Located<Expression>, Error> {
+    let expr = match kind(value)? {
+        "Error" => Expression::Error,
+        "Constant" => read_constant(value)?,
+        "UnaryOp" => {
+            let operand = field(value, "operand")?;
+            let magnitude = operand.get("value").and_then(Value::as_u64);
+            match (kind(field(value, "op")?)?, magnitude) {
+                // i64::MIN has no positive counterpart, go through i128
+                ("USub", Some(n)) => Expression::Number(i64::try_from(-(n as i128)).map_err(|_| unsupported(value, "integers this small"))?),
+                _ => return Err(unsupported(value, "unary operations other than negative numbers")),
+            }
+        }
+        "Name" => Expression::Variable(string(value, "id")?),
+        "Tuple" => Expression::Tuple(list(value, "elts", read_expression)?),
+        "Await" => Expression::Await(Box::new(read_expression(field(value, "value")?)?)),
+        "Yield" => Expression::Yield(optional(value, "value", read_expression)?.map(Box::new)),
+        "YieldFrom" => Expression::YieldFrom(Box::new(read_expression(field(value, "value")?)?)),
+        _ => return Err(unsupported(value, "expressions of this kind")),
+    };
+
+    Ok(read_located(value, expr))
+}
+
+fn read_pattern(value: &Value) -> Result<Located<Pattern>, Error> {
+    let pattern = match kind(value)? {
+        "MatchValue" => Pattern::Value(read_expression(field(value, "value")?)?),
+        "MatchSingleton" => {
+            let constant = match field(value, "value")? {
+                Value::Bool(value) => Expression::Boolean(*value),
+                Value::Null => Expression::None,
+                _ => return Err(unsupported(value, "singletons other than True, False and None")),
+            };
+            Pattern::Singleton(read_located(value, constant))
+        }
+        "MatchSequence" => Pattern::Sequence(list(value, "patterns", read_pattern)?),
+        "MatchMapping" => Pattern::Mapping {
+            keys: list(value, "keys", read_expression)?,
+            patterns: list(value, "patterns", read_pattern)?,
+            rest: optional_string(value, "rest")?,
+        },
+        "MatchClass" => Pattern::Class {
+            cls: read_expression(field(value, "cls")?)?,
+            patterns: list(value, "patterns", read_pattern)?,
+            kwd_attrs: list(value, "kwd_attrs", |attr| attr.as_str().map(Symbol::from).ok_or_else(|| Error::custom("kwd_attrs should be strings")))?,
+            kwd_patterns: list(value, "kwd_patterns", read_pattern)?,
+        },
+        "MatchStar" => Pattern::Star(optional_string(value, "name")?),
+        "MatchAs" => Pattern::As(optional(value, "pattern", read_pattern)?.map(Box::new), optional_string(value, "name")?),
+        "MatchOr" => Pattern::Or(list(value, "patterns", read_pattern)?),
+        _ => return Err(unsupported(value, "patterns of this kind")),
+    };
+
+    Ok(read_located(value, pattern))
+}
@@ -0,0 +1,599 @@
+use crate::{Alias, BinaryOperator, CompareOperator, Expression, Located, Parameter, Pattern, Program, Stmt, Symbol, TypeParam, WithItem};
+
+// How tightly an expression binds, loosest first. An operand is wrapped in
+// parentheses when it binds looser than its position in the grammar allows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    // `yield` is only allowed bare as a statement or an assigned value
+    Yield,
+    Boolean,
+    Comparison,
+    AddSub,
+    Factor,
+    // A negative number literal
+    Unary,
+    Power,
+    Await,
+    Postfix,
+    Atom,
+}
+
+fn precedence(expr: &Expression) -> Precedence {
+    match expr {
+        Expression::Yield(_) | Expression::YieldFrom(_) => Precedence::Yield,
+        Expression::CompareOp(_, CompareOperator::And | CompareOperator::Or, _) => Precedence::Boolean,
+        Expression::CompareOp(..) | Expression::Compare(..) => Precedence::Comparison,
+        Expression::BinaryOp(_, BinaryOperator::Add | BinaryOperator::Subtract, _) => Precedence::AddSub,
+        Expression::BinaryOp(_, BinaryOperator::Power, _) => Precedence::Power,
+        Expression::BinaryOp(..) => Precedence::Factor,
+        Expression::Number(n) if *n < 0 => Precedence::Unary,
+        Expression::Await(_) => Precedence::Await,
+        Expression::Attribute(..) | Expression::Call(..) | Expression::Subscript(..) => Precedence::Postfix,
+        _ => Precedence::Atom,
+    }
+}
+
+fn binary_operator(operator: &BinaryOperator) -> &'static str {
+    match operator {
+        BinaryOperator::Add => "+",
+        BinaryOperator::Subtract => "-",
+        BinaryOperator::Multiply => "*",
+        BinaryOperator::Divide => "/",
+        BinaryOperator::FloorDivide => "//",
+        BinaryOperator::Modulus => "%",
+        BinaryOperator::Power => "**",
+    }
+}
+
+fn compare_operator(operator: &CompareOperator) -> &'static str {
+    match operator {
+        CompareOperator::Equal => "==",
+        CompareOperator::NotEqual => "!=",
+        CompareOperator::LessThan => "<",
+        CompareOperator::LessEqual => "<=",
+        CompareOperator::GreaterThan => ">",
+        CompareOperator::GreaterEqual => ">=",
+        CompareOperator::And => "&&",
+        CompareOperator::Or => "||",
+    }
+}
+
+// Turn a program back into source. Parsing the result gives a program equal
+// to the one unparsed. Error placeholders left by recovery come out as `...`
+pub fn unparse(program: &Program) -> String {
+    let mut out = String::new();
+    write_statements(&mut out, &program.statements, 0);
+    out
+}
+
+// The source of a single expression, with no more parentheses than needed
+pub fn unparse_expression(expr: &Expression) -> String {
+    let mut out = String::new();
+    write_expression(&mut out, expr, Precedence::Yield);
+    out
+}
+
+fn write_expression(out: &mut String, expr: &Expression, min: Precedence) {
+    if precedence(expr) < min {
+        out.push('(');
+        write_expression(out, expr, Precedence::Yield);
+        out.push(')');
+        return;
+    }
+
+    // Chains like `1 + 1 + ...` nest as deeply as they are long, so the left
+    // operands written without parentheses are found in a loop. The innermost
+    // is written first, then each node writes what follows its left operand
+    let mut chain = Vec::new();
+    let mut expr = expr;
+    while let Some((left, _)) = left_operand(expr).filter(|(left, left_min)| precedence(left) >= *left_min) {
+        chain.push(expr);
+        expr = left;
+    }
+
+    write_node(out, expr);
+    for expr in chain.into_iter().rev() {
+        write_after_left(out, expr);
+    }
+}
+
+// How tightly the operands of a binary operator have to bind, left and right
+fn binary_operands(operator: &BinaryOperator) -> (Precedence, Precedence) {
+    match operator {
+        BinaryOperator::Add | BinaryOperator::Subtract => (Precedence::AddSub, Precedence::Factor),
+        // `**` groups to the right, `a ** b ** c` is `a ** (b ** c)`
+        BinaryOperator::Power => (Precedence::Await, Precedence::Unary),
+        _ => (Precedence::Factor, Precedence::Unary),
+    }
+}
+
+fn compare_operands(operator: &CompareOperator) -> (Precedence, Precedence) {
+    match operator {
+        CompareOperator::And | CompareOperator::Or => (Precedence::Boolean, Precedence::Comparison),
+        // A comparison as an operand keeps its parentheses on either side,
+        // `(a < b) == c` is not the chain `a < b == c`
+        _ => (Precedence::AddSub, Precedence::AddSub),
+    }
+}
+
+// The left operand of an operator or postfix expression, and how tightly it
+// has to bind to be written without parentheses
+fn left_operand(expr: &Expression) -> Option<(&Expression, Precedence)> {
+    let (left, min) = match expr {
+        Expression::BinaryOp(left, operator, _) => (left, binary_operands(operator).0),
+        Expression::CompareOp(left, operator, _) => (left, compare_operands(operator).0),
+        Expression::Compare(left, _) => (left, Precedence::AddSub),
+        // `1.x` would read as a float, write_node writes `(1).x`
+        Expression::Attribute(value, _) if matches!(value.node, Expression::Number(_)) => return None,
+        Expression::Attribute(value, _) | Expression::Subscript(value, _) | Expression::Call(value, _) => (value, Precedence::Postfix),
+        _ => return None,
+    };
+    Some((&left.node, min))
+}
+
+fn write_node(out: &mut String, expr: &Expression) {
+    if let Some((left, left_min)) = left_operand(expr) {
+        write_expression(out, left, left_min);
+        write_after_left(out, expr);
+        return;
+    }
+
+    match expr {
+        Expression::Number(n) => out.push_str(&n.to_string()),
+        // Strings hold their source text between the quotes
+        Expression::String(value) => {
+            out.push('"');
+            out.push_str(value);
+            out.push('"');
+        }
+        Expression::Variable(name) => out.push_str(name),
+        Expression::Boolean(true) => out.push_str("True"),
+        Expression::Boolean(false) => out.push_str("False"),
+        Expression::None => out.push_str("None"),
+        Expression::Tuple(elements) => {
+            out.push('(');
+            write_comma_separated(out, elements);
+            if elements.len() == 1 {
+                out.push(',');
+            }
+            out.push(')');
+        }
+        Expression::Attribute(value, _) => {
+            out.push('(');
+            write_expression(out, &value.node, Precedence::Yield);
+            out.push(')');
+            write_after_left(out, expr);
+        }
+        Expression::Await(value) => {
+            out.push_str("await ");
+            write_expression(out, &value.node, Precedence::Postfix);
+        }
+        Expression::Yield(None) => out.push_str("yield"),
+        Expression::Yield(Some(value)) => {
+            out.push_str("yield ");
+            write_expression(out, &value.node, Precedence::Boolean);
+        }
+        Expression::YieldFrom(value) => {
+            out.push_str("yield from ");
+            write_expression(out, &value.node, Precedence::Boolean);
+        }
+        Expression::Error => out.push_str("..."),
+        Expression::BinaryOp(..) | Expression::CompareOp(..) | Expression::Compare(..) | Expression::Subscript(..) | Expression::Call(..) => {}
+    }
+}
+
+// Everything of an expression that comes after its left operand
+fn write_after_left(out: &mut String, expr: &Expression) {
+    match expr {
+        Expression::BinaryOp(_, operator, right) => {
+            write_operator(out, binary_operator(operator));
+            write_expression(out, &right.node, binary_operands(operator).1);
+        }
+        Expression::CompareOp(_, operator, right) => {
+            write_operator(out, compare_operator(operator));
+            write_expression(out, &right.node, compare_operands(operator).1);
+        }
+        Expression::Compare(_, rest) => {
+            for (operator, right) in rest {
+                write_operator(out, compare_operator(operator));
+                write_expression(out, &right.node, Precedence::AddSub);
+            }
+        }
+        Expression::Attribute(_, name) => {
+            out.push('.');
+            out.push_str(name);
+        }
+        Expression::Subscript(_, index) => {
+            out.push('[');
+            write_expression(out, &index.node, Precedence::Boolean);
+            out.push(']');
+        }
+        Expression::Call(_, args) => {
+            out.push('(');
+            write_comma_separated(out, args);
+            out.push(')');
+        }
+        _ => {}
+    }
+}
+
+fn write_operator(out: &mut String, operator: &str) {
+    // The spaces matter, `a -1` would read as `a` followed by the number -1
+    out.push(' ');
+    out.push_str(operator);
+    out.push(' ');
+}
+
+fn write_comma_separated(out: &mut String, exprs: &[Located<Expression>]) {
+    for (i, expr) in exprs.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        write_expression(out, &expr.node, Precedence::Boolean);
+    }
+}
+
+fn write_indent(out: &mut String, indent: usize) {
+    for _ in 0..indent {
+        out.push_str("    ");
+    }
+}
+
+fn write_statements(out: &mut String, statements: &[Located<Stmt>], indent: usize) {
+    for stmt in statements {
+        write_statement(out, &stmt.node, indent);
+    }
+}
+
+// The `:` and indented body of a compound statement, an empty body gets a `pass`
+fn write_block(out: &mut String, body: &[Located<Stmt>], indent: usize) {
+    out.push_str(":\n");
+    if body.is_empty() {
+        write_indent(out, indent + 1);
+        out.push_str("pass\n");
+    }
+    write_statements(out, body, indent + 1);
+}
+
+fn write_statement(out: &mut String, stmt: &Stmt, indent: usize) {
+    write_indent(out, indent);
+
+    match stmt {
+        Stmt::Error => out.push_str("..."),
+        Stmt::Assignment(target, value) => {
+            out.push_str(target);
+            out.push_str(" = ");
+            write_expression(out, &value.node, Precedence::Yield);
+        }
+        Stmt::Expr(value) => write_expression(out, &value.node, Precedence::Yield),
+        Stmt::Pass => out.push_str("pass"),
+        Stmt::Import(names) => {
+            out.push_str("import ");
+            write_aliases(out, names);
+        }
+        Stmt::ImportFrom { module, names, level } => {
+            out.push_str("from ");
+            out.push_str(&".".repeat(*level));
+            if let Some(module) = module {
+                out.push_str(module);
+            }
+            out.push_str(" import ");
+            write_aliases(out, names);
+        }
+        Stmt::Raise { exc, cause } => {
+            out.push_str("raise");
+            if let Some(exc) = exc {
+                out.push(' ');
+                write_expression(out, &exc.node, Precedence::Boolean);
+                if let Some(cause) = cause {
+                    out.push_str(" from ");
+                    write_expression(out, &cause.node, Precedence::Boolean);
+                }
+            }
+        }
+        Stmt::Return(value) => {
+            out.push_str("return");
+            if let Some(value) = value {
+                out.push(' ');
+                write_expression(out, &value.node, Precedence::Boolean);
+            }
+        }
+        Stmt::AnnAssign { target, annotation, value } => {
+            out.push_str(target);
+            out.push_str(": ");
+            write_expression(out, &annotation.node, Precedence::Boolean);
+            if let Some(value) = value {
+                out.push_str(" = ");
+                write_expression(out, &value.node, Precedence::Yield);
+            }
+        }
+        Stmt::TypeAlias { name, type_params, value } => {
+            out.push_str("type ");
+            out.push_str(name);
+            write_type_params(out, type_params);
+            out.push_str(" = ");
+            write_expression(out, &value.node, Precedence::Boolean);
+        }
+        Stmt::Try { body, handlers, orelse, finalbody, star } => {
+            out.push_str("try");
+            write_block(out, body, indent);
+            for handler in handlers {
+                write_indent(out, indent);
+                out.push_str(if *star { "except*" } else { "except" });
+                if let Some(exception) = &handler.node.exception {
+                    out.push(' ');
+                    write_expression(out, &exception.node, Precedence::Boolean);
+                    if let Some(name) = &handler.node.name {
+                        out.push_str(" as ");
+                        out.push_str(name);
+                    }
+                }
+                write_block(out, &handler.node.body, indent);
+            }
+            write_else(out, "else", orelse, indent);
+            write_else(out, "finally", finalbody, indent);
+            return;
+        }
+        Stmt::With { items, body, is_async } => {
+            if *is_async {
+                out.push_str("async ");
+            }
+            out.push_str("with ");
+            write_with_items(out, items);
+            write_block(out, body, indent);
+            return;
+        }
+        Stmt::FunctionDef { name, type_params, params, returns, body, decorator_list, is_async, .. } => {
+            write_decorators(out, decorator_list, indent);
+            if *is_async {
+                out.push_str("async ");
+            }
+            out.push_str("def ");
+            out.push_str(name);
+            write_type_params(out, type_params);
+            out.push('(');
+            write_parameters(out, params);
+            out.push(')');
+            if let Some(returns) = returns {
+                out.push_str(" -> ");
+                write_expression(out, &returns.node, Precedence::Boolean);
+            }
+            write_block(out, body, indent);
+            return;
+        }
+        Stmt::ClassDef { name, type_params, bases, body, decorator_list } => {
+            write_decorators(out, decorator_list, indent);
+            out.push_str("class ");
+            out.push_str(name);
+            write_type_params(out, type_params);
+            if !bases.is_empty() {
+                out.push('(');
+                write_comma_separated(out, bases);
+                out.push(')');
+            }
+            write_block(out, body, indent);
+            return;
+        }
+        Stmt::Match { subject, cases } => {
+            out.push_str("match ");
+            write_expression(out, &subject.node, Precedence::Boolean);
+            out.push_str(":\n");
+            for case in cases {
+                write_indent(out, indent + 1);
+                out.push_str("case ");
+                write_pattern(out, &case.pattern.node);
+                if let Some(guard) = &case.guard {
+                    out.push_str(" if ");
+                    write_expression(out, &guard.node, Precedence::Boolean);
+                }
+                write_block(out, &case.body, indent + 1);
+            }
+            return;
+        }
+        Stmt::For { target, iter, body, orelse, is_async } => {
+            if *is_async {
+                out.push_str("async ");
+            }
+            out.push_str("for ");
+            write_expression(out, &target.node, Precedence::Postfix);
+            out.push_str(" in ");
+            write_expression(out, &iter.node, Precedence::Boolean);
+            write_block(out, body, indent);
+            write_else(out, "else", orelse, indent);
+            return;
+        }
+    }
+
+    // Simple statements end with the line, compound ones with their block
+    out.push('\n');
+}
+
+// An `else:` or `finally:` clause, left out when empty
+fn write_else(out: &mut String, keyword: &str, body: &[Located<Stmt>], indent: usize) {
+    if !body.is_empty() {
+        write_indent(out, indent);
+        out.push_str(keyword);
+        write_block(out, body, indent);
+    }
+}
+
+fn write_decorators(out: &mut String, decorator_list: &[Located<Expression>], indent: usize) {
+    for decorator in decorator_list {
+        out.push('@');
+        write_expression(out, &decorator.node, Precedence::Boolean);
+        out.push('\n');
+        write_indent(out, indent);
+    }
+}
+
+fn write_aliases(out: &mut String, names: &[Located<Alias>]) {
+    for (i, alias) in names.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        out.push_str(&alias.node.name);
+        if let Some(asname) = &alias.node.asname {
+            out.push_str(" as ");
+            out.push_str(asname);
+        }
+    }
+}
+
+fn write_with_items(out: &mut String, items: &[WithItem]) {
+    let mut text = String::new();
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            text.push_str(", ");
+        }
+        write_expression(&mut text, &item.context_expr.node, Precedence::Boolean);
+        if let Some(target) = &item.optional_vars {
+            text.push_str(" as ");
+            write_expression(&mut text, &target.node, Precedence::Postfix);
+        }
+    }
+
+    // Items starting with a parenthesis would be taken for the parenthesized
+    // form `with (a, b):`, so use that form to keep them apart
+    if text.starts_with('(') {
+        out.push('(');
+        out.push_str(&text);
+        out.push(')');
+    } else {
+        out.push_str(&text);
+    }
+}
+
+fn write_parameters(out: &mut String, params: &[Located<Parameter>]) {
+    for (i, param) in params.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        out.push_str(&param.node.name);
+        if let Some(annotation) = &param.node.annotation {
+            out.push_str(": ");
+            write_expression(out, &annotation.node, Precedence::Boolean);
+        }
+        if let Some(default) = &param.node.default {
+            out.push_str(if param.node.annotation.is_some() { " = " } else { "=" });
+            write_expression(out, &default.node, Precedence::Boolean);
+        }
+    }
+}
+
+fn write_type_params(out: &mut String, type_params: &[Located<TypeParam>]) {
+    if type_params.is_empty() {
+        return;
+    }
+
+    out.push('[');
+    for (i, type_param) in type_params.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        match &type_param.node {
+            TypeParam::TypeVar { name, bound } => {
+                out.push_str(name);
+                if let Some(bound) = bound {
+                    out.push_str(": ");
+                    write_expression(out, &bound.node, Precedence::Boolean);
+                }
+            }
+            TypeParam::TypeVarTuple(name) => {
+                out.push('*');
+                out.push_str(name);
+            }
+            TypeParam::ParamSpec(name) => {
+                out.push_str("**");
+                out.push_str(name);
+            }
+        }
+    }
+    out.push(']');
+}
+
+fn write_capture(out: &mut String, name: &Option<Symbol>) {
+    out.push_str(name.as_deref().unwrap_or("_"));
+}
+
+// Alternatives of an or pattern are closed patterns, an or or as pattern
+// there needs parentheses. Before `as` only another as pattern does
+fn write_closed_pattern(out: &mut String, pattern: &Pattern, allow_or: bool) {
+    if matches!(pattern, Pattern::As(Some(_), _)) || (!allow_or && matches!(pattern, Pattern::Or(_))) {
+        out.push('(');
+        write_pattern(out, pattern);
+        out.push(')');
+    } else {
+        write_pattern(out, pattern);
+    }
+}
+
+fn write_patterns(out: &mut String, patterns: &[Located<Pattern>]) {
+    for (i, pattern) in patterns.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        write_pattern(out, &pattern.node);
+    }
+}
+
+fn write_pattern(out: &mut String, pattern: &Pattern) {
+    match pattern {
+        Pattern::Value(value) | Pattern::Singleton(value) => write_expression(out, &value.node, Precedence::Boolean),
+        Pattern::Sequence(patterns) => {
+            out.push('[');
+            write_patterns(out, patterns);
+            out.push(']');
+        }
+        Pattern::Mapping { keys, patterns, rest } => {
+            out.push('{');
+            for (i, (key, pattern)) in keys.iter().zip(patterns).enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_expression(out, &key.node, Precedence::Boolean);
+                out.push_str(": ");
+                write_pattern(out, &pattern.node);
+            }
+            if let Some(rest) = rest {
+                if !keys.is_empty() {
+                    out.push_str(", ");
+                }
+                out.push_str("**");
+                out.push_str(rest);
+            }
+            out.push('}');
+        }
+        Pattern::Class { cls, patterns, kwd_attrs, kwd_patterns } => {
+            write_expression(out, &cls.node, Precedence::Postfix);
+            out.push('(');
+            write_patterns(out, patterns);
+            for (i, (attr, pattern)) in kwd_attrs.iter().zip(kwd_patterns).enumerate() {
+                if i > 0 || !patterns.is_empty() {
+                    out.push_str(", ");
+                }
+                out.push_str(attr);
+                out.push('=');
+                write_pattern(out, &pattern.node);
+            }
+            out.push(')');
+        }
+        Pattern::Star(name) => {
+            out.push('*');
+            write_capture(out, name);
+        }
+        Pattern::As(None, name) => write_capture(out, name),
+        Pattern::As(Some(pattern), name) => {
+            write_closed_pattern(out, &pattern.node, true);
+            out.push_str(" as ");
+            write_capture(out, name);
+        }
+        Pattern::Or(alternatives) => {
+            for (i, alternative) in alternatives.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(" | ");
+                }
+                write_closed_pattern(out, &alternative.node, false);
+            }
+        }
+    }
+}
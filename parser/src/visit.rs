@@ -0,0 +1,665 @@
+use crate::{left_operand, take_expression, Alias, ExceptHandler, Expression, Located, MatchCase, Parameter, Pattern, Program, Stmt, TypeParam, WithItem};
+
+// Traversals over the AST. Each method of a trait handles one kind of node and
+// by default walks into its children through the matching free function, so an
+// implementation overrides the methods it cares about and calls the walk
+// function where it wants to recurse. Children are visited in source order.
+//
+// Chains like `1 + 1 + ...` nest as deeply as they are long, so walking one
+// operand at a time through walk_expression could overflow the stack. An
+// implementation of visit_expression instead handles each expression of
+// left_spine(expr) and then calls walk_chain, which visits everything else
+// under them in a loop
+
+// Read-only traversal
+pub trait Visitor {
+    fn visit_program(&mut self, program: &Program) {
+        walk_program(self, program)
+    }
+
+    fn visit_stmt(&mut self, stmt: &Located<Stmt>) {
+        walk_stmt(self, stmt)
+    }
+
+    fn visit_expression(&mut self, expr: &Located<Expression>) {
+        walk_chain(self, expr)
+    }
+
+    fn visit_pattern(&mut self, pattern: &Located<Pattern>) {
+        walk_pattern(self, pattern)
+    }
+
+    fn visit_match_case(&mut self, case: &MatchCase) {
+        walk_match_case(self, case)
+    }
+
+    fn visit_except_handler(&mut self, handler: &Located<ExceptHandler>) {
+        walk_except_handler(self, handler)
+    }
+
+    fn visit_with_item(&mut self, item: &WithItem) {
+        walk_with_item(self, item)
+    }
+
+    fn visit_parameter(&mut self, param: &Located<Parameter>) {
+        walk_parameter(self, param)
+    }
+
+    fn visit_type_param(&mut self, type_param: &Located<TypeParam>) {
+        walk_type_param(self, type_param)
+    }
+
+    fn visit_alias(&mut self, _alias: &Located<Alias>) {}
+}
+
+pub fn walk_program<V: Visitor + ?Sized>(visitor: &mut V, program: &Program) {
+    for stmt in &program.statements {
+        visitor.visit_stmt(stmt);
+    }
+}
+
+pub fn walk_stmt<V: Visitor + ?Sized>(visitor: &mut V, stmt: &Located<Stmt>) {
+    match &stmt.node {
+        Stmt::Error | Stmt::Pass => {}
+        Stmt::Assignment(_, value) | Stmt::Expr(value) => visitor.visit_expression(value),
+        Stmt::Import(names) | Stmt::ImportFrom { names, .. } => names.iter().for_each(|alias| visitor.visit_alias(alias)),
+        Stmt::Raise { exc, cause } => exc.iter().chain(cause).for_each(|e| visitor.visit_expression(e)),
+        Stmt::Return(value) => value.iter().for_each(|e| visitor.visit_expression(e)),
+        Stmt::Try { body, handlers, orelse, finalbody, .. } => {
+            body.iter().for_each(|s| visitor.visit_stmt(s));
+            handlers.iter().for_each(|h| visitor.visit_except_handler(h));
+            orelse.iter().chain(finalbody).for_each(|s| visitor.visit_stmt(s));
+        }
+        Stmt::With { items, body, .. } => {
+            items.iter().for_each(|item| visitor.visit_with_item(item));
+            body.iter().for_each(|s| visitor.visit_stmt(s));
+        }
+        Stmt::FunctionDef { type_params, params, returns, body, decorator_list, .. } => {
+            decorator_list.iter().for_each(|e| visitor.visit_expression(e));
+            type_params.iter().for_each(|t| visitor.visit_type_param(t));
+            params.iter().for_each(|p| visitor.visit_parameter(p));
+            returns.iter().for_each(|e| visitor.visit_expression(e));
+            body.iter().for_each(|s| visitor.visit_stmt(s));
+        }
+        Stmt::ClassDef { type_params, bases, body, decorator_list, .. } => {
+            decorator_list.iter().for_each(|e| visitor.visit_expression(e));
+            type_params.iter().for_each(|t| visitor.visit_type_param(t));
+            bases.iter().for_each(|e| visitor.visit_expression(e));
+            body.iter().for_each(|s| visitor.visit_stmt(s));
+        }
+        Stmt::AnnAssign { annotation, value, .. } => {
+            visitor.visit_expression(annotation);
+            value.iter().for_each(|e| visitor.visit_expression(e));
+        }
+        Stmt::TypeAlias { type_params, value, .. } => {
+            type_params.iter().for_each(|t| visitor.visit_type_param(t));
+            visitor.visit_expression(value);
+        }
+        Stmt::Match { subject, cases } => {
+            visitor.visit_expression(subject);
+            cases.iter().for_each(|case| visitor.visit_match_case(case));
+        }
+        Stmt::For { target, iter, body, orelse, .. } => {
+            visitor.visit_expression(target);
+            visitor.visit_expression(iter);
+            body.iter().chain(orelse).for_each(|s| visitor.visit_stmt(s));
+        }
+    }
+}
+
+pub fn walk_expression<V: Visitor + ?Sized>(visitor: &mut V, expr: &Located<Expression>) {
+    match &expr.node {
+        Expression::Number(_) | Expression::String(_) | Expression::Variable(_) | Expression::Boolean(_) | Expression::None | Expression::Error => {}
+        Expression::BinaryOp(left, _, right) | Expression::CompareOp(left, _, right) | Expression::Subscript(left, right) => {
+            visitor.visit_expression(left);
+            visitor.visit_expression(right);
+        }
+        Expression::Compare(left, rest) => {
+            visitor.visit_expression(left);
+            rest.iter().for_each(|(_, e)| visitor.visit_expression(e));
+        }
+        Expression::Tuple(elements) => elements.iter().for_each(|e| visitor.visit_expression(e)),
+        Expression::Attribute(value, _) | Expression::Await(value) | Expression::YieldFrom(value) => visitor.visit_expression(value),
+        Expression::Call(func, args) => {
+            visitor.visit_expression(func);
+            args.iter().for_each(|e| visitor.visit_expression(e));
+        }
+        Expression::Yield(value) => value.iter().for_each(|e| visitor.visit_expression(e)),
+    }
+}
+
+// The expression, its left operand, that operand's left operand and so on,
+// e.g. `a + b + c`, `a + b` and `a`
+pub fn left_spine(expr: &Located<Expression>) -> impl Iterator<Item = &Located<Expression>> {
+    std::iter::successors(Some(expr), |expr| left_operand(expr))
+}
+
+// Visits the children of the expressions of left_spine(expr) other than those
+// expressions themselves, in source order and without recursing down the chain
+pub fn walk_chain<V: Visitor + ?Sized>(visitor: &mut V, expr: &Located<Expression>) {
+    if left_operand(expr).is_none() {
+        return walk_expression(visitor, expr);
+    }
+
+    let mut spine: Vec<_> = left_spine(expr).collect();
+    if let Some(bottom) = spine.pop() {
+        walk_expression(visitor, bottom);
+    }
+
+    for expr in spine.into_iter().rev() {
+        match &expr.node {
+            Expression::BinaryOp(_, _, right) | Expression::CompareOp(_, _, right) | Expression::Subscript(_, right) => visitor.visit_expression(right),
+            Expression::Compare(_, rest) => rest.iter().for_each(|(_, e)| visitor.visit_expression(e)),
+            Expression::Call(_, args) => args.iter().for_each(|e| visitor.visit_expression(e)),
+            _ => {}
+        }
+    }
+}
+
+pub fn walk_pattern<V: Visitor + ?Sized>(visitor: &mut V, pattern: &Located<Pattern>) {
+    match &pattern.node {
+        Pattern::Value(value) | Pattern::Singleton(value) => visitor.visit_expression(value),
+        Pattern::Sequence(patterns) | Pattern::Or(patterns) => patterns.iter().for_each(|p| visitor.visit_pattern(p)),
+        Pattern::Mapping { keys, patterns, .. } => {
+            for (key, pattern) in keys.iter().zip(patterns) {
+                visitor.visit_expression(key);
+                visitor.visit_pattern(pattern);
+            }
+        }
+        Pattern::Class { cls, patterns, kwd_patterns, .. } => {
+            visitor.visit_expression(cls);
+            patterns.iter().chain(kwd_patterns).for_each(|p| visitor.visit_pattern(p));
+        }
+        Pattern::As(pattern, _) => pattern.iter().for_each(|p| visitor.visit_pattern(p)),
+        Pattern::Star(_) => {}
+    }
+}
+
+pub fn walk_match_case<V: Visitor + ?Sized>(visitor: &mut V, case: &MatchCase) {
+    visitor.visit_pattern(&case.pattern);
+    case.guard.iter().for_each(|e| visitor.visit_expression(e));
+    case.body.iter().for_each(|s| visitor.visit_stmt(s));
+}
+
+pub fn walk_except_handler<V: Visitor + ?Sized>(visitor: &mut V, handler: &Located<ExceptHandler>) {
+    handler.node.exception.iter().for_each(|e| visitor.visit_expression(e));
+    handler.node.body.iter().for_each(|s| visitor.visit_stmt(s));
+}
+
+pub fn walk_with_item<V: Visitor + ?Sized>(visitor: &mut V, item: &WithItem) {
+    visitor.visit_expression(&item.context_expr);
+    item.optional_vars.iter().for_each(|e| visitor.visit_expression(e));
+}
+
+pub fn walk_parameter<V: Visitor + ?Sized>(visitor: &mut V, param: &Located<Parameter>) {
+    param.node.annotation.iter().chain(&param.node.default).for_each(|e| visitor.visit_expression(e));
+}
+
+pub fn walk_type_param<V: Visitor + ?Sized>(visitor: &mut V, type_param: &Located<TypeParam>) {
+    if let TypeParam::TypeVar { bound: Some(bound), .. } = &type_param.node {
+        visitor.visit_expression(bound);
+    }
+}
+
+// In-place traversal, for transforms that keep the shape of the tree
+pub trait VisitorMut {
+    fn visit_program_mut(&mut self, program: &mut Program) {
+        walk_program_mut(self, program)
+    }
+
+    fn visit_stmt_mut(&mut self, stmt: &mut Located<Stmt>) {
+        walk_stmt_mut(self, stmt)
+    }
+
+    // Down chains like `1 + 1 + ...` in a loop, as walk_chain does
+    fn visit_expression_mut(&mut self, expr: &mut Located<Expression>) {
+        walk_chain_mut(self, expr)
+    }
+
+    fn visit_pattern_mut(&mut self, pattern: &mut Located<Pattern>) {
+        walk_pattern_mut(self, pattern)
+    }
+
+    fn visit_match_case_mut(&mut self, case: &mut MatchCase) {
+        walk_match_case_mut(self, case)
+    }
+
+    fn visit_except_handler_mut(&mut self, handler: &mut Located<ExceptHandler>) {
+        walk_except_handler_mut(self, handler)
+    }
+
+    fn visit_with_item_mut(&mut self, item: &mut WithItem) {
+        walk_with_item_mut(self, item)
+    }
+
+    fn visit_parameter_mut(&mut self, param: &mut Located<Parameter>) {
+        walk_parameter_mut(self, param)
+    }
+
+    fn visit_type_param_mut(&mut self, type_param: &mut Located<TypeParam>) {
+        walk_type_param_mut(self, type_param)
+    }
+
+    fn visit_alias_mut(&mut self, _alias: &mut Located<Alias>) {}
+}
+
+pub fn walk_program_mut<V: VisitorMut + ?Sized>(visitor: &mut V, program: &mut Program) {
+    for stmt in &mut program.statements {
+        visitor.visit_stmt_mut(stmt);
+    }
+}
+
+pub fn walk_stmt_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut Located<Stmt>) {
+    match &mut stmt.node {
+        Stmt::Error | Stmt::Pass => {}
+        Stmt::Assignment(_, value) | Stmt::Expr(value) => visitor.visit_expression_mut(value),
+        Stmt::Import(names) | Stmt::ImportFrom { names, .. } => names.iter_mut().for_each(|alias| visitor.visit_alias_mut(alias)),
+        Stmt::Raise { exc, cause } => exc.iter_mut().chain(cause).for_each(|e| visitor.visit_expression_mut(e)),
+        Stmt::Return(value) => value.iter_mut().for_each(|e| visitor.visit_expression_mut(e)),
+        Stmt::Try { body, handlers, orelse, finalbody, .. } => {
+            body.iter_mut().for_each(|s| visitor.visit_stmt_mut(s));
+            handlers.iter_mut().for_each(|h| visitor.visit_except_handler_mut(h));
+            orelse.iter_mut().chain(finalbody).for_each(|s| visitor.visit_stmt_mut(s));
+        }
+        Stmt::With { items, body, .. } => {
+            items.iter_mut().for_each(|item| visitor.visit_with_item_mut(item));
+            body.iter_mut().for_each(|s| visitor.visit_stmt_mut(s));
+        }
+        Stmt::FunctionDef { type_params, params, returns, body, decorator_list, .. } => {
+            decorator_list.iter_mut().for_each(|e| visitor.visit_expression_mut(e));
+            type_params.iter_mut().for_each(|t| visitor.visit_type_param_mut(t));
+            params.iter_mut().for_each(|p| visitor.visit_parameter_mut(p));
+            returns.iter_mut().for_each(|e| visitor.visit_expression_mut(e));
+            body.iter_mut().for_each(|s| visitor.visit_stmt_mut(s));
+        }
+        Stmt::ClassDef { type_params, bases, body, decorator_list, .. } => {
+            decorator_list.iter_mut().for_each(|e| visitor.visit_expression_mut(e));
+            type_params.iter_mut().for_each(|t| visitor.visit_type_param_mut(t));
+            bases.iter_mut().for_each(|e| visitor.visit_expression_mut(e));
+            body.iter_mut().for_each(|s| visitor.visit_stmt_mut(s));
+        }
+        Stmt::AnnAssign { annotation, value, .. } => {
+            visitor.visit_expression_mut(annotation);
+            value.iter_mut().for_each(|e| visitor.visit_expression_mut(e));
+        }
+        Stmt::TypeAlias { type_params, value, .. } => {
+            type_params.iter_mut().for_each(|t| visitor.visit_type_param_mut(t));
+            visitor.visit_expression_mut(value);
+        }
+        Stmt::Match { subject, cases } => {
+            visitor.visit_expression_mut(subject);
+            cases.iter_mut().for_each(|case| visitor.visit_match_case_mut(case));
+        }
+        Stmt::For { target, iter, body, orelse, .. } => {
+            visitor.visit_expression_mut(target);
+            visitor.visit_expression_mut(iter);
+            body.iter_mut().chain(orelse).for_each(|s| visitor.visit_stmt_mut(s));
+        }
+    }
+}
+
+pub fn walk_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Located<Expression>) {
+    match &mut expr.node {
+        Expression::Number(_) | Expression::String(_) | Expression::Variable(_) | Expression::Boolean(_) | Expression::None | Expression::Error => {}
+        Expression::BinaryOp(left, _, right) | Expression::CompareOp(left, _, right) | Expression::Subscript(left, right) => {
+            visitor.visit_expression_mut(left);
+            visitor.visit_expression_mut(right);
+        }
+        Expression::Compare(left, rest) => {
+            visitor.visit_expression_mut(left);
+            rest.iter_mut().for_each(|(_, e)| visitor.visit_expression_mut(e));
+        }
+        Expression::Tuple(elements) => elements.iter_mut().for_each(|e| visitor.visit_expression_mut(e)),
+        Expression::Attribute(value, _) | Expression::Await(value) | Expression::YieldFrom(value) => visitor.visit_expression_mut(value),
+        Expression::Call(func, args) => {
+            visitor.visit_expression_mut(func);
+            args.iter_mut().for_each(|e| visitor.visit_expression_mut(e));
+        }
+        Expression::Yield(value) => value.iter_mut().for_each(|e| visitor.visit_expression_mut(e)),
+    }
+}
+
+// walk_chain for VisitorMut. The left operands are taken out of the chain and
+// put back once the rest of it has been visited
+fn walk_chain_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Located<Expression>) {
+    if left_operand(expr).is_none() {
+        return walk_expression_mut(visitor, expr);
+    }
+
+    let mut chain = Vec::new();
+    let mut bottom = take_expression(expr);
+    while left_operand(&bottom).is_some() {
+        let left = take_expression(left_operand_mut(&mut bottom));
+        chain.push(bottom);
+        bottom = left;
+    }
+
+    walk_expression_mut(visitor, &mut bottom);
+    while let Some(mut node) = chain.pop() {
+        match &mut node.node {
+            Expression::BinaryOp(_, _, right) | Expression::CompareOp(_, _, right) | Expression::Subscript(_, right) => visitor.visit_expression_mut(right),
+            Expression::Compare(_, rest) => rest.iter_mut().for_each(|(_, e)| visitor.visit_expression_mut(e)),
+            Expression::Call(_, args) => args.iter_mut().for_each(|e| visitor.visit_expression_mut(e)),
+            _ => {}
+        }
+        *left_operand_mut(&mut node) = bottom;
+        bottom = node;
+    }
+    *expr = bottom;
+}
+
+pub fn walk_pattern_mut<V: VisitorMut + ?Sized>(visitor: &mut V, pattern: &mut Located<Pattern>) {
+    match &mut pattern.node {
+        Pattern::Value(value) | Pattern::Singleton(value) => visitor.visit_expression_mut(value),
+        Pattern::Sequence(patterns) | Pattern::Or(patterns) => patterns.iter_mut().for_each(|p| visitor.visit_pattern_mut(p)),
+        Pattern::Mapping { keys, patterns, .. } => {
+            for (key, pattern) in keys.iter_mut().zip(patterns) {
+                visitor.visit_expression_mut(key);
+                visitor.visit_pattern_mut(pattern);
+            }
+        }
+        Pattern::Class { cls, patterns, kwd_patterns, .. } => {
+            visitor.visit_expression_mut(cls);
+            patterns.iter_mut().chain(kwd_patterns).for_each(|p| visitor.visit_pattern_mut(p));
+        }
+        Pattern::As(pattern, _) => pattern.iter_mut().for_each(|p| visitor.visit_pattern_mut(p)),
+        Pattern::Star(_) => {}
+    }
+}
+
+pub fn walk_match_case_mut<V: VisitorMut + ?Sized>(visitor: &mut V, case: &mut MatchCase) {
+    visitor.visit_pattern_mut(&mut case.pattern);
+    case.guard.iter_mut().for_each(|e| visitor.visit_expression_mut(e));
+    case.body.iter_mut().for_each(|s| visitor.visit_stmt_mut(s));
+}
+
+pub fn walk_except_handler_mut<V: VisitorMut + ?Sized>(visitor: &mut V, handler: &mut Located<ExceptHandler>) {
+    handler.node.exception.iter_mut().for_each(|e| visitor.visit_expression_mut(e));
+    handler.node.body.iter_mut().for_each(|s| visitor.visit_stmt_mut(s));
+}
+
+pub fn walk_with_item_mut<V: VisitorMut + ?Sized>(visitor: &mut V, item: &mut WithItem) {
+    visitor.visit_expression_mut(&mut item.context_expr);
+    item.optional_vars.iter_mut().for_each(|e| visitor.visit_expression_mut(e));
+}
+
+pub fn walk_parameter_mut<V: VisitorMut + ?Sized>(visitor: &mut V, param: &mut Located<Parameter>) {
+    let param = &mut param.node;
+    param.annotation.iter_mut().chain(&mut param.default).for_each(|e| visitor.visit_expression_mut(e));
+}
+
+pub fn walk_type_param_mut<V: VisitorMut + ?Sized>(visitor: &mut V, type_param: &mut Located<TypeParam>) {
+    if let TypeParam::TypeVar { bound: Some(bound), .. } = &mut type_param.node {
+        visitor.visit_expression_mut(bound);
+    }
+}
+
+// Owning traversal that rebuilds the tree, for transforms that replace nodes
+// with nodes of a different kind. The fold functions keep each node's span
+pub trait Fold {
+    fn fold_program(&mut self, program: Program) -> Program {
+        fold_program(self, program)
+    }
+
+    fn fold_stmt(&mut self, stmt: Located<Stmt>) -> Located<Stmt> {
+        fold_stmt(self, stmt)
+    }
+
+    // Down chains like `1 + 1 + ...` in a loop, as walk_chain does
+    fn fold_expression(&mut self, expr: Located<Expression>) -> Located<Expression> {
+        fold_chain(self, expr)
+    }
+
+    fn fold_pattern(&mut self, pattern: Located<Pattern>) -> Located<Pattern> {
+        fold_pattern(self, pattern)
+    }
+
+    fn fold_match_case(&mut self, case: MatchCase) -> MatchCase {
+        fold_match_case(self, case)
+    }
+
+    fn fold_except_handler(&mut self, handler: Located<ExceptHandler>) -> Located<ExceptHandler> {
+        fold_except_handler(self, handler)
+    }
+
+    fn fold_with_item(&mut self, item: WithItem) -> WithItem {
+        fold_with_item(self, item)
+    }
+
+    fn fold_parameter(&mut self, param: Located<Parameter>) -> Located<Parameter> {
+        fold_parameter(self, param)
+    }
+
+    fn fold_type_param(&mut self, type_param: Located<TypeParam>) -> Located<TypeParam> {
+        fold_type_param(self, type_param)
+    }
+
+    fn fold_alias(&mut self, alias: Located<Alias>) -> Located<Alias> {
+        alias
+    }
+}
+
+fn fold_statements<F: Fold + ?Sized>(folder: &mut F, statements: Vec<Located<Stmt>>) -> Vec<Located<Stmt>> {
+    statements.into_iter().map(|s| folder.fold_stmt(s)).collect()
+}
+
+fn fold_expressions<F: Fold + ?Sized>(folder: &mut F, exprs: Vec<Located<Expression>>) -> Vec<Located<Expression>> {
+    exprs.into_iter().map(|e| folder.fold_expression(e)).collect()
+}
+
+fn fold_patterns<F: Fold + ?Sized>(folder: &mut F, patterns: Vec<Located<Pattern>>) -> Vec<Located<Pattern>> {
+    patterns.into_iter().map(|p| folder.fold_pattern(p)).collect()
+}
+
+fn fold_type_params<F: Fold + ?Sized>(folder: &mut F, type_params: Vec<Located<TypeParam>>) -> Vec<Located<TypeParam>> {
+    type_params.into_iter().map(|t| folder.fold_type_param(t)).collect()
+}
+
+fn fold_aliases<F: Fold + ?Sized>(folder: &mut F, names: Vec<Located<Alias>>) -> Vec<Located<Alias>> {
+    names.into_iter().map(|alias| folder.fold_alias(alias)).collect()
+}
+
+pub fn fold_program<F: Fold + ?Sized>(folder: &mut F, mut program: Program) -> Program {
+    Program { statements: fold_statements(folder, std::mem::take(&mut program.statements)) }
+}
+
+pub fn fold_stmt<F: Fold + ?Sized>(folder: &mut F, stmt: Located<Stmt>) -> Located<Stmt> {
+    let node = match stmt.node {
+        Stmt::Error => Stmt::Error,
+        Stmt::Pass => Stmt::Pass,
+        Stmt::Assignment(target, value) => Stmt::Assignment(target, folder.fold_expression(value)),
+        Stmt::Expr(value) => Stmt::Expr(folder.fold_expression(value)),
+        Stmt::Import(names) => Stmt::Import(fold_aliases(folder, names)),
+        Stmt::ImportFrom { module, names, level } => Stmt::ImportFrom { module, names: fold_aliases(folder, names), level },
+        Stmt::Raise { exc, cause } => Stmt::Raise {
+            exc: exc.map(|e| folder.fold_expression(e)),
+            cause: cause.map(|e| folder.fold_expression(e)),
+        },
+        Stmt::Return(value) => Stmt::Return(value.map(|e| folder.fold_expression(e))),
+        Stmt::Try { body, handlers, orelse, finalbody, star } => Stmt::Try {
+            body: fold_statements(folder, body),
+            handlers: handlers.into_iter().map(|h| folder.fold_except_handler(h)).collect(),
+            orelse: fold_statements(folder, orelse),
+            finalbody: fold_statements(folder, finalbody),
+            star,
+        },
+        Stmt::With { items, body, is_async } => Stmt::With {
+            items: items.into_iter().map(|item| folder.fold_with_item(item)).collect(),
+            body: fold_statements(folder, body),
+            is_async,
+        },
+        Stmt::FunctionDef { name, type_params, params, returns, body, decorator_list, is_async, is_generator } => {
+            let decorator_list = fold_expressions(folder, decorator_list);
+            Stmt::FunctionDef {
+                name,
+                type_params: fold_type_params(folder, type_params),
+                params: params.into_iter().map(|p| folder.fold_parameter(p)).collect(),
+                returns: returns.map(|e| folder.fold_expression(e)),
+                body: fold_statements(folder, body),
+                decorator_list,
+                is_async,
+                is_generator,
+            }
+        }
+        Stmt::ClassDef { name, type_params, bases, body, decorator_list } => {
+            let decorator_list = fold_expressions(folder, decorator_list);
+            Stmt::ClassDef {
+                name,
+                type_params: fold_type_params(folder, type_params),
+                bases: fold_expressions(folder, bases),
+                body: fold_statements(folder, body),
+                decorator_list,
+            }
+        }
+        Stmt::AnnAssign { target, annotation, value } => Stmt::AnnAssign {
+            target,
+            annotation: folder.fold_expression(annotation),
+            value: value.map(|e| folder.fold_expression(e)),
+        },
+        Stmt::TypeAlias { name, type_params, value } => Stmt::TypeAlias {
+            name,
+            type_params: fold_type_params(folder, type_params),
+            value: folder.fold_expression(value),
+        },
+        Stmt::Match { subject, cases } => Stmt::Match {
+            subject: folder.fold_expression(subject),
+            cases: cases.into_iter().map(|case| folder.fold_match_case(case)).collect(),
+        },
+        Stmt::For { target, iter, body, orelse, is_async } => Stmt::For {
+            target: folder.fold_expression(target),
+            iter: folder.fold_expression(iter),
+            body: fold_statements(folder, body),
+            orelse: fold_statements(folder, orelse),
+            is_async,
+        },
+    };
+
+    Located::new(node, stmt.span)
+}
+
+pub fn fold_expression<F: Fold + ?Sized>(folder: &mut F, mut expr: Located<Expression>) -> Located<Expression> {
+    // Subexpressions are folded in place, an Expression cannot be taken apart
+    // by moving out of it
+    let mut fold = |expr: &mut Located<Expression>| *expr = folder.fold_expression(take_expression(expr));
+    match &mut expr.node {
+        Expression::BinaryOp(left, _, right) | Expression::CompareOp(left, _, right) | Expression::Subscript(left, right) => {
+            fold(left);
+            fold(right);
+        }
+        Expression::Compare(left, rest) => {
+            fold(left);
+            rest.iter_mut().for_each(|(_, e)| fold(e));
+        }
+        Expression::Tuple(elements) => elements.iter_mut().for_each(fold),
+        Expression::Call(func, args) => {
+            fold(func);
+            args.iter_mut().for_each(fold);
+        }
+        Expression::Attribute(value, _) | Expression::Await(value) | Expression::YieldFrom(value) | Expression::Yield(Some(value)) => fold(value),
+        Expression::Number(_) | Expression::String(_) | Expression::Variable(_) | Expression::Boolean(_) | Expression::None | Expression::Yield(None) | Expression::Error => {}
+    }
+
+    expr
+}
+
+// Takes the left operands out of a chain, folds what is at the bottom of it,
+// then puts each back after folding the other operands of its node
+fn fold_chain<F: Fold + ?Sized>(folder: &mut F, mut expr: Located<Expression>) -> Located<Expression> {
+    let mut chain = Vec::new();
+    while left_operand(&expr).is_some() {
+        let left = take_expression(left_operand_mut(&mut expr));
+        chain.push(expr);
+        expr = left;
+    }
+
+    let mut folded = fold_expression(folder, expr);
+    while let Some(mut expr) = chain.pop() {
+        let mut fold = |expr: &mut Located<Expression>| *expr = folder.fold_expression(take_expression(expr));
+        match &mut expr.node {
+            Expression::BinaryOp(_, _, right) | Expression::CompareOp(_, _, right) | Expression::Subscript(_, right) => fold(right),
+            Expression::Compare(_, rest) => rest.iter_mut().for_each(|(_, e)| fold(e)),
+            Expression::Call(_, args) => args.iter_mut().for_each(fold),
+            _ => {}
+        }
+        *left_operand_mut(&mut expr) = folded;
+        folded = expr;
+    }
+    folded
+}
+
+// The left operand of an expression left_operand has one for
+fn left_operand_mut(expr: &mut Located<Expression>) -> &mut Located<Expression> {
+    match &mut expr.node {
+        Expression::BinaryOp(left, _, _)
+        | Expression::CompareOp(left, _, _)
+        | Expression::Compare(left, _)
+        | Expression::Attribute(left, _)
+        | Expression::Subscript(left, _)
+        | Expression::Call(left, _) => left,
+        _ => unreachable!(),
+    }
+}
+
+pub fn fold_pattern<F: Fold + ?Sized>(folder: &mut F, pattern: Located<Pattern>) -> Located<Pattern> {
+    let node = match pattern.node {
+        Pattern::Value(value) => Pattern::Value(folder.fold_expression(value)),
+        Pattern::Singleton(value) => Pattern::Singleton(folder.fold_expression(value)),
+        Pattern::Sequence(patterns) => Pattern::Sequence(fold_patterns(folder, patterns)),
+        Pattern::Or(patterns) => Pattern::Or(fold_patterns(folder, patterns)),
+        Pattern::Mapping { keys, patterns, rest } => {
+            let (keys, patterns) = keys
+                .into_iter()
+                .zip(patterns)
+                .map(|(key, pattern)| (folder.fold_expression(key), folder.fold_pattern(pattern)))
+                .unzip();
+            Pattern::Mapping { keys, patterns, rest }
+        }
+        Pattern::Class { cls, patterns, kwd_attrs, kwd_patterns } => Pattern::Class {
+            cls: folder.fold_expression(cls),
+            patterns: fold_patterns(folder, patterns),
+            kwd_attrs,
+            kwd_patterns: fold_patterns(folder, kwd_patterns),
+        },
+        Pattern::As(pattern, name) => Pattern::As(pattern.map(|p| Box::new(folder.fold_pattern(*p))), name),
+        Pattern::Star(name) => Pattern::Star(name),
+    };
+
+    Located::new(node, pattern.span)
+}
+
+pub fn fold_match_case<F: Fold + ?Sized>(folder: &mut F, case: MatchCase) -> MatchCase {
+    MatchCase {
+        pattern: folder.fold_pattern(case.pattern),
+        guard: case.guard.map(|e| folder.fold_expression(e)),
+        body: fold_statements(folder, case.body),
+    }
+}
+
+pub fn fold_except_handler<F: Fold + ?Sized>(folder: &mut F, handler: Located<ExceptHandler>) -> Located<ExceptHandler> {
+    let ExceptHandler { exception, name, body } = handler.node;
+    let exception = exception.map(|e| folder.fold_expression(e));
+    let node = ExceptHandler { exception, name, body: fold_statements(folder, body) };
+    Located::new(node, handler.span)
+}
+
+pub fn fold_with_item<F: Fold + ?Sized>(folder: &mut F, item: WithItem) -> WithItem {
+    WithItem {
+        context_expr: folder.fold_expression(item.context_expr),
+        optional_vars: item.optional_vars.map(|e| folder.fold_expression(e)),
+    }
+}
+
+pub fn fold_parameter<F: Fold + ?Sized>(folder: &mut F, param: Located<Parameter>) -> Located<Parameter> {
+    let Parameter { name, annotation, default } = param.node;
+    let annotation = annotation.map(|e| folder.fold_expression(e));
+    let node = Parameter { name, annotation, default: default.map(|e| folder.fold_expression(e)) };
+    Located::new(node, param.span)
+}
+
+pub fn fold_type_param<F: Fold + ?Sized>(folder: &mut F, type_param: Located<TypeParam>) -> Located<TypeParam> {
+    let node = match type_param.node {
+        TypeParam::TypeVar { name, bound } => TypeParam::TypeVar { name, bound: bound.map(|e| folder.fold_expression(e)) },
+        other => other,
+    };
+    Located::new(node, type_param.span)
+}
@@ -0,0 +1,82 @@
+use std::fs;
+use std::path::Path;
+
+use parser::arena::{self, Ast};
+use parser::*;
+use tokenizer::tokenize_spanned;
+
+fn corpus() -> Vec<String> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance");
+    fs::read_dir(dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "py") && !path.ends_with("generate.py"))
+        .map(|path| fs::read_to_string(path).unwrap())
+        .collect()
+}
+
+#[test]
+fn round_trip() {
+    for source in corpus() {
+        let tokens = tokenize_spanned(&source);
+        let expected = to_python_ast(&parse_program(&tokens).unwrap());
+
+        // Moving into the arena and back keeps every node and span
+        let ast = Ast::from(parse_program(&tokens).unwrap());
+        assert!(!ast.is_empty());
+        assert_eq!(to_python_ast(&Program::from(ast)), expected);
+    }
+}
+
+#[test]
+fn parse_into_arena() {
+    let sources = corpus().into_iter().chain([
+        "x = = 1\ndef f():\n    return 1\nyield 2\n".to_string(),
+        "async def f():\n    yield 1\n    return 2\n".to_string(),
+    ]);
+
+    for source in sources {
+        let tokens = tokenize_spanned(&source);
+        let (program, errors) = parse_program_recovering(&tokens);
+        let (ast, arena_errors) = parse_program_arena(&tokens);
+
+        assert_eq!(arena_errors, errors);
+        assert_eq!(to_python_ast(&Program::from(ast)), to_python_ast(&program));
+    }
+}
+
+#[test]
+fn indexing() {
+    let (ast, errors) = parse_program_arena(&tokenize_spanned("x = a * (b + 1)\n"));
+    assert!(errors.is_empty());
+
+    let arena::Stmt::Assignment(target, value) = &ast[ast.statements[0]].node else { panic!() };
+    assert_eq!(&**target, "x");
+    let arena::Expression::BinaryOp(left, BinaryOperator::Multiply, right) = ast[*value].node else { panic!() };
+    assert!(matches!(&ast[left].node, arena::Expression::Variable(name) if &**name == "a"));
+    assert!(matches!(ast[right].node, arena::Expression::BinaryOp(_, BinaryOperator::Add, _)));
+    assert_eq!(ast[right].span.start.column, 9);
+}
+
+#[test]
+fn built_by_hand() {
+    // Children are added before their parents
+    let mut ast = Ast::default();
+    let one = ast.add_expression(arena::Expression::Number(1).into());
+    let name = ast.add_expression(arena::Expression::Variable("n".into()).into());
+    let sum = ast.add_expression(arena::Expression::BinaryOp(name, BinaryOperator::Add, one).into());
+    let stmt = ast.add_stmt(arena::Stmt::Assignment("n".into(), sum).into());
+    ast.statements.push(stmt);
+
+    assert_eq!(unparse(&Program::from(ast)), "n = n + 1\n");
+}
+
+#[test]
+fn long_chains() {
+    let source = format!("x = 1{}\n", " + 1".repeat(100_000));
+    let (ast, errors) = parse_program_arena(&tokenize_spanned(&source));
+    assert!(errors.is_empty());
+    assert_eq!(ast.len(), 1 + 200_001);
+
+    let program = Program::from(ast);
+    assert_eq!(Ast::from(program).len(), 1 + 200_001);
+}
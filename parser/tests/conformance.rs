@@ -0,0 +1,261 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use parser::*;
+use serde_json::{Map, Value};
+use tokenizer::tokenize_spanned;
+
+// Every snippet in tests/conformance is parsed and compared, spans included,
+// with the `ast.dump` output CPython gives for it. The golden .ast files are
+// checked in and regenerated with tests/conformance/generate.py
+
+fn corpus() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance");
+    let mut files: Vec<PathBuf> = fs::read_dir(dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "py") && !path.ends_with("generate.py"))
+        .collect();
+    files.sort();
+    files
+}
+
+// Parses `ast.dump` output into the same JSON shape as to_python_ast:
+// `Name(id='x', ctx=Load())` becomes {"_type": "Name", "id": "x", "ctx": {"_type": "Load"}}
+struct Dump<'a> {
+    text: &'a [u8],
+    position: usize,
+}
+
+impl Dump<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.position < self.text.len() && self.text[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&mut self) -> u8 {
+        self.skip_whitespace();
+        self.text.get(self.position).copied().unwrap_or(0)
+    }
+
+    fn expect(&mut self, byte: u8) {
+        assert_eq!(self.peek() as char, byte as char, "at byte {} of the dump", self.position);
+        self.position += 1;
+    }
+
+    fn word(&mut self) -> String {
+        self.skip_whitespace();
+        let start = self.position;
+        while self.position < self.text.len() && (self.text[self.position].is_ascii_alphanumeric() || b"_-.".contains(&self.text[self.position])) {
+            self.position += 1;
+        }
+        String::from_utf8(self.text[start..self.position].to_vec()).unwrap()
+    }
+
+    // Python's repr of a string, in single or double quotes
+    fn string(&mut self) -> String {
+        let quote = self.peek();
+        self.position += 1;
+        let mut bytes = Vec::new();
+        while self.text[self.position] != quote {
+            let byte = self.text[self.position];
+            self.position += 1;
+            if byte != b'\\' {
+                bytes.push(byte);
+                continue;
+            }
+            let escaped = self.text[self.position];
+            self.position += 1;
+            match escaped {
+                b'n' => bytes.push(b'\n'),
+                b't' => bytes.push(b'\t'),
+                b'r' => bytes.push(b'\r'),
+                b'x' | b'u' | b'U' => {
+                    let digits = match escaped { b'x' => 2, b'u' => 4, _ => 8 };
+                    let hex = std::str::from_utf8(&self.text[self.position..self.position + digits]).unwrap();
+                    let c = char::from_u32(u32::from_str_radix(hex, 16).unwrap()).unwrap();
+                    bytes.extend(c.to_string().as_bytes());
+                    self.position += digits;
+                }
+                other => bytes.push(other),
+            }
+        }
+        self.position += 1;
+        String::from_utf8(bytes).unwrap()
+    }
+
+    fn value(&mut self) -> Value {
+        match self.peek() {
+            b'\'' | b'"' => Value::String(self.string()),
+            b'[' => {
+                self.position += 1;
+                let mut items = Vec::new();
+                while self.peek() != b']' {
+                    items.push(self.value());
+                    if self.peek() == b',' {
+                        self.position += 1;
+                    }
+                }
+                self.position += 1;
+                Value::Array(items)
+            }
+            _ => {
+                let word = self.word();
+                match word.as_str() {
+                    "None" => Value::Null,
+                    "True" => Value::Bool(true),
+                    "False" => Value::Bool(false),
+                    _ if self.peek() == b'(' => self.node(word),
+                    _ => serde_json::from_str(&word).unwrap_or_else(|_| panic!("unexpected {:?} in the dump", word)),
+                }
+            }
+        }
+    }
+
+    fn node(&mut self, kind: String) -> Value {
+        let mut object = Map::new();
+        object.insert("_type".into(), Value::String(kind));
+        self.expect(b'(');
+        while self.peek() != b')' {
+            let field = self.word();
+            self.expect(b'=');
+            object.insert(field, self.value());
+            if self.peek() == b',' {
+                self.position += 1;
+            }
+        }
+        self.position += 1;
+        Value::Object(object)
+    }
+}
+
+fn parse_dump(text: &str) -> Value {
+    let mut dump = Dump { text: text.as_bytes(), position: 0 };
+    let value = dump.value();
+    assert_eq!(dump.peek(), 0, "trailing text in the dump");
+    value
+}
+
+// Fields that are None or empty are left out, as newer Pythons do in their
+// dumps and older ones lack some of them altogether, like type_params
+fn normalize(value: Value) -> Value {
+    match value {
+        Value::Object(object) => Value::Object(object.into_iter()
+            .filter(|(_, v)| !v.is_null() && v.as_array().is_none_or(|items| !items.is_empty()))
+            .map(|(k, v)| (k, normalize(v)))
+            .collect()),
+        Value::Array(items) => Value::Array(items.into_iter().map(normalize).collect()),
+        value => value,
+    }
+}
+
+// The path to the first place two trees disagree
+fn difference(expected: &Value, actual: &Value, path: String) -> Option<String> {
+    match (expected, actual) {
+        (Value::Object(a), Value::Object(b)) if a.get("_type") == b.get("_type") => {
+            let mut keys: Vec<&String> = a.keys().chain(b.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            keys.into_iter().find_map(|key| {
+                let missing = Value::Null;
+                difference(a.get(key).unwrap_or(&missing), b.get(key).unwrap_or(&missing), format!("{}.{}", path, key))
+            })
+        }
+        (Value::Array(a), Value::Array(b)) if a.len() == b.len() => {
+            a.iter().zip(b).enumerate().find_map(|(i, (a, b))| difference(a, b, format!("{}[{}]", path, i)))
+        }
+        _ if expected == actual => None,
+        _ => Some(format!("{}: expected {}, found {}", path, expected, actual)),
+    }
+}
+
+// Compares our tree for `source` with CPython's dump of it
+fn check(name: &str, source: &str, dump: &str) -> Option<String> {
+    let program = match parse_program(&tokenize_spanned(source)) {
+        Ok(program) => program,
+        Err(e) => return Some(format!("{}: failed to parse: {:?}", name, e)),
+    };
+
+    let expected = normalize(parse_dump(dump));
+    let actual = normalize(to_python_ast(&program));
+    difference(&expected, &actual, name.into())
+}
+
+fn assert_conforms(failures: Vec<String>) {
+    assert!(failures.is_empty(), "{} snippets differ from CPython:\n{}", failures.len(), failures.join("\n"));
+}
+
+#[test]
+fn golden_files() {
+    let corpus = corpus();
+    assert!(!corpus.is_empty());
+
+    let failures = corpus.iter().filter_map(|path| {
+        let name = path.file_name().unwrap().to_string_lossy();
+        let source = fs::read_to_string(path).unwrap();
+        let dump = fs::read_to_string(path.with_extension("ast"))
+            .unwrap_or_else(|_| panic!("{} has no golden file, run generate.py", name));
+        check(&name, &source, &dump)
+    }).collect();
+
+    assert_conforms(failures);
+}
+
+// The same comparison against whatever python3 is installed, skipped when
+// there is none
+#[test]
+fn local_python() {
+    let script = "import ast, sys\nprint(ast.dump(ast.parse(sys.stdin.read()), include_attributes=True))";
+    if Command::new("python3").arg("--version").output().is_err() {
+        eprintln!("python3 not found, skipping");
+        return;
+    }
+
+    let failures = corpus().iter().filter_map(|path| {
+        let name = path.file_name().unwrap().to_string_lossy();
+        let source = fs::read_to_string(path).unwrap();
+
+        let mut child = Command::new("python3").args(["-c", script])
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        std::io::Write::write_all(&mut child.stdin.take().unwrap(), source.as_bytes()).unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success(), "python3 could not parse {}", name);
+
+        check(&name, &source, &String::from_utf8(output.stdout).unwrap())
+    }).collect();
+
+    assert_conforms(failures);
+}
+
+#[test]
+fn dump_parser() {
+    let value = parse_dump("Module(body=[Expr(value=Constant(value='it\\'s \"x\"\\n', kind=None))], type_ignores=[], n=-1)");
+    assert_eq!(value, serde_json::json!({
+        "_type": "Module",
+        "body": [{ "_type": "Expr", "value": { "_type": "Constant", "value": "it's \"x\"\n", "kind": null } }],
+        "type_ignores": [],
+        "n": -1,
+    }));
+}
+
+#[test]
+fn differences_are_reported() {
+    let dump = "Module(body=[Expr(value=BinOp(\
+        left=Name(id='a', ctx=Load(), lineno=1, col_offset=0, end_lineno=1, end_col_offset=1), op=Add(), \
+        right=Name(id='b', ctx=Load(), lineno=1, col_offset=4, end_lineno=1, end_col_offset=5), \
+        lineno=1, col_offset=0, end_lineno=1, end_col_offset=5), \
+        lineno=1, col_offset=0, end_lineno=1, end_col_offset=5)], type_ignores=[])";
+    assert_eq!(check("same", "a + b", dump), None);
+    assert_eq!(
+        check("swapped", "b + a", dump).unwrap(),
+        "swapped.body[0].value.left.id: expected \"a\", found \"b\"",
+    );
+    assert_eq!(
+        check("spaced", "a +  b", dump).unwrap(),
+        "spaced.body[0].end_col_offset: expected 5, found 6",
+    );
+}
@@ -0,0 +1,11 @@
+a = 1 + 2 * 3
+b = 1 * 2 + 3
+c = 1 - 2 - 3
+d = 8 / 4 // 2 % 3
+e = (1 + 2) * 3
+f = 2 ** 3 * 4
+g = 4 * 2 ** 3
+h = 2 ** 3 ** 2
+i = -1 + x
+j = x - 1
+k = x * -2
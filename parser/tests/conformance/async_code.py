@@ -0,0 +1,7 @@
+async def fetch(url):
+    response = await get(url)
+    async with session as s:
+        await s.close()
+    async for item in stream:
+        await process(item)
+    return await response.json()
@@ -0,0 +1,8 @@
+a = "text"
+b = True
+c = False
+d = None
+e = (1, "two", x)
+f = ()
+g = (x,)
+h = 0
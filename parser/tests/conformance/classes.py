@@ -0,0 +1,10 @@
+class Empty:
+    pass
+class Child(Base, mixin.Mixin):
+    x: int
+    y: int = 0
+    def method(self):
+        return self.x
+@dataclass
+class Point:
+    pass
@@ -0,0 +1,7 @@
+a = x < y
+b = x == y != z
+c = x < y <= z > w >= v
+d = x + 1 < y
+e = x < y + 1
+f = x * 2 == y // 3
+g = (x < y) == z
@@ -0,0 +1,16 @@
+try:
+    risky()
+except ValueError as e:
+    raise RuntimeError("bad") from e
+except (TypeError, KeyError):
+    pass
+except:
+    raise
+else:
+    done = True
+finally:
+    cleanup()
+try:
+    pass
+except* OSError:
+    pass
@@ -0,0 +1,12 @@
+def empty():
+    pass
+def add(a, b):
+    return a + b
+def annotated(x: int, y: str = "y") -> bool:
+    return True
+def defaults(a, b=1, c=2):
+    return
+@decorator
+@module.decorator(arg)
+def decorated():
+    pass
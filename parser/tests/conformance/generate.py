@@ -0,0 +1,18 @@
+# Regenerates the golden .ast file next to every .py snippet in this
+# directory from CPython's own parser:
+#
+#     python3 parser/tests/conformance/generate.py
+#
+# The goldens were produced with Python 3.11. The harness ignores fields that
+# are None or empty lists, so dumps from newer versions compare the same.
+import ast
+import pathlib
+
+here = pathlib.Path(__file__).parent
+for source in sorted(here.glob("*.py")):
+    if source.name == "generate.py":
+        continue
+    tree = ast.parse(source.read_text(), filename=source.name)
+    dump = ast.dump(tree, indent=1, include_attributes=True)
+    source.with_suffix(".ast").write_text(dump + "\n")
+    print(source.with_suffix(".ast").name)
@@ -0,0 +1,8 @@
+def gen():
+    yield
+    yield 1
+    x = yield y
+    yield from other()
+    z = (yield)
+async def agen():
+    yield 1
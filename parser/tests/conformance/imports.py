@@ -0,0 +1,6 @@
+import os
+import os.path as p, sys
+from collections import OrderedDict
+from . import sibling
+from ..pkg.mod import a as b, c
+from .mod import (x, y as z)
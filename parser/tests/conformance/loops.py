@@ -0,0 +1,8 @@
+for x in items:
+    total = total + x
+for i, (k, v) in pairs:
+    pass
+else:
+    finished = True
+for obj.attr in values:
+    pass
@@ -0,0 +1,25 @@
+match command:
+    case "quit":
+        pass
+    case 0 | 1 | -1:
+        pass
+    case None:
+        pass
+    case True:
+        pass
+    case [x, y, *rest]:
+        pass
+    case (a, b):
+        pass
+    case {"key": value, **others}:
+        pass
+    case Point(x, y=0):
+        pass
+    case Color.RED:
+        pass
+    case [1, 2] as pair:
+        pass
+    case _ if guard:
+        pass
+    case other:
+        pass
@@ -0,0 +1,8 @@
+a = f()
+b = f(x, y + 1)
+c = obj.attr.other
+d = items[0]
+e = items[i + 1][j]
+f = obj.method(x).result[2]
+g = f(x)(y)
+h = (1, 2)[0]
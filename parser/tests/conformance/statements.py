@@ -0,0 +1,8 @@
+x = 1
+y: int
+z: list[int] = items
+f(x)
+pass
+raise
+raise Error
+raise Error("message") from cause
@@ -0,0 +1,8 @@
+with open(path) as f:
+    data = f.read()
+with a, b as c:
+    pass
+with (a as b, c as d):
+    pass
+with lock:
+    pass
@@ -0,0 +1,99 @@
+use parser::*;
+use tokenizer::tokenize_spanned;
+
+// The kinds of the nodes directly under a node
+fn child_kinds(node: &CstNode) -> Vec<CstKind> {
+    node.children.iter().filter_map(|child| match child {
+        CstElement::Node(node) => Some(node.kind),
+        CstElement::Token(_) => None,
+    }).collect()
+}
+
+fn child_node(node: &CstNode, index: usize) -> &CstNode {
+    node.children.iter().filter_map(|child| match child {
+        CstElement::Node(node) => Some(node),
+        CstElement::Token(_) => None,
+    }).nth(index).unwrap()
+}
+
+#[test]
+fn round_trip() {
+    let inputs = [
+        "x = 1",
+        "x = 1  # one\n\n\n# two\ny = (a +\n     b)\n",
+        "@d  # decorator\ndef f(a: int = 1, *, b) -> str:\n\treturn a \\\n\t\t+ b\n\n",
+        "match p:\n    case Point(x=0) | [1, *_]:  # hit\n        pass\n",
+        "try:\n    pass\nexcept* E as e:\n    raise\n",
+        "   \n\t\n",
+        "",
+        "def f(:\n    pass\nx = 1 +\n",
+    ];
+
+    for input in inputs {
+        let cst = parse_cst(input).unwrap();
+        assert_eq!(cst.to_string(), input);
+        assert_eq!(cst.kind, CstKind::Module);
+    }
+}
+
+#[test]
+fn cst_to_ast() {
+    let input = "import a as b  # x\n\nclass C[T: int](B):\n    def m(self): return self.v[0]\n";
+    let cst = parse_cst(input).unwrap();
+
+    assert_eq!(cst.to_ast().unwrap(), parse_program(&tokenize_spanned(input)).unwrap());
+}
+
+#[test]
+fn nodes_follow_the_ast() {
+    let input = "@d\ndef f(a=1):  # c\n    pass\nx = g(y)\n";
+    let cst = parse_cst(input).unwrap();
+
+    assert_eq!(child_kinds(&cst), vec![CstKind::Statement, CstKind::Statement]);
+
+    // A decorated definition takes in its decorators, the comment ends up in
+    // the statement it is written inside of
+    let def = child_node(&cst, 0);
+    assert_eq!(def.to_string(), "@d\ndef f(a=1):  # c\n    pass");
+    assert_eq!(child_kinds(def), vec![CstKind::Expression, CstKind::Parameter, CstKind::Expression, CstKind::Statement]);
+    assert_eq!(child_node(def, 1).to_string(), "a");
+
+    let assignment = child_node(&cst, 1);
+    let call = child_node(assignment, 0);
+    assert_eq!(call.to_string(), "g(y)");
+    assert_eq!(child_kinds(call), vec![CstKind::Expression, CstKind::Expression]);
+
+    // Any node can be turned back into an AST
+    let program = assignment.to_ast().unwrap();
+    assert_eq!(program.statements[0], parse_program(&tokenize_spanned("x = g(y)")).unwrap().statements[0]);
+}
+
+#[test]
+fn invalid_source() {
+    // Syntax errors still give a tree, only tokenize errors fail
+    let cst = parse_cst("x = = 1\n").unwrap();
+    assert_eq!(cst.to_string(), "x = = 1\n");
+    assert_eq!(cst.to_ast_recovering().1.len(), 1);
+
+    assert!(parse_cst("x = \"abc\n").is_err());
+}
+
+#[test]
+fn long_chains() {
+    // On a thread with 2MB of stack, far less than nesting a node per operand
+    // would need if the tree were built or walked recursively
+    std::thread::Builder::new().stack_size(2 << 20).spawn(|| {
+        let source = format!("x = 1{}\n", " + 1".repeat(100_000));
+        let cst = parse_cst(&source).unwrap();
+        assert_eq!(cst.to_string(), source);
+        assert_eq!(cst.to_ast().unwrap().statements.len(), 1);
+
+        let mut depth = 0;
+        let mut node = child_node(&cst, 0);
+        while let Some(CstElement::Node(child)) = node.children.iter().find(|c| matches!(c, CstElement::Node(_))) {
+            depth += 1;
+            node = child;
+        }
+        assert_eq!(depth, 100_001);
+    }).unwrap().join().unwrap();
+}
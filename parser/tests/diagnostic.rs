@@ -0,0 +1,84 @@
+use parser::*;
+use tokenizer::{decode_source, tokenize_spanned, try_tokenize};
+
+fn render(input: &str, color: bool) -> String {
+    let error = match parse_program(&tokenize_spanned(input)) {
+        Ok(program) => panic!("{input:?} parsed as {program:?}"),
+        Err(e) => e,
+    };
+    Diagnostic::from(&error).render("example.py", input, color)
+}
+
+#[test]
+fn parse_error_report() {
+    let input = "x = 1\nresult = total(a\n";
+
+    assert_eq!(render(input, false), "\
+error: '(' was never closed
+ --> example.py:2:15
+  |
+2 | result = total(a
+  |               ^
+  = help: add a closing ')'
+");
+}
+
+#[test]
+fn underline_covers_span() {
+    let input = "def g():\n\tf() = 1\n";
+
+    assert_eq!(render(input, false), "\
+error: cannot assign to expression
+ --> example.py:2:2
+  |
+2 | \tf() = 1
+  | \t^^^
+  = help: only names, attributes and tuples can be assigned to
+");
+}
+
+#[test]
+fn tokenize_error_report() {
+    let input = "name = \"Zoë\nprint(name)";
+    let error = try_tokenize(input).unwrap_err();
+
+    assert_eq!(Diagnostic::from(&error).render("example.py", input, false), "\
+error: unterminated string literal (detected at line 1)
+ --> example.py:1:8
+  |
+1 | name = \"Zoë
+  |        ^^^^
+  = help: add a closing '\"'
+");
+}
+
+#[test]
+fn encoding_error_report() {
+    // Undecodable source is shown lossily, where a byte can take up more
+    // than one
+    let bytes = b"# caf\xe9\n# coding: klingon\n";
+    let error = decode_source(bytes).unwrap_err();
+    let shown = String::from_utf8_lossy(bytes);
+
+    assert_eq!(Diagnostic::from(&error).render("example.py", &shown, false), "\
+error: unknown encoding: klingon
+ --> example.py:2:11
+  |
+2 | # coding: klingon
+  |           ^^^^^^^
+  = help: declare utf-8, latin-1 or ascii
+");
+
+    let bytes = b"s = \"\xe9t\xe9\"\n";
+    let error = decode_source(bytes).unwrap_err();
+    let rendered = Diagnostic::from(&error).render("example.py", &String::from_utf8_lossy(bytes), false);
+    assert!(rendered.contains("\n1 | s = \"\u{fffd}t\u{fffd}\"\n  |      ^\n"), "{}", rendered);
+}
+
+#[test]
+fn color() {
+    let report = render("x = )", true);
+
+    assert!(report.starts_with("\x1b[1;31merror\x1b[0m\x1b[1m: expected an expression\x1b[0m\n"), "{report:?}");
+    assert!(!render("x = )", false).contains('\x1b'));
+}
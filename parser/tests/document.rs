@@ -0,0 +1,130 @@
+use parser::*;
+use proptest::prelude::*;
+use tokenizer::{tokenize_spanned, Lexer};
+
+// The document holds what parsing its whole source gives, spans and byte
+// offsets included
+fn assert_parsed(document: &Document) {
+    let source = document.source();
+    let (program, errors) = parse_program_recovering(&tokenize_spanned(source));
+    let tokenize_errors: Vec<_> = Lexer::new(source).filter_map(Result::err).collect();
+
+    let json = |program| serde_json::to_value(program).unwrap();
+    assert_eq!(json(document.program()), json(&program), "for {:?}", source);
+    assert_eq!(document.errors().cloned().collect::<Vec<_>>(), errors, "for {:?}", source);
+    assert_eq!(document.tokenize_errors(), tokenize_errors, "for {:?}", source);
+}
+
+// Replaces the first occurrence of `old` in the document's source
+fn replace(document: &mut Document, old: &str, new: &str) -> std::ops::Range<usize> {
+    let start = document.source().find(old).unwrap();
+    let parsed = document.edit(start..start + old.len(), new);
+    assert_parsed(document);
+    parsed
+}
+
+#[test]
+fn edits() {
+    let source = "import os\n\ndef f(a):\n    return a + 1\n\nx = f(2)\ny = [x,\n     3]\n";
+    let cases = [
+        ("a + 1", "a * 2"),
+        ("x = f(2)\n", ""),
+        ("\n\ndef", "\n# comment\n\ndef"),
+        ("x = f(2)", "for i in x:\n    pass\nelse:\n    x = 1"),
+        ("import os\n", "@d\n"),
+        ("y = [x,\n", "y = (x,\n"),
+        ("return", "yield"),
+        ("3]", "3]\nz = $"),
+        ("x = f(2)\n", "try:\n    a\n"),
+        ("\n", ""),
+        ("import", "  import"),
+        ("f(a)", "f(\"a"),
+    ];
+
+    for (old, new) in cases {
+        let mut document = Document::new(source);
+        assert_parsed(&document);
+        replace(&mut document, old, new);
+    }
+}
+
+#[test]
+fn continuing_statements() {
+    let mut document = Document::new("try:\n    b\nexcept E:\n    pass\nc = 1\nd = 2\n");
+    replace(&mut document, "c = 1", "finally:\n    c = 1");
+    assert_eq!(document.program().statements.len(), 2);
+
+    replace(&mut document, "finally:", "else:");
+    replace(&mut document, "else:\n    c = 1\n", "");
+    assert_eq!(document.program().statements.len(), 2);
+
+    let mut document = Document::new("@d\n\nx = 1\ny = 2\n");
+    replace(&mut document, "x = 1", "def f():\n    pass");
+    assert_eq!(document.program().statements.len(), 2);
+}
+
+#[test]
+fn reuses_statements() {
+    let source: String = (0..100).map(|i| format!("def f{0}(x):\n    return x + {0}\n\n", i)).collect();
+    let mut document = Document::new(source);
+
+    // Only the statement edited and the one before it are parsed again, and
+    // the ones after move down a line
+    let parsed = replace(&mut document, "return x + 50\n", "y = x\n    return y + 50\n");
+    assert_eq!(parsed, 49..51);
+    let Stmt::FunctionDef { body, .. } = &document.program().statements[50].node else { panic!() };
+    assert_eq!(body.len(), 2);
+    assert_eq!(document.program().statements[51].span.start.line, 155);
+
+    let parsed = replace(&mut document, "def f99", "def g99");
+    assert_eq!(parsed, 98..100);
+    let parsed = replace(&mut document, "def f0(", "def g0(");
+    assert_eq!(parsed, 0..1);
+
+    // An edit within a line leaves the statements after it where they were
+    let parsed = replace(&mut document, "x + 7\n", "x + 7 + 1\n");
+    assert_eq!(parsed, 6..8);
+}
+
+#[test]
+fn long_chains() {
+    // Moving a long expression after an edit does not recurse down it
+    let mut document = Document::new(format!("x = 1\ny = 1{}\n", " + 1".repeat(100_000)));
+    assert_eq!(document.edit(0..6, "\nx = 2\n"), 0..1);
+    let span = document.program().statements[1].span;
+    assert_eq!((span.start.line, span.start.offset), (3, 7));
+    assert_eq!(span.end.offset, document.source().len() - 1);
+
+    assert_eq!(document.edit(11..12, "2"), 0..2);
+    assert_eq!(document.errors().count(), 0);
+}
+
+// Sources of a few statements, some broken, and edits that insert, delete
+// and replace text in them
+fn python_like() -> impl Strategy<Value = String> {
+    let line = prop_oneof![
+        "[a-c] = [0-9]",
+        "(if|while|for x in|def f\\(\\)|try|else|except|finally|elif [a-c]|class C):",
+        "    (pass|return [a-c]|[a-c]\\(\\)|x = \\$)",
+        "@d|\\(1,|2\\)|\"[a-z]|# [a-z]|",
+    ];
+    prop::collection::vec(line, 0..12).prop_map(|lines| lines.join("\n"))
+}
+
+fn edit() -> impl Strategy<Value = (prop::sample::Index, prop::sample::Index, String)> {
+    let text = "|\n|\n  |    |:\n    |\\\\\n|else:|x|\\(|\\)|@d\n|\\$|#|\"";
+    (any::<prop::sample::Index>(), any::<prop::sample::Index>(), text)
+}
+
+proptest! {
+    #[test]
+    fn random_edits(source in python_like(), edits in prop::collection::vec(edit(), 1..5)) {
+        let mut document = Document::new(source);
+        for (start, len, text) in edits {
+            let start = start.index(document.source().len() + 1);
+            let end = start + len.index(document.source().len() - start + 1);
+            document.edit(start..end, &text);
+            assert_parsed(&document);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use parser::*;
+use tokenizer::tokenize_spanned;
+fn program_eq(input: &str, expected: Program) {
+    let tokens = tokenize_spanned(input);
+
+    match parse_program(&tokens) {
+        Ok(program) => {
+            assert_eq!(program, expected)
+        }
+
+        Err(e) => panic!("failed to parse  program:  {:?}", e)
+    }
+}
+
+fn var(name: &str) -> Located<Expression> {
+    Expression::Variable(name.into()).into()
+}
+
+fn subscript(value: &str, index: Located<Expression>) -> Located<Expression> {
+    Expression::Subscript(Box::new(var(value)), Box::new(index)).into()
+}
+
+#[test]
+fn generic_function() {
+    let input = "def first[T](items: list[T], default: T = None) -> T:\n    pass";
+
+    let expected = Program {
+        statements: vec![
+            Stmt::FunctionDef {
+                name: "first".into(),
+                type_params: vec![TypeParam::TypeVar { name: "T".into(), bound: None }.into()],
+                params: vec![
+                    Parameter {
+                        name: "items".into(),
+                        annotation: Some(subscript("list", var("T"))),
+                        default: None,
+                    }.into(),
+                    Parameter {
+                        name: "default".into(),
+                        annotation: Some(var("T")),
+                        default: Some(Expression::None.into()),
+                    }.into(),
+                ],
+                returns: Some(var("T")),
+                body: vec![Stmt::Pass.into()],
+                decorator_list: vec![],
+                is_async: false,
+                is_generator: false,
+            }.into()
+        ]
+    };
+
+    program_eq(input, expected);
+}
+
+#[test]
+fn generic_class() {
+    let input = "class C[T: int, *Ts, **P](Base[T]):\n    size: int = 0\n    name: str";
+
+    let expected = Program {
+        statements: vec![
+            Stmt::ClassDef {
+                name: "C".into(),
+                type_params: vec![
+                    TypeParam::TypeVar { name: "T".into(), bound: Some(var("int")) }.into(),
+                    TypeParam::TypeVarTuple("Ts".into()).into(),
+                    TypeParam::ParamSpec("P".into()).into(),
+                ],
+                bases: vec![subscript("Base", var("T"))],
+                body: vec![
+                    Stmt::AnnAssign {
+                        target: "size".into(),
+                        annotation: var("int"),
+                        value: Some(Expression::Number(0).into()),
+                    }.into(),
+                    Stmt::AnnAssign {
+                        target: "name".into(),
+                        annotation: var("str"),
+                        value: None,
+                    }.into(),
+                ],
+                decorator_list: vec![],
+            }.into()
+        ]
+    };
+
+    program_eq(input, expected);
+}
+
+#[test]
+fn type_alias() {
+    let input = "type Alias[T] = dict[str, T]\ntype Point = tuple[int, int]\ntype = 1";
+
+    let expected = Program {
+        statements: vec![
+            Stmt::TypeAlias {
+                name: "Alias".into(),
+                type_params: vec![TypeParam::TypeVar { name: "T".into(), bound: None }.into()],
+                value: subscript("dict", Expression::Tuple(vec![var("str"), var("T")]).into()),
+            }.into(),
+            Stmt::TypeAlias {
+                name: "Point".into(),
+                type_params: vec![],
+                value: subscript("tuple", Expression::Tuple(vec![var("int"), var("int")]).into()),
+            }.into(),
+            Stmt::Assignment("type".into(), Expression::Number(1).into()).into(),
+        ]
+    };
+
+    program_eq(input, expected);
+}
+
+#[test]
+fn invalid_type_params() {
+    for input in ["def f[]():\n    pass", "class C[T:\n    pass", "type X[T]", "def f() -> :\n    pass"] {
+        assert!(parse_program(&tokenize_spanned(input)).is_err(), "{input:?} should not parse");
+    }
+}
@@ -0,0 +1,121 @@
+use parser::*;
+use tokenizer::tokenize_spanned;
+fn program_eq(input: &str, expected: Program) {
+    let tokens = tokenize_spanned(input);
+
+    match parse_program(&tokens) {
+        Ok(program) => {
+            assert_eq!(program, expected)
+        }
+
+        Err(e) => panic!("failed to parse  program:  {:?}", e)
+    }
+}
+
+fn var(name: &str) -> Located<Expression> {
+    Expression::Variable(name.into()).into()
+}
+
+#[test]
+fn async_def_await() {
+    let input = "async def fetch(session, retries=3):
+    async for k, v in session.stream():
+        await handle(k, v)
+    else:
+        pass
+    return await session.close() ** 2
+";
+
+    let expected = Program {
+        statements: vec![
+            Stmt::FunctionDef {
+                name: "fetch".into(),
+                params: vec![
+                    Parameter { name: "session".into(), annotation: None, default: None }.into(),
+                    Parameter { name: "retries".into(), annotation: None, default: Some(Expression::Number(3).into()) }.into(),
+                ],
+                body: vec![
+                    Stmt::For {
+                        target: Expression::Tuple(vec![var("k"), var("v")]).into(),
+                        iter: Expression::Call(
+                            Box::new(Expression::Attribute(Box::new(var("session")), "stream".into()).into()),
+                            vec![],
+                        ).into(),
+                        body: vec![Stmt::Expr(Expression::Await(Box::new(Expression::Call(
+                            Box::new(var("handle")),
+                            vec![var("k"), var("v")],
+                        ).into())).into()).into()],
+                        orelse: vec![Stmt::Pass.into()],
+                        is_async: true,
+                    }.into(),
+                    Stmt::Return(Some(Expression::BinaryOp(
+                        Box::new(Expression::Await(Box::new(Expression::Call(
+                            Box::new(Expression::Attribute(Box::new(var("session")), "close".into()).into()),
+                            vec![],
+                        ).into())).into()),
+                        BinaryOperator::Power,
+                        Box::new(Expression::Number(2).into()),
+                    ).into())).into(),
+                ],
+                type_params: vec![],
+                returns: None,
+                decorator_list: vec![],
+                is_async: true,
+                is_generator: false,
+            }.into()
+        ]
+    };
+
+    program_eq(input, expected);
+}
+
+#[test]
+fn plain_def_and_for() {
+    let input = "def main():\n    for x in items: run(x)\n    return\n";
+
+    let expected = Program {
+        statements: vec![
+            Stmt::FunctionDef {
+                name: "main".into(),
+                params: vec![],
+                body: vec![
+                    Stmt::For {
+                        target: var("x"),
+                        iter: var("items"),
+                        body: vec![Stmt::Expr(Expression::Call(Box::new(var("run")), vec![var("x")]).into()).into()],
+                        orelse: vec![],
+                        is_async: false,
+                    }.into(),
+                    Stmt::Return(None).into(),
+                ],
+                type_params: vec![],
+                returns: None,
+                decorator_list: vec![],
+                is_async: false,
+                is_generator: false,
+            }.into()
+        ]
+    };
+
+    program_eq(input, expected);
+}
+
+#[test]
+fn await_outside_async_function() {
+    let cases = [
+        ("x = await y", "await"),
+        ("def f():\n    await y", "await"),
+        ("async def f():\n    def g():\n        return await y", "await"),
+        ("async with a: pass", "async with"),
+        ("def f():\n    async for x in y: pass", "async for"),
+        ("for x[await y] in z: pass", "await"),
+        ("with a as b(await y).c: pass", "await"),
+    ];
+
+    for (input, construct) in cases {
+        match parse_program(&tokenize_spanned(input)) {
+            Err(ParseError { kind: ParseErrorKind::OutsideAsyncFunction(found), .. }) => assert_eq!(found, construct),
+            other => panic!("{input:?} parsed as {other:?}"),
+        }
+    }
+}
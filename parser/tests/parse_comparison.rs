@@ -1,9 +1,9 @@
 use std::vec;
 
 use parser::*;
-use tokenizer::tokenize;
+use tokenizer::tokenize_spanned;
 fn program_eq(input: &str, expected: Program) {
-    let tokens = tokenize(input);
+    let tokens = tokenize_spanned(input);
 
     match parse_program(&tokens) {
         Ok(program) => {
@@ -21,8 +21,8 @@ fn parse_boolean() {
     let expected = Program {
         statements: vec![
             Stmt::Assignment(
-                "x".to_string(),
-                Expression::Boolean(false))
+                "x".into(),
+                Expression::Boolean(false).into()).into()
         ]
     };
 
@@ -30,6 +30,27 @@ fn parse_boolean() {
 
 }
 
+#[test]
+fn comparison_chains() {
+    let number = |n| Box::new(Located::from(Expression::Number(n)));
+
+    let expected = Program {
+        statements: vec![
+            Stmt::Expr(Expression::Compare(
+                number(1),
+                vec![(CompareOperator::LessThan, *number(2)), (CompareOperator::Equal, *number(3))],
+            ).into()).into(),
+            Stmt::Expr(Expression::CompareOp(
+                Box::new(Expression::CompareOp(number(1), CompareOperator::LessThan, number(2)).into()),
+                CompareOperator::Equal,
+                number(3),
+            ).into()).into(),
+        ]
+    };
+
+    program_eq("1 < 2 == 3\n(1 < 2) == 3\n", expected);
+}
+
 #[test]
 fn parse_comparison() {
     let input = "x = (5 == 5) && (3 > 2)";
@@ -37,24 +58,24 @@ fn parse_comparison() {
     let expected = Program {
         statements: vec![
             Stmt::Assignment(
-                "x".to_string(),
+                "x".into(),
                 Expression::CompareOp(
                     Box::new(
                         Expression::CompareOp(
-                            Box::new(Expression::Number(5)),
+                            Box::new(Expression::Number(5).into()),
                             CompareOperator::Equal,
-                            Box::new(Expression::Number(5))
-                        )),
+                            Box::new(Expression::Number(5).into())
+                        ).into()),
                     CompareOperator::And,
                     Box::new(
                         Expression::CompareOp(
-                            Box::new(Expression::Number(3)),
+                            Box::new(Expression::Number(3).into()),
                             CompareOperator::GreaterThan,
-                            Box::new(Expression::Number(2))
-                        )
+                            Box::new(Expression::Number(2).into())
+                        ).into()
                     )
-                )
-            )
+                ).into()
+            ).into()
         ]
     };
 
@@ -0,0 +1,98 @@
+use parser::*;
+use tokenizer::tokenize_spanned;
+fn program_eq(input: &str, expected: Program) {
+    let tokens = tokenize_spanned(input);
+
+    match parse_program(&tokens) {
+        Ok(program) => {
+            assert_eq!(program, expected)
+        }
+
+        Err(e) => panic!("failed to parse  program:  {:?}", e)
+    }
+}
+
+fn var(name: &str) -> Located<Expression> {
+    Expression::Variable(name.into()).into()
+}
+
+fn attr(value: Located<Expression>, name: &str) -> Located<Expression> {
+    Expression::Attribute(Box::new(value), name.into()).into()
+}
+
+#[test]
+fn function_decorators() {
+    let input = "@route(\"/\")
+@buttons[0].clicked.connect
+@task
+async def index():
+    pass
+";
+
+    let expected = Program {
+        statements: vec![
+            Stmt::FunctionDef {
+                name: "index".into(),
+                type_params: vec![],
+                params: vec![],
+                returns: None,
+                body: vec![Stmt::Pass.into()],
+                decorator_list: vec![
+                    Expression::Call(Box::new(var("route")), vec![Expression::String("/".into()).into()]).into(),
+                    attr(
+                        attr(
+                            Expression::Subscript(Box::new(var("buttons")), Box::new(Expression::Number(0).into())).into(),
+                            "clicked",
+                        ),
+                        "connect",
+                    ),
+                    var("task"),
+                ],
+                is_async: true,
+                is_generator: false,
+            }.into()
+        ]
+    };
+
+    program_eq(input, expected);
+}
+
+#[test]
+fn class_decorators() {
+    let input = "@dataclass
+class Point(Base, mixins.Eq):
+    @property
+    def norm(self):
+        return self.x
+";
+
+    let expected = Program {
+        statements: vec![
+            Stmt::ClassDef {
+                name: "Point".into(),
+                bases: vec![var("Base"), attr(var("mixins"), "Eq")],
+                body: vec![Stmt::FunctionDef {
+                    name: "norm".into(),
+                    params: vec![Parameter { name: "self".into(), annotation: None, default: None }.into()],
+                    body: vec![Stmt::Return(Some(attr(var("self"), "x"))).into()],
+                    type_params: vec![],
+                    returns: None,
+                    decorator_list: vec![var("property")],
+                    is_async: false,
+                    is_generator: false,
+                }.into()],
+                type_params: vec![],
+                decorator_list: vec![var("dataclass")],
+            }.into()
+        ]
+    };
+
+    program_eq(input, expected);
+}
+
+#[test]
+fn invalid_decorators() {
+    for input in ["@task\nx = 1", "@\ndef f(): pass", "@task def f(): pass", "@task\n"] {
+        assert!(parse_program(&tokenize_spanned(input)).is_err(), "{input:?} should not parse");
+    }
+}
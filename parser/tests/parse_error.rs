@@ -0,0 +1,59 @@
+use parser::*;
+use tokenizer::{tokenize_spanned, Position, Token};
+
+fn parse_error(input: &str) -> ParseError {
+    match parse_program(&tokenize_spanned(input)) {
+        Ok(program) => panic!("{input:?} parsed as {program:?}"),
+        Err(e) => e,
+    }
+}
+
+fn at(line: usize, column: usize, offset: usize) -> Position {
+    Position { line, column, offset }
+}
+
+#[test]
+fn unexpected_token_span_and_expected() {
+    let error = parse_error("x = 1\nf(a b)");
+
+    assert_eq!(error.kind, ParseErrorKind::UnexpectedToken(Token::Identifier("b".into())));
+    assert_eq!((error.span.start, error.span.end), (at(2, 4, 10), at(2, 5, 11)));
+    assert_eq!(error.expected, vec!["')'", "','"]);
+    assert_eq!(error.to_string(), "expected ')' or ','");
+}
+
+#[test]
+fn unclosed_bracket_points_at_opening() {
+    let error = parse_error("x = a[1");
+
+    assert_eq!(error.kind, ParseErrorKind::MismatchParenthesis('['));
+    assert_eq!((error.span.start, error.span.end), (at(1, 5, 5), at(1, 6, 6)));
+    assert_eq!(error.to_string(), "'[' was never closed");
+}
+
+#[test]
+fn messages() {
+    let cases = [
+        ("def f()\n    pass", "expected ':'"),
+        ("if_ = 1\n    y = 2", "unexpected indent"),
+        ("def f():\npass", "expected an indented block"),
+        ("x = ", "expected an expression"),
+        ("x = 1 2", "invalid syntax"),
+        ("def (): pass", "expected an identifier"),
+        ("f() = 1", "cannot assign to expression"),
+        ("try:\n    pass\nexcept:\n    pass\nexcept E:\n    pass", "default 'except:' must be last"),
+        ("yield 1", "'yield' outside function"),
+        ("x = await y", "'await' outside async function"),
+        ("async def f():\n    yield from x", "'yield from' inside async function"),
+    ];
+
+    for (input, message) in cases {
+        assert_eq!(parse_error(input).to_string(), message, "{input:?}");
+    }
+}
+
+#[test]
+fn error_trait() {
+    let error: Box<dyn std::error::Error> = Box::new(parse_error("x = )"));
+    assert_eq!(error.to_string(), "expected an expression");
+}
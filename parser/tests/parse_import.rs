@@ -0,0 +1,80 @@
+use parser::*;
+use tokenizer::tokenize_spanned;
+fn program_eq(input: &str, expected: Program) {
+    let tokens = tokenize_spanned(input);
+
+    match parse_program(&tokens) {
+        Ok(program) => {
+            assert_eq!(program, expected)
+        }
+
+        Err(e) => panic!("failed to parse  program:  {:?}", e)
+    }
+}
+
+fn alias(name: &str, asname: Option<&str>) -> Located<Alias> {
+    Alias {
+        name: name.into(),
+        asname: asname.map(|a| a.into()),
+    }.into()
+}
+
+#[test]
+fn import_dotted_as() {
+    let input = "import a.b as c, d";
+
+    let expected = Program {
+        statements: vec![
+            Stmt::Import(vec![alias("a.b", Some("c")), alias("d", None)]).into()
+        ]
+    };
+
+    program_eq(input, expected);
+}
+
+#[test]
+fn import_from_relative() {
+    let input = "from . import x\nfrom ..pkg import (a,\n    b as c,\n)";
+
+    let expected = Program {
+        statements: vec![
+            Stmt::ImportFrom {
+                module: None,
+                names: vec![alias("x", None)],
+                level: 1,
+            }.into(),
+            Stmt::ImportFrom {
+                module: Some("pkg".into()),
+                names: vec![alias("a", None), alias("b", Some("c"))],
+                level: 2,
+            }.into(),
+        ]
+    };
+
+    program_eq(input, expected);
+}
+
+#[test]
+fn import_from_star() {
+    let input = "from os.path import *\nx = 1";
+
+    let expected = Program {
+        statements: vec![
+            Stmt::ImportFrom {
+                module: Some("os.path".into()),
+                names: vec![alias("*", None)],
+                level: 0,
+            }.into(),
+            Stmt::Assignment("x".into(), Expression::Number(1).into()).into(),
+        ]
+    };
+
+    program_eq(input, expected);
+}
+
+#[test]
+fn import_errors() {
+    for input in ["from m import a,", "from m import (a", "import a as", "from . import", "import a b"] {
+        assert!(parse_program(&tokenize_spanned(input)).is_err(), "{input:?} should not parse");
+    }
+}
@@ -0,0 +1,79 @@
+use parser::*;
+use tokenizer::tokenize_spanned;
+
+fn parse(input: &str) -> Program {
+    match parse_program(&tokenize_spanned(input)) {
+        Ok(program) => program,
+        Err(e) => panic!("failed to parse  program:  {:?}", e)
+    }
+}
+
+// (lineno, col_offset, end_lineno, end_col_offset) as CPython's ast reports them
+fn loc<T>(node: &Located<T>) -> (usize, usize, usize, usize) {
+    (node.span.start.line, node.span.start.column, node.span.end.line, node.span.end.column)
+}
+
+#[test]
+fn expression_locations() {
+    let program = parse("x = (1, 2,)\ny = (1 + 2)\nf(a)[0].b\n");
+
+    let Stmt::Assignment(_, tuple) = &program.statements[0].node else { panic!() };
+    let Expression::Tuple(elements) = &tuple.node else { panic!() };
+    assert_eq!(loc(&program.statements[0]), (1, 0, 1, 11));
+    assert_eq!(loc(tuple), (1, 4, 1, 11));
+    assert_eq!(loc(&elements[1]), (1, 8, 1, 9));
+
+    // Parentheses are part of a tuple, but not of other expressions they wrap
+    let Stmt::Assignment(_, sum) = &program.statements[1].node else { panic!() };
+    assert_eq!(loc(&program.statements[1]), (2, 0, 2, 11));
+    assert_eq!(loc(sum), (2, 5, 2, 10));
+
+    let Stmt::Expr(attribute) = &program.statements[2].node else { panic!() };
+    let Expression::Attribute(subscript, _) = &attribute.node else { panic!() };
+    let Expression::Subscript(call, index) = &subscript.node else { panic!() };
+    assert_eq!(loc(attribute), (3, 0, 3, 9));
+    assert_eq!(loc(subscript), (3, 0, 3, 7));
+    assert_eq!(loc(call), (3, 0, 3, 4));
+    assert_eq!(loc(index), (3, 5, 3, 6));
+    assert_eq!((index.span.start.offset, index.span.end.offset), (29, 30));
+}
+
+#[test]
+fn compound_statement_locations() {
+    let program = parse("@d\ndef f(a: int = 1):\n    pass\n\n# done\nx = 1\n");
+
+    // A decorated definition starts at `def` and ends with its body
+    let Stmt::FunctionDef { params, body, decorator_list, .. } = &program.statements[0].node else { panic!() };
+    assert_eq!(loc(&program.statements[0]), (2, 0, 3, 8));
+    assert_eq!(loc(&decorator_list[0]), (1, 1, 1, 2));
+    assert_eq!(loc(&params[0]), (2, 6, 2, 12));
+    assert_eq!(loc(&body[0]), (3, 4, 3, 8));
+    assert_eq!(loc(&program.statements[1]), (6, 0, 6, 5));
+}
+
+#[test]
+fn pattern_locations() {
+    let program = parse("match x:\n    case [1, *_] | {\"a\": 2} as y if z:\n        pass\n");
+
+    let Stmt::Match { cases, .. } = &program.statements[0].node else { panic!() };
+    let Pattern::As(Some(or), _) = &cases[0].pattern.node else { panic!() };
+    let Pattern::Or(alternatives) = &or.node else { panic!() };
+    let Pattern::Sequence(items) = &alternatives[0].node else { panic!() };
+
+    assert_eq!(loc(&program.statements[0]), (1, 0, 3, 12));
+    assert_eq!(loc(&cases[0].pattern), (2, 9, 2, 32));
+    assert_eq!(loc(or), (2, 9, 2, 27));
+    assert_eq!(loc(&alternatives[0]), (2, 9, 2, 16));
+    assert_eq!(loc(&alternatives[1]), (2, 19, 2, 27));
+    assert_eq!(loc(&items[1]), (2, 13, 2, 15));
+}
+
+#[test]
+fn error_locations() {
+    let (program, errors) = parse_program_recovering(&tokenize_spanned("x = 1\ndef f:\n    pass\ny = await z\n"));
+
+    assert_eq!(loc(&program.statements[1]), (2, 0, 3, 8));
+    assert_eq!(errors.len(), 2);
+    // Semantic errors point at the offending expression
+    assert_eq!((errors[1].span.start.line, errors[1].span.start.column), (4, 4));
+}
@@ -0,0 +1,230 @@
+use parser::*;
+use tokenizer::tokenize_spanned;
+fn program_eq(input: &str, expected: Program) {
+    let tokens = tokenize_spanned(input);
+
+    match parse_program(&tokens) {
+        Ok(program) => {
+            assert_eq!(program, expected)
+        }
+
+        Err(e) => panic!("failed to parse  program:  {:?}", e)
+    }
+}
+
+fn var(name: &str) -> Located<Expression> {
+    Expression::Variable(name.into()).into()
+}
+
+fn capture(name: &str) -> Located<Pattern> {
+    Pattern::As(None, Some(name.into())).into()
+}
+
+fn case(pattern: Located<Pattern>) -> MatchCase {
+    MatchCase { pattern, guard: None, body: vec![Stmt::Pass.into()] }
+}
+
+#[test]
+fn literal_capture_wildcard_value() {
+    let input = "match command:
+    case 1 | -2 | \"three\":
+        pass
+    case None | True:
+        pass
+    case Color.RED:
+        pass
+    case x if x > 3:
+        pass
+    case _:
+        pass
+";
+
+    let expected = Program {
+        statements: vec![
+            Stmt::Match {
+                subject: var("command"),
+                cases: vec![
+                    case(Pattern::Or(vec![
+                        Pattern::Value(Expression::Number(1).into()).into(),
+                        Pattern::Value(Expression::Number(-2).into()).into(),
+                        Pattern::Value(Expression::String("three".into()).into()).into(),
+                    ]).into()),
+                    case(Pattern::Or(vec![
+                        Pattern::Singleton(Expression::None.into()).into(),
+                        Pattern::Singleton(Expression::Boolean(true).into()).into(),
+                    ]).into()),
+                    case(Pattern::Value(Expression::Attribute(Box::new(var("Color")), "RED".into()).into()).into()),
+                    MatchCase {
+                        pattern: capture("x"),
+                        guard: Some(Expression::CompareOp(
+                            Box::new(var("x")),
+                            CompareOperator::GreaterThan,
+                            Box::new(Expression::Number(3).into()),
+                        ).into()),
+                        body: vec![Stmt::Pass.into()],
+                    },
+                    case(Pattern::As(None, None).into()),
+                ],
+            }.into()
+        ]
+    };
+
+    program_eq(input, expected);
+}
+
+#[test]
+fn sequence_mapping_class_as() {
+    let input = "match a, b:
+    case [x, *rest] | (x, *rest):
+        pass
+    case first, *others:
+        pass
+    case {\"id\": id, **extra}:
+        pass
+    case Point(0, y=(1 | 2) as y_value):
+        pass
+    case ():
+        pass
+";
+
+    let expected = Program {
+        statements: vec![
+            Stmt::Match {
+                subject: Expression::Tuple(vec![var("a"), var("b")]).into(),
+                cases: vec![
+                    case(Pattern::Or(vec![
+                        Pattern::Sequence(vec![capture("x"), Pattern::Star(Some("rest".into())).into()]).into(),
+                        Pattern::Sequence(vec![capture("x"), Pattern::Star(Some("rest".into())).into()]).into(),
+                    ]).into()),
+                    case(Pattern::Sequence(vec![capture("first"), Pattern::Star(Some("others".into())).into()]).into()),
+                    case(Pattern::Mapping {
+                        keys: vec![Expression::String("id".into()).into()],
+                        patterns: vec![capture("id")],
+                        rest: Some("extra".into()),
+                    }.into()),
+                    case(Pattern::Class {
+                        cls: var("Point"),
+                        patterns: vec![Pattern::Value(Expression::Number(0).into()).into()],
+                        kwd_attrs: vec!["y".into()],
+                        kwd_patterns: vec![Pattern::As(
+                            Some(Box::new(Pattern::Or(vec![
+                                Pattern::Value(Expression::Number(1).into()).into(),
+                                Pattern::Value(Expression::Number(2).into()).into(),
+                            ]).into())),
+                            Some("y_value".into()),
+                        ).into()],
+                    }.into()),
+                    case(Pattern::Sequence(vec![]).into()),
+                ],
+            }.into()
+        ]
+    };
+
+    program_eq(input, expected);
+}
+
+#[test]
+fn soft_keywords() {
+    let input = "match = case(1)\nmatch(match)\ncase = match.case";
+
+    let expected = Program {
+        statements: vec![
+            Stmt::Assignment(
+                "match".into(),
+                Expression::Call(Box::new(var("case")), vec![Expression::Number(1).into()]).into(),
+            ).into(),
+            Stmt::Expr(Expression::Call(Box::new(var("match")), vec![var("match")]).into()).into(),
+            Stmt::Assignment(
+                "case".into(),
+                Expression::Attribute(Box::new(var("match")), "case".into()).into(),
+            ).into(),
+        ]
+    };
+
+    program_eq(input, expected);
+}
+
+#[test]
+fn invalid_patterns() {
+    for input in [
+        "match x:\n    pass\n",
+        "match x:\n    case y as _:\n        pass\n",
+        "match x:\n    case *y:\n        pass\n",
+        "match x:\n    case Point(x=1, 2):\n        pass\n",
+        "match x:\n    case {**rest, \"a\": 1}:\n        pass\n",
+        "match x:\n    case [a, b:\n        pass\n",
+        "match x:\n    case {**_}:\n        pass\n",
+        "match x:\n    case - -5:\n        pass\n",
+        "match x:\n    case - -9223372036854775808:\n        pass\n",
+    ] {
+        assert!(parse_program(&tokenize_spanned(input)).is_err(), "{input:?} should not parse");
+    }
+}
+
+#[test]
+fn double_star_not_last() {
+    let input = "match x:\n    case {**rest, \"a\": 1}:\n        pass\n";
+    let error = parse_program(&tokenize_spanned(input)).unwrap_err();
+    assert_eq!(error.kind, ParseErrorKind::DoubleStarNotLast);
+    assert_eq!((error.span.start.column, error.span.end.column), (10, 16));
+    assert_eq!(error.to_string(), "'**' pattern must be last");
+}
+
+#[test]
+fn alternatives_bind_different_names() {
+    for input in [
+        "match x:\n    case [a] | (b,):\n        pass\n",
+        "match x:\n    case {\"k\": a, **b} | {\"k\": a}:\n        pass\n",
+        "match x:\n    case (1 as a) | 2:\n        pass\n",
+    ] {
+        let error = parse_program(&tokenize_spanned(input)).unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::AlternativesBindDifferentNames, "{input:?}");
+        assert_eq!(error.to_string(), "alternative patterns bind different names");
+    }
+
+    // The same names in any order and nesting
+    let input = "match x:\n    case [a, *b] | P(b, y=a) | {1: a, **b} | [*b, a]:\n        pass\n";
+    assert!(parse_program(&tokenize_spanned(input)).is_ok());
+}
+
+#[test]
+fn unreachable_patterns() {
+    let input = "match x:\n    case y:\n        pass\n    case 1:\n        pass\n";
+    let error = parse_program(&tokenize_spanned(input)).unwrap_err();
+    assert_eq!(error.kind, ParseErrorKind::UnreachablePatterns("name capture 'y'".to_string()));
+    assert_eq!((error.span.start.line, error.span.start.column, error.span.end.column), (2, 9, 10));
+    assert_eq!(error.to_string(), "name capture 'y' makes remaining patterns unreachable");
+
+    let input = "match x:\n    case 1 | _:\n        pass\n    case (2 | 3) as w:\n        pass\n";
+    assert_eq!(parse_program(&tokenize_spanned(input)).unwrap_err().to_string(), "wildcard makes remaining patterns unreachable");
+
+    let input = "match x:\n    case _ | 1:\n        pass\n";
+    let error = parse_program(&tokenize_spanned(input)).unwrap_err();
+    assert_eq!(error.to_string(), "wildcard makes remaining patterns unreachable");
+    assert_eq!((error.span.start.column, error.span.end.column), (9, 10));
+
+    // A guard can fail, and the last case may match anything
+    let input = "match x:\n    case y if y:\n        pass\n    case [_]:\n        pass\n    case _:\n        pass\n";
+    assert!(parse_program(&tokenize_spanned(input)).is_ok());
+}
+
+#[test]
+fn repeated_keyword_pattern() {
+    let input = "match x:\n    case Point(x=1, y=2, x=3):\n        pass\n";
+    let error = parse_program(&tokenize_spanned(input)).unwrap_err();
+    assert_eq!(error.kind, ParseErrorKind::RepeatedKeywordPattern("x".to_string()));
+    assert_eq!((error.span.start.column, error.span.end.column), (25, 26));
+    assert_eq!(error.to_string(), "attribute name repeated in class pattern: x");
+}
+
+#[test]
+fn subject_errors() {
+    // Once the header is followed by a case, errors in the subject are reported
+    let input = "match (x:\n    case 1:\n        pass\n";
+    assert!(parse_program(&tokenize_spanned(input)).is_err());
+
+    let input = "match x y:\n    case 1:\n        pass\n";
+    let error = parse_program(&tokenize_spanned(input)).unwrap_err();
+    assert_eq!(error.expected, vec!["':'"]);
+    assert_eq!(error.span.start.column, 8);
+}
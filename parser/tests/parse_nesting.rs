@@ -0,0 +1,187 @@
+use parser::*;
+use serde_json::Value;
+use tokenizer::tokenize_spanned;
+
+fn parenthesized(depth: usize) -> String {
+    format!("x = {}1{}\n", "(".repeat(depth), ")".repeat(depth))
+}
+
+fn nesting_error(input: &str, limit: usize) -> ParseError {
+    match parse_program_with_limit(&tokenize_spanned(input), limit) {
+        Ok(_) => panic!("parsed within a limit of {}", limit),
+        Err(e) => e,
+    }
+}
+
+#[test]
+fn nested_parentheses() {
+    assert!(parse_program(&tokenize_spanned(&parenthesized(DEFAULT_NESTING_LIMIT - 1))).is_ok());
+
+    // Deep enough to overflow the stack without the limit
+    let error = parse_program(&tokenize_spanned(&parenthesized(100_000))).unwrap_err();
+    assert_eq!(error.kind, ParseErrorKind::TooDeeplyNested);
+    assert_eq!(error.span.start.column, 4 + DEFAULT_NESTING_LIMIT);
+    assert_eq!(error.to_string(), "too deeply nested");
+}
+
+#[test]
+fn configurable_limit() {
+    let source = parenthesized(20);
+    let tokens = tokenize_spanned(&source);
+    assert!(parse_program_with_limit(&tokens, 30).is_ok());
+    assert_eq!(nesting_error(&parenthesized(20), 10).kind, ParseErrorKind::TooDeeplyNested);
+
+    // Larger limits are lowered to the most the parser has stack for
+    assert!(parse_program_with_limit(&tokens, usize::MAX).is_ok());
+    let deepest = parenthesized(MAX_NESTING_LIMIT);
+    assert_eq!(nesting_error(&deepest, usize::MAX).span.start.column, 4 + MAX_NESTING_LIMIT);
+
+    // The limit only lasts for its own parse
+    assert!(parse_program(&tokenize_spanned(&parenthesized(50))).is_ok());
+}
+
+#[test]
+fn nested_blocks_patterns_and_powers() {
+    let mut blocks = String::new();
+    for depth in 0..20 {
+        blocks += &format!("{}def f():\n", "    ".repeat(depth));
+    }
+    blocks += &format!("{}pass\n", "    ".repeat(20));
+    assert!(parse_program_with_limit(&tokenize_spanned(&blocks), 30).is_ok());
+    assert_eq!(nesting_error(&blocks, 10).kind, ParseErrorKind::TooDeeplyNested);
+
+    let patterns = format!("match x:\n    case {}y{}:\n        pass\n", "[".repeat(20), "]".repeat(20));
+    assert!(parse_program_with_limit(&tokenize_spanned(&patterns), 30).is_ok());
+    assert_eq!(nesting_error(&patterns, 10).kind, ParseErrorKind::TooDeeplyNested);
+
+    // `**` groups to the right, so a chain of them nests
+    let powers = format!("x = 2{}\n", " ** 2".repeat(100_000));
+    assert_eq!(nesting_error(&powers, DEFAULT_NESTING_LIMIT).kind, ParseErrorKind::TooDeeplyNested);
+}
+
+#[test]
+fn recovers_after_nesting_error() {
+    let input = format!("{}y = 2\n", parenthesized(1000));
+    let (program, errors) = parse_program_recovering(&tokenize_spanned(&input));
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, ParseErrorKind::TooDeeplyNested);
+    assert_eq!(program.statements.len(), 2);
+    assert!(matches!(program.statements[1].node, Stmt::Assignment(..)));
+}
+
+// Chains that group to the left are built in a loop, they are not limited and
+// neither checking nor dropping them recurses down the chain
+#[test]
+fn long_left_associative_chains() {
+    let chains = [
+        format!("x = 1{}\n", " + 1".repeat(100_000)),
+        format!("x = a{}\n", " < a".repeat(100_000)),
+        format!("x = a{}\n", " && a".repeat(100_000)),
+        format!("x = a{}\n", ".b".repeat(100_000)),
+        format!("x = f{}\n", "(1)[2]".repeat(100_000)),
+    ];
+    struct Identity;
+    impl Fold for Identity {}
+
+    for input in &chains {
+        let program = parse_program(&tokenize_spanned(input)).unwrap();
+        assert_eq!(program.statements.len(), 1);
+
+        let folded = Identity.fold_program(program);
+        assert_eq!(&unparse(&folded), input);
+        assert!(folded == parse_program(&tokenize_spanned(input)).unwrap());
+        // The chains differ only in their first operand, at the bottom
+        let changed = format!("x = z{}", &input[5..]);
+        assert!(folded != parse_program(&tokenize_spanned(&changed)).unwrap());
+    }
+
+    let generator = format!("def f():\n    x = (yield){}\n", " + 1".repeat(100_000));
+    let program = parse_program(&tokenize_spanned(&generator)).unwrap();
+    assert!(matches!(program.statements[0].node, Stmt::FunctionDef { is_generator: true, .. }));
+
+    let outside = format!("x = 1{} + (yield)\n", " + 1".repeat(100_000));
+    let error = parse_program(&tokenize_spanned(&outside)).unwrap_err();
+    assert_eq!(error.kind, ParseErrorKind::OutsideFunction("yield".into()));
+}
+
+// Runs on a thread with the 2MB stack Rust gives threads by default, so the
+// tests do not depend on the stack of the test harness
+fn on_small_stack(f: impl FnOnce() + Send + 'static) {
+    std::thread::Builder::new().stack_size(2 << 20).spawn(f).unwrap().join().unwrap();
+}
+
+#[test]
+fn parses_get_their_own_stack() {
+    on_small_stack(|| {
+        let depth = DEFAULT_NESTING_LIMIT - 1;
+        let calls = format!("x = {}1{}\n", "f(".repeat(depth), ")".repeat(depth));
+        assert!(parse_program(&tokenize_spanned(&calls)).is_ok());
+
+        let deeper = format!("x = {}1{}\n", "(".repeat(1000), ")".repeat(1000));
+        assert!(parse_program_with_limit(&tokenize_spanned(&deeper), 1001).is_ok());
+    });
+}
+
+#[test]
+fn long_chains_with_syntax_errors() {
+    on_small_stack(|| {
+        let input = format!("x = 1{} +\n", " + 1".repeat(60_000));
+        let tokens = tokenize_spanned(&input);
+
+        assert!(parse_program(&tokens).is_err());
+        let (program, errors) = parse_program_recovering(&tokens);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(program.statements.len(), 1);
+    });
+}
+
+#[test]
+fn long_chains_outside_programs() {
+    on_small_stack(|| {
+        let input = format!("x = 1{}\n", " + 1".repeat(200_000));
+        let mut program = parse_program(&tokenize_spanned(&input)).unwrap();
+
+        let Stmt::Assignment(_, value) = program.statements.pop().unwrap().node else { panic!() };
+        drop(value);
+    });
+}
+
+// serde_json drops a Value recursively, so it is taken apart first
+fn dismantle(value: Value) {
+    let mut pending = vec![value];
+    while let Some(value) = pending.pop() {
+        match value {
+            Value::Array(values) => pending.extend(values),
+            Value::Object(map) => pending.extend(map.into_iter().map(|(_, value)| value)),
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn long_chains_unparse_and_walk() {
+    on_small_stack(|| {
+        let input = format!("x = 1{}\n", " + 1".repeat(200_000));
+        let program = parse_program(&tokenize_spanned(&input)).unwrap();
+
+        assert_eq!(unparse(&program), input);
+        dismantle(to_python_ast(&program));
+
+        struct Statements(usize);
+        impl Visitor for Statements {
+            fn visit_stmt(&mut self, stmt: &Located<Stmt>) {
+                self.0 += 1;
+                walk_stmt(self, stmt)
+            }
+        }
+        let mut statements = Statements(0);
+        statements.visit_program(&program);
+        assert_eq!(statements.0, 1);
+
+        struct Noop;
+        impl VisitorMut for Noop {}
+        let mut program = program;
+        Noop.visit_program_mut(&mut program);
+    });
+}
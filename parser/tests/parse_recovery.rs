@@ -0,0 +1,108 @@
+use parser::*;
+use tokenizer::tokenize_spanned;
+
+#[test]
+fn reports_every_error() {
+    let input = "x = 1 +
+import
+def f:
+    y = 2
+z = 1 2
+w = 4
+";
+
+    let (program, errors) = parse_program_recovering(&tokenize_spanned(input));
+
+    assert_eq!(errors.len(), 4, "{errors:?}");
+    assert_eq!(program, Program {
+        statements: vec![
+            Stmt::Assignment("x".into(), Expression::Error.into()).into(),
+            Stmt::Error.into(),
+            Stmt::Error.into(),
+            Stmt::Error.into(),
+            Stmt::Assignment("w".into(), Expression::Number(4).into()).into(),
+        ]
+    });
+}
+
+#[test]
+fn recovers_inside_blocks() {
+    let input = "def f():
+    a = 1
+    )
+    return a +
+b = 2
+";
+
+    let (program, errors) = parse_program_recovering(&tokenize_spanned(input));
+
+    assert_eq!(errors.len(), 2, "{errors:?}");
+    assert_eq!(program, Program {
+        statements: vec![
+            Stmt::FunctionDef {
+                name: "f".into(),
+                type_params: vec![],
+                params: vec![],
+                returns: None,
+                body: vec![
+                    Stmt::Assignment("a".into(), Expression::Number(1).into()).into(),
+                    Stmt::Error.into(),
+                    Stmt::Return(Some(Expression::Error.into())).into(),
+                ],
+                decorator_list: vec![],
+                is_async: false,
+                is_generator: false,
+            }.into(),
+            Stmt::Assignment("b".into(), Expression::Number(2).into()).into(),
+        ]
+    });
+}
+
+#[test]
+fn semantic_errors_are_collected() {
+    let input = "yield 1\nawait x\nasync with a: pass";
+
+    let (program, errors) = parse_program_recovering(&tokenize_spanned(input));
+
+    assert_eq!(program.statements.len(), 3);
+    let kinds: Vec<_> = errors.into_iter().map(|e| e.kind).collect();
+    assert!(matches!(kinds.as_slice(), [
+        ParseErrorKind::OutsideFunction(_),
+        ParseErrorKind::OutsideAsyncFunction(_),
+        ParseErrorKind::OutsideAsyncFunction(_),
+    ]), "{kinds:?}");
+}
+
+#[test]
+fn syntax_errors_come_before_check_errors() {
+    let input = "yield 1\nx = )\nawait y\nz = 1 2\n";
+
+    let (_, errors) = parse_program_recovering(&tokenize_spanned(input));
+
+    let lines: Vec<_> = errors.iter().map(|e| e.span.start.line).collect();
+    assert_eq!(lines, [2, 4, 1, 3], "{errors:?}");
+}
+
+#[test]
+fn integers_too_large() {
+    // The tokenizer reports them, and the parser carries on with a placeholder
+    let input = "x = 99999999999999999999 + 1\nmatch x:\n    case -99999999999999999999:\n        pass\n";
+
+    let (program, errors) = parse_program_recovering(&tokenize_spanned(input));
+
+    assert!(errors.is_empty(), "{errors:?}");
+    let Stmt::Assignment(_, value) = &program.statements[0].node else { panic!() };
+    let Expression::BinaryOp(left, _, _) = &value.node else { panic!() };
+    assert_eq!(left.node, Expression::Error);
+    assert_eq!((left.span.start.column, left.span.end.column), (4, 24));
+}
+
+#[test]
+fn strict_mode_returns_first_error() {
+    let input = "x = )\ny = (";
+
+    assert!(matches!(parse_program(&tokenize_spanned(input)), Err(ParseError { kind: ParseErrorKind::UnexpectedToken(_), .. })));
+
+    let (_, errors) = parse_program_recovering(&tokenize_spanned("x = 1\ny = 2"));
+    assert!(errors.is_empty());
+}
@@ -0,0 +1,79 @@
+use std::fs;
+use std::path::Path;
+
+use parser::*;
+use tokenizer::{tokenize_spanned, Lexer, TokenizeErrorKind};
+
+// Parsing from a Lexer gives the tree, spans included, and the errors of
+// parsing the whole token vector
+fn assert_streams(input: &str) {
+    let (program, errors) = parse_program_recovering(&tokenize_spanned(input));
+    let (streamed, streamed_errors) = parse_lexer(Lexer::new(input)).unwrap();
+
+    assert_eq!(to_python_ast(&streamed), to_python_ast(&program), "for {:?}", input);
+    assert_eq!(streamed_errors, errors, "for {:?}", input);
+}
+
+#[test]
+fn statements_spanning_lines() {
+    let cases = [
+        "x = 1\ny = 2\n",
+        "if a:\n    b\nelif c:\n    d\nelse:\n    e\nf\n",
+        "if a: b\nelse: c\n",
+        "try:\n    a\nexcept E:\n    b\nelse:\n    c\nfinally:\n    d\n",
+        "for x in y:\n    a\nelse:\n    b\n",
+        "@d\n@e(1)\ndef f():\n    pass\n@d\nclass C:\n    pass\nx\n",
+        "x = (1,\n     2)\ny = [\n]\n",
+        "def f():\n    if a:\n        return 1\n    return 2\n\n\ng = f()\n",
+        "match x:\n    case 1:\n        pass\n    case _:\n        pass\ny\n",
+        "",
+        "x",
+    ];
+
+    for input in cases {
+        assert_streams(input);
+    }
+}
+
+#[test]
+fn errors() {
+    let cases = [
+        "x = = 1\ny = 2\n",
+        "try:\n    a\nx = 1\n",
+        "def f(:\n    a\n    b\nc\n",
+        "  x\ny\n",
+        "@d\nx = 1\n",
+        "return 1\nyield 2\n",
+        "else:\n    a\n",
+    ];
+
+    for input in cases {
+        assert_streams(input);
+    }
+}
+
+#[test]
+fn tokenize_error() {
+    let error = parse_lexer(Lexer::new("x = 1\ny = $\n")).unwrap_err();
+    assert_eq!(error.kind, TokenizeErrorKind::InvalidCharacter('$'));
+    assert_eq!(error.span.start.line, 2);
+}
+
+#[test]
+fn conformance_corpus() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance");
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|ext| ext == "py") {
+            assert_streams(&fs::read_to_string(path).unwrap());
+        }
+    }
+}
+
+#[test]
+fn long_program() {
+    let input: String = (0..10_000).map(|i| format!("def f{0}(x):\n    return x + {0}\n", i)).collect();
+    let (program, errors) = parse_lexer(Lexer::new(&input)).unwrap();
+    assert!(errors.is_empty());
+    assert_eq!(program.statements.len(), 10_000);
+}
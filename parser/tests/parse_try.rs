@@ -0,0 +1,133 @@
+use parser::*;
+use tokenizer::tokenize_spanned;
+fn program_eq(input: &str, expected: Program) {
+    let tokens = tokenize_spanned(input);
+
+    match parse_program(&tokens) {
+        Ok(program) => {
+            assert_eq!(program, expected)
+        }
+
+        Err(e) => panic!("failed to parse  program:  {:?}", e)
+    }
+}
+
+fn var(name: &str) -> Located<Expression> {
+    Expression::Variable(name.into()).into()
+}
+
+#[test]
+fn try_except_else_finally() {
+    let input = "try:
+    x = 1
+except (KeyError, errors.Missing) as e:
+    raise LookupError(\"missing\") from e
+except:
+    pass
+else:
+    y = 2
+finally:
+    cleanup()
+";
+
+    let expected = Program {
+        statements: vec![
+            Stmt::Try {
+                body: vec![Stmt::Assignment("x".into(), Expression::Number(1).into()).into()],
+                handlers: vec![
+                    ExceptHandler {
+                        exception: Some(Expression::Tuple(vec![
+                            var("KeyError"),
+                            Expression::Attribute(Box::new(var("errors")), "Missing".into()).into(),
+                        ]).into()),
+                        name: Some("e".into()),
+                        body: vec![Stmt::Raise {
+                            exc: Some(Expression::Call(
+                                Box::new(var("LookupError")),
+                                vec![Expression::String("missing".into()).into()],
+                            ).into()),
+                            cause: Some(var("e")),
+                        }.into()],
+                    }.into(),
+                    ExceptHandler {
+                        exception: None,
+                        name: None,
+                        body: vec![Stmt::Pass.into()],
+                    }.into(),
+                ],
+                orelse: vec![Stmt::Assignment("y".into(), Expression::Number(2).into()).into()],
+                finalbody: vec![Stmt::Expr(Expression::Call(Box::new(var("cleanup")), vec![]).into()).into()],
+                star: false,
+            }.into()
+        ]
+    };
+
+    program_eq(input, expected);
+}
+
+#[test]
+fn try_except_star() {
+    let input = "try: run()\nexcept* ValueError: pass\nexcept* (TypeError, KeyError) as eg:\n    raise";
+
+    let expected = Program {
+        statements: vec![
+            Stmt::Try {
+                body: vec![Stmt::Expr(Expression::Call(Box::new(var("run")), vec![]).into()).into()],
+                handlers: vec![
+                    ExceptHandler {
+                        exception: Some(var("ValueError")),
+                        name: None,
+                        body: vec![Stmt::Pass.into()],
+                    }.into(),
+                    ExceptHandler {
+                        exception: Some(Expression::Tuple(vec![var("TypeError"), var("KeyError")]).into()),
+                        name: Some("eg".into()),
+                        body: vec![Stmt::Raise { exc: None, cause: None }.into()],
+                    }.into(),
+                ],
+                orelse: vec![],
+                finalbody: vec![],
+                star: true,
+            }.into()
+        ]
+    };
+
+    program_eq(input, expected);
+}
+
+#[test]
+fn try_finally_then_statement() {
+    let input = "try:\n    pass\nfinally:\n    pass\nx = 1\n";
+
+    let expected = Program {
+        statements: vec![
+            Stmt::Try {
+                body: vec![Stmt::Pass.into()],
+                handlers: vec![],
+                orelse: vec![],
+                finalbody: vec![Stmt::Pass.into()],
+                star: false,
+            }.into(),
+            Stmt::Assignment("x".into(), Expression::Number(1).into()).into(),
+        ]
+    };
+
+    program_eq(input, expected);
+}
+
+#[test]
+fn default_except_not_last() {
+    let input = "try:\n    pass\nexcept:\n    pass\nexcept ValueError:\n    pass\n";
+
+    assert!(matches!(parse_program(&tokenize_spanned(input)), Err(ParseError { kind: ParseErrorKind::DefaultExceptNotLast, .. })));
+}
+
+#[test]
+fn invalid_try() {
+    let mixed = "try:\n    pass\nexcept ValueError:\n    pass\nexcept* TypeError:\n    pass\n";
+    assert!(matches!(parse_program(&tokenize_spanned(mixed)), Err(ParseError { kind: ParseErrorKind::MixedExceptStar, .. })));
+
+    for input in ["try:\n    pass\nx = 1", "try:\n    pass\nelse:\n    pass", "try:\n    pass\nexcept*:\n    pass", "raise from e"] {
+        assert!(parse_program(&tokenize_spanned(input)).is_err(), "{input:?} should not parse");
+    }
+}
@@ -0,0 +1,115 @@
+use parser::*;
+use tokenizer::tokenize_spanned;
+fn program_eq(input: &str, expected: Program) {
+    let tokens = tokenize_spanned(input);
+
+    match parse_program(&tokens) {
+        Ok(program) => {
+            assert_eq!(program, expected)
+        }
+
+        Err(e) => panic!("failed to parse  program:  {:?}", e)
+    }
+}
+
+fn var(name: &str) -> Located<Expression> {
+    Expression::Variable(name.into()).into()
+}
+
+#[test]
+fn with_multiple_items() {
+    let input = "with open(p) as f, lock:\n    data = f.read()\n";
+
+    let expected = Program {
+        statements: vec![
+            Stmt::With {
+                items: vec![
+                    WithItem {
+                        context_expr: Expression::Call(Box::new(var("open")), vec![var("p")]).into(),
+                        optional_vars: Some(var("f")),
+                    },
+                    WithItem {
+                        context_expr: var("lock"),
+                        optional_vars: None,
+                    },
+                ],
+                body: vec![Stmt::Assignment(
+                    "data".into(),
+                    Expression::Call(
+                        Box::new(Expression::Attribute(Box::new(var("f")), "read".into()).into()),
+                        vec![],
+                    ).into(),
+                ).into()],
+                is_async: false,
+            }.into()
+        ]
+    };
+
+    program_eq(input, expected);
+}
+
+#[test]
+fn with_parenthesized() {
+    let input = "with (\n    a as b,\n    c as d,\n):\n    pass\nwith (a, b) as c: pass";
+
+    let expected = Program {
+        statements: vec![
+            Stmt::With {
+                items: vec![
+                    WithItem { context_expr: var("a"), optional_vars: Some(var("b")) },
+                    WithItem { context_expr: var("c"), optional_vars: Some(var("d")) },
+                ],
+                body: vec![Stmt::Pass.into()],
+                is_async: false,
+            }.into(),
+            Stmt::With {
+                items: vec![
+                    WithItem {
+                        context_expr: Expression::Tuple(vec![var("a"), var("b")]).into(),
+                        optional_vars: Some(var("c")),
+                    },
+                ],
+                body: vec![Stmt::Pass.into()],
+                is_async: false,
+            }.into(),
+        ]
+    };
+
+    program_eq(input, expected);
+}
+
+#[test]
+fn async_with() {
+    let input = "async def main():\n    async with session as (s, t): pass";
+
+    let expected = Program {
+        statements: vec![
+            Stmt::FunctionDef {
+                name: "main".into(),
+                params: vec![],
+                body: vec![Stmt::With {
+                    items: vec![WithItem {
+                        context_expr: var("session"),
+                        optional_vars: Some(Expression::Tuple(vec![var("s"), var("t")]).into()),
+                    }],
+                    body: vec![Stmt::Pass.into()],
+                    is_async: true,
+                }.into()],
+                type_params: vec![],
+                returns: None,
+                decorator_list: vec![],
+                is_async: true,
+                is_generator: false,
+            }.into()
+        ]
+    };
+
+    program_eq(input, expected);
+}
+
+#[test]
+fn invalid_with() {
+    for input in ["with a as 1: pass", "with (a as b: pass", "with: pass", "with a, : pass"] {
+        assert!(parse_program(&tokenize_spanned(input)).is_err(), "{input:?} should not parse");
+    }
+}
@@ -0,0 +1,124 @@
+use parser::*;
+use tokenizer::tokenize_spanned;
+fn program_eq(input: &str, expected: Program) {
+    let tokens = tokenize_spanned(input);
+
+    match parse_program(&tokens) {
+        Ok(program) => {
+            assert_eq!(program, expected)
+        }
+
+        Err(e) => panic!("failed to parse  program:  {:?}", e)
+    }
+}
+
+fn var(name: &str) -> Located<Expression> {
+    Expression::Variable(name.into()).into()
+}
+
+#[test]
+fn yield_forms() {
+    let input = "def pipeline(source):
+    yield
+    yield a, b
+    received = yield value
+    yield from source
+    log((yield), (yield from other))
+";
+
+    let expected = Program {
+        statements: vec![
+            Stmt::FunctionDef {
+                name: "pipeline".into(),
+                params: vec![Parameter { name: "source".into(), annotation: None, default: None }.into()],
+                body: vec![
+                    Stmt::Expr(Expression::Yield(None).into()).into(),
+                    Stmt::Expr(Expression::Yield(Some(Box::new(Expression::Tuple(vec![var("a"), var("b")]).into()))).into()).into(),
+                    Stmt::Assignment("received".into(), Expression::Yield(Some(Box::new(var("value")))).into()).into(),
+                    Stmt::Expr(Expression::YieldFrom(Box::new(var("source"))).into()).into(),
+                    Stmt::Expr(Expression::Call(
+                        Box::new(var("log")),
+                        vec![Expression::Yield(None).into(), Expression::YieldFrom(Box::new(var("other"))).into()],
+                    ).into()).into(),
+                ],
+                type_params: vec![],
+                returns: None,
+                decorator_list: vec![],
+                is_async: false,
+                is_generator: true,
+            }.into()
+        ]
+    };
+
+    program_eq(input, expected);
+}
+
+fn generator_flags(program: &Program) -> Vec<(String, bool, bool)> {
+    fn collect(statements: &[Located<Stmt>], flags: &mut Vec<(String, bool, bool)>) {
+        for stmt in statements {
+            if let Stmt::FunctionDef { name, body, is_async, is_generator, .. } = &stmt.node {
+                flags.push((name.to_string(), *is_async, *is_generator));
+                collect(body, flags);
+            }
+        }
+    }
+
+    let mut flags = Vec::new();
+    collect(&program.statements, &mut flags);
+    flags
+}
+
+#[test]
+fn generator_detection() {
+    let input = "def outer():
+    def inner():
+        yield 1
+    return inner
+async def agen():
+    for x in source:
+        try:
+            pass
+        finally:
+            yield x
+async def coro():
+    return await fetch()
+def for_target():
+    for x[(yield)] in source:
+        pass
+def with_target():
+    with open() as f((yield)).y:
+        pass
+";
+
+    let program = parse_program(&tokenize_spanned(input)).unwrap();
+
+    assert_eq!(generator_flags(&program), vec![
+        ("outer".into(), false, false),
+        ("inner".into(), false, true),
+        ("agen".into(), true, true),
+        ("coro".into(), true, false),
+        ("for_target".into(), false, true),
+        ("with_target".into(), false, true),
+    ]);
+}
+
+#[test]
+fn invalid_yield() {
+    let cases = [
+        "yield 1",
+        "x = yield",
+        "async def f():\n    yield from g()",
+        "async def f():\n    yield 1\n    return 2",
+        "def f():\n    return yield x",
+        "def f():\n    f(yield x)",
+        "def f():\n    x = 1 + yield 2",
+    ];
+
+    for input in cases {
+        assert!(parse_program(&tokenize_spanned(input)).is_err(), "{input:?} should not parse");
+    }
+
+    assert!(matches!(parse_program(&tokenize_spanned(cases[0])), Err(ParseError { kind: ParseErrorKind::OutsideFunction(_), .. })));
+    assert!(matches!(parse_program(&tokenize_spanned(cases[2])), Err(ParseError { kind: ParseErrorKind::YieldFromInsideAsyncFunction, .. })));
+    assert!(matches!(parse_program(&tokenize_spanned(cases[3])), Err(ParseError { kind: ParseErrorKind::ReturnInsideAsyncGenerator, .. })));
+}
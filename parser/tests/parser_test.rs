@@ -1,7 +1,7 @@
 use parser::*;
-use tokenizer::tokenize;
+use tokenizer::tokenize_spanned;
 fn program_eq(input: &str, expected: Program) {
-    let tokens = tokenize(input);
+    let tokens = tokenize_spanned(input);
 
     match parse_program(&tokens) {
         Ok(program) => {
@@ -19,13 +19,13 @@ fn addition() {
     let expected = Program {
         statements: vec![
             Stmt::Assignment(
-                "x".to_string(), 
+                "x".into(), 
                 Expression::BinaryOp(
-                    Box::new(Expression::Number(1)),
+                    Box::new(Expression::Number(1).into()),
                     BinaryOperator::Add,
-                    Box::new(Expression::Number(2))
-                )
-            )
+                    Box::new(Expression::Number(2).into())
+                ).into()
+            ).into()
         ]
     };
 
@@ -38,13 +38,13 @@ fn subtract() {
     let expected = Program {
         statements: vec![
             Stmt::Assignment(
-                "x".to_string(), 
+                "x".into(), 
                 Expression::BinaryOp(
-                    Box::new(Expression::Number(1)),
+                    Box::new(Expression::Number(1).into()),
                     BinaryOperator::Subtract,
-                    Box::new(Expression::Number(2))
-                )
-            )
+                    Box::new(Expression::Number(2).into())
+                ).into()
+            ).into()
         ]
     };
 
@@ -57,13 +57,13 @@ fn multiply() {
     let expected = Program {
         statements: vec![
             Stmt::Assignment(
-                "x".to_string(), 
+                "x".into(), 
                 Expression::BinaryOp(
-                    Box::new(Expression::Number(1)),
+                    Box::new(Expression::Number(1).into()),
                     BinaryOperator::Multiply,
-                    Box::new(Expression::Number(2))
-                )
-            )
+                    Box::new(Expression::Number(2).into())
+                ).into()
+            ).into()
         ]
     };
 
@@ -76,13 +76,13 @@ fn divide() {
     let expected = Program {
         statements: vec![
             Stmt::Assignment(
-                "x".to_string(), 
+                "x".into(), 
                 Expression::BinaryOp(
-                    Box::new(Expression::Number(1)),
+                    Box::new(Expression::Number(1).into()),
                     BinaryOperator::Divide,
-                    Box::new(Expression::Number(2))
-                )
-            )
+                    Box::new(Expression::Number(2).into())
+                ).into()
+            ).into()
         ]
     };
 
@@ -96,13 +96,13 @@ fn floor_divide() {
     let expected = Program {
         statements: vec![
             Stmt::Assignment(
-                "x".to_string(), 
+                "x".into(), 
                 Expression::BinaryOp(
-                        Box::new(Expression::Number(1)),
+                        Box::new(Expression::Number(1).into()),
                         BinaryOperator::FloorDivide, 
-                        Box::new(Expression::Number(2))
-                    )
-                )
+                        Box::new(Expression::Number(2).into())
+                    ).into()
+                ).into()
         ]
     };
 
@@ -117,13 +117,13 @@ fn modulus() {
     let expected = Program {
         statements: vec![
             Stmt::Assignment(
-                "x".to_string(), 
+                "x".into(), 
                 Expression::BinaryOp(
-                        Box::new(Expression::Number(1)),
+                        Box::new(Expression::Number(1).into()),
                         BinaryOperator::Modulus, 
-                        Box::new(Expression::Number(2))
-                    )
-                )
+                        Box::new(Expression::Number(2).into())
+                    ).into()
+                ).into()
         ]
     };
 
@@ -138,13 +138,13 @@ fn power() {
     let expected = Program {
         statements: vec![
             Stmt::Assignment(
-                "x".to_string(), 
+                "x".into(), 
                 Expression::BinaryOp(
-                    Box::new(Expression::Number(1)),
+                    Box::new(Expression::Number(1).into()),
                     BinaryOperator::Power, 
-                    Box::new(Expression::Number(2))
-                )
-            )
+                    Box::new(Expression::Number(2).into())
+                ).into()
+            ).into()
         ]
     };
 
@@ -159,17 +159,17 @@ fn parentheses() {
     let expected = Program {
         statements: vec![
             Stmt::Assignment(
-                "x".to_string(),
+                "x".into(),
                 Expression::BinaryOp(
                     Box::new(Expression::BinaryOp(
-                        Box::new(Expression::Number(1)),
+                        Box::new(Expression::Number(1).into()),
                         BinaryOperator::Add,
-                        Box::new(Expression::Number(2))
-                    )),
+                        Box::new(Expression::Number(2).into())
+                    ).into()),
                     BinaryOperator::Multiply,
-                    Box::new(Expression::Number(3))
-                ),
-            ),
+                    Box::new(Expression::Number(3).into())
+                ).into(),
+            ).into(),
         ]
     };
 
@@ -183,42 +183,42 @@ fn order_operations() {
     let expected = Program {
         statements: vec![
             Stmt::Assignment(
-                "x".to_string(), 
+                "x".into(), 
                 Expression::BinaryOp(
                     Box::new(Expression::BinaryOp(
-                            Box::new(Expression::Number(1)),
+                            Box::new(Expression::Number(1).into()),
                             BinaryOperator::Add,
-                            Box::new(Expression::Number(2))
-                        )
+                            Box::new(Expression::Number(2).into())
+                        ).into()
                     ), 
                     BinaryOperator::Subtract,
                     Box::new(Expression::BinaryOp(
                         Box::new(Expression::BinaryOp(
                             Box::new(Expression::BinaryOp(
                                 Box::new(Expression::BinaryOp(
-                                    Box::new(Expression::Number(3)),
+                                    Box::new(Expression::Number(3).into()),
                                     BinaryOperator::Multiply,
-                                    Box::new(Expression::Number(4))
-                                )
+                                    Box::new(Expression::Number(4).into())
+                                ).into()
                             ), 
                             BinaryOperator::Divide,
-                            Box::new(Expression::Number(5))
-                        )),
+                            Box::new(Expression::Number(5).into())
+                        ).into()),
                         BinaryOperator::FloorDivide,
-                        Box::new(Expression::Number(6))
-                    )),
+                        Box::new(Expression::Number(6).into())
+                    ).into()),
                     BinaryOperator::Modulus,
                     Box::new(Expression::BinaryOp(
-                        Box::new(Expression::Number(7)),
+                        Box::new(Expression::Number(7).into()),
                         BinaryOperator::Power,
                         Box::new(Expression::BinaryOp(
-                            Box::new(Expression::Number(8)),
+                            Box::new(Expression::Number(8).into()),
                             BinaryOperator::Add,
-                            Box::new(Expression::Number(9))
-                        ))
-                    ))
-                ))    
-            ))
+                            Box::new(Expression::Number(9).into())
+                        ).into())
+                    ).into())
+                ).into())    
+            ).into()).into()
         ]
     };
 
@@ -232,33 +232,33 @@ fn multi_line() {
     let expected = Program {
         statements: vec![
             Stmt::Assignment(
-                "x".to_string(),
+                "x".into(),
                 Expression::BinaryOp(
-                    Box::new(Expression::Number(1)),
+                    Box::new(Expression::Number(1).into()),
                     BinaryOperator::Add, 
                     Box::new(Expression::BinaryOp(
-                        Box::new(Expression::Number(2)),
+                        Box::new(Expression::Number(2).into()),
                         BinaryOperator::Multiply,
-                        Box::new(Expression::Number(-3))
-                    )),
-                ),
-            ),
+                        Box::new(Expression::Number(-3).into())
+                    ).into()),
+                ).into(),
+            ).into(),
             Stmt::Assignment(
-                "y".to_string(),
+                "y".into(),
                 Expression::BinaryOp(
-                    Box::new(Expression::Number(4)),
+                    Box::new(Expression::Number(4).into()),
                     BinaryOperator::Subtract,
-                    Box::new(Expression::Number(5))
-                ),
-            ), 
+                    Box::new(Expression::Number(5).into())
+                ).into(),
+            ).into(), 
             Stmt::Assignment(
-                "z".to_string(),
+                "z".into(),
                 Expression::BinaryOp(
-                    Box::new(Expression::Number(3)),
+                    Box::new(Expression::Number(3).into()),
                     BinaryOperator::Modulus,
-                    Box::new(Expression::Number(4))
-                ),
-            ),  
+                    Box::new(Expression::Number(4).into())
+                ).into(),
+            ).into(),  
         ],
     };
 
@@ -272,13 +272,67 @@ fn parse_string() {
     let expected = Program {
         statements: vec![
             Stmt::Assignment(
-                "x".to_string(),
-                Expression::String("Hello".to_string()),
+                "x".into(),
+                Expression::String("Hello".into()).into(),
                    
-            )
+            ).into()
         ]
     };
 
     program_eq(input, expected);
 }
 
+
+#[test]
+fn names_are_interned() {
+    let tokens = tokenize_spanned("x = y\ndef f(y):\n    return x.y\n");
+    let program = parse_program(&tokens).unwrap();
+
+    let mut names = Vec::new();
+    for stmt in &program.statements {
+        match &stmt.node {
+            Stmt::Assignment(target, value) => {
+                names.push(target.clone());
+                if let Expression::Variable(name) = &value.node {
+                    names.push(name.clone());
+                }
+            }
+            Stmt::FunctionDef { params, body, .. } => {
+                names.push(params[0].node.name.clone());
+                if let Stmt::Return(Some(value)) = &body[0].node {
+                    if let Expression::Attribute(object, attr) = &value.node {
+                        if let Expression::Variable(name) = &object.node {
+                            names.push(name.clone());
+                        }
+                        names.push(attr.clone());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // x, y, y, x, y with every occurrence of a name sharing one allocation
+    assert_eq!(names.len(), 5);
+    assert!(std::sync::Arc::ptr_eq(&names[0], &names[3]));
+    assert!(std::sync::Arc::ptr_eq(&names[1], &names[2]) && std::sync::Arc::ptr_eq(&names[1], &names[4]));
+
+    // A separate parse gets its own
+    let again = parse_program(&tokenize_spanned("x")).unwrap();
+    let Stmt::Expr(expr) = &again.statements[0].node else { panic!() };
+    let Expression::Variable(x) = &expr.node else { panic!() };
+    assert!(!std::sync::Arc::ptr_eq(x, &names[0]));
+}
+
+#[test]
+fn unicode_names() {
+    // Spellings that are the same name in NFKC form parse to one name
+    let program = parse_program(&tokenize_spanned("ﬁle = 1\nπ = file\n")).unwrap();
+    let Stmt::Assignment(target, _) = &program.statements[0].node else { panic!() };
+    let Stmt::Assignment(pi, value) = &program.statements[1].node else { panic!() };
+    let Expression::Variable(name) = &value.node else { panic!() };
+
+    assert_eq!((&**target, &**pi), ("file", "π"));
+    assert!(std::sync::Arc::ptr_eq(target, name));
+    assert_eq!(unparse(&program), "file = 1\nπ = file\n");
+}
@@ -0,0 +1,92 @@
+use parser::*;
+use proptest::prelude::*;
+use serde_json::Value;
+use tokenizer::{tokenize_spanned, try_tokenize, Lexer};
+
+// Source built from keywords, names and punctuation, with line breaks and
+// indentation, so that random programs get some way into most statements
+fn python_like() -> impl Strategy<Value = String> {
+    let piece = prop_oneof![
+        6 => "[a-c]|x[0-9]|-?[0-9]{1,2}|\"[a-z]{0,2}\"|True|None",
+        6 => " ?([-+*/%<>=.,:@]|\\*\\*|//|==|!=|<=|&&|\\|\\||->|\\(|\\)|\\[|\\]|\\{|\\}) ?",
+        4 => "(def|class|if|elif|return|yield|from|await|async|pass|raise|import|try|except|finally|else|with|as|for|in|match|case|type) ",
+        4 => "\n|\n    |\n        |:\n    ",
+        1 => "#[a-z]{0,2}|\\$|[0-9]{20}",
+        1 => "- -[0-9]{1,2}|\\([a-c] (<|==|!=) [a-c]\\)( (<|==) [a-c])?",
+    ];
+    prop::collection::vec(piece, 0..30).prop_map(|pieces| pieces.concat())
+}
+
+// The ast of a program without lines and columns, which unparsing changes
+fn without_locations(mut value: Value) -> Value {
+    match &mut value {
+        Value::Object(object) => {
+            for key in ["lineno", "col_offset", "end_lineno", "end_col_offset"] {
+                object.remove(key);
+            }
+            for field in object.values_mut() {
+                *field = without_locations(field.take());
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(|v| *v = without_locations(v.take())),
+        _ => {}
+    }
+    value
+}
+
+fn check(input: &str) -> Result<(), TestCaseError> {
+    let tokens = tokenize_spanned(input);
+    let result = parse_program(&tokens);
+    let (program, errors) = parse_program_recovering(&tokens);
+
+    // Both entry points agree on whether the program is valid
+    match &result {
+        Ok(parsed) => {
+            prop_assert!(errors.is_empty(), "{:?}", errors);
+            prop_assert_eq!(parsed, &program);
+        }
+        Err(_) => prop_assert!(!errors.is_empty()),
+    }
+
+    // Parsing from the lexer a statement at a time gives the same tree, spans
+    // included, and the same errors
+    match parse_lexer(Lexer::new(input)) {
+        Ok((streamed, streamed_errors)) => {
+            prop_assert_eq!(to_python_ast(&streamed), to_python_ast(&program));
+            prop_assert_eq!(&streamed_errors, &errors);
+        }
+        Err(e) => prop_assert_eq!(try_tokenize(input).err(), Some(e)),
+    }
+
+    for error in &errors {
+        let _ = Diagnostic::from(error).render("<input>", input, false);
+    }
+
+    if let Ok(cst) = parse_cst(input) {
+        prop_assert_eq!(cst.to_string(), input);
+    }
+
+    // Whatever parses prints back to source that parses to the same ast, and
+    // that source prints back as itself
+    if let (Ok(parsed), Ok(_)) = (result, try_tokenize(input)) {
+        let source = unparse(&parsed);
+        let reparsed = parse_program(&tokenize_spanned(&source));
+        prop_assert!(reparsed.is_ok(), "unparsed as:\n{}", source);
+        let reparsed = reparsed.unwrap();
+        prop_assert_eq!(without_locations(to_python_ast(&reparsed)), without_locations(to_python_ast(&parsed)), "unparsed as:\n{}", source);
+        prop_assert_eq!(unparse(&reparsed), source);
+    }
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn arbitrary_input(input in any::<String>()) {
+        check(&input)?;
+    }
+
+    #[test]
+    fn python_like_input(input in python_like()) {
+        check(&input)?;
+    }
+}
@@ -0,0 +1,171 @@
+use parser::*;
+use serde_json::json;
+use tokenizer::tokenize_spanned;
+
+fn parse(input: &str) -> Program {
+    match parse_program(&tokenize_spanned(input)) {
+        Ok(program) => program,
+        Err(e) => panic!("failed to parse  program:  {:?}", e)
+    }
+}
+
+// What `ast.parse` gives for the same source, as dumped by a small script
+// that writes every field and attribute of each node
+#[test]
+fn matches_python() {
+    let program = parse("x = -1 < y\n");
+
+    assert_eq!(to_python_ast(&program), json!({
+        "_type": "Module",
+        "body": [{
+            "_type": "Assign",
+            "targets": [{
+                "_type": "Name", "id": "x", "ctx": { "_type": "Store" },
+                "lineno": 1, "col_offset": 0, "end_lineno": 1, "end_col_offset": 1,
+            }],
+            "value": {
+                "_type": "Compare",
+                "left": {
+                    "_type": "UnaryOp",
+                    "op": { "_type": "USub" },
+                    "operand": {
+                        "_type": "Constant", "value": 1, "kind": null,
+                        "lineno": 1, "col_offset": 5, "end_lineno": 1, "end_col_offset": 6,
+                    },
+                    "lineno": 1, "col_offset": 4, "end_lineno": 1, "end_col_offset": 6,
+                },
+                "ops": [{ "_type": "Lt" }],
+                "comparators": [{
+                    "_type": "Name", "id": "y", "ctx": { "_type": "Load" },
+                    "lineno": 1, "col_offset": 9, "end_lineno": 1, "end_col_offset": 10,
+                }],
+                "lineno": 1, "col_offset": 4, "end_lineno": 1, "end_col_offset": 10,
+            },
+            "type_comment": null,
+            "lineno": 1, "col_offset": 0, "end_lineno": 1, "end_col_offset": 10,
+        }],
+        "type_ignores": [],
+    }));
+}
+
+#[test]
+fn flattened_chains() {
+    let module = to_python_ast(&parse("a < b <= c\nd && e && f || g\n"));
+
+    let compare = &module["body"][0]["value"];
+    assert_eq!(compare["_type"], "Compare");
+    assert_eq!(compare["ops"], json!([{ "_type": "Lt" }, { "_type": "LtE" }]));
+    assert_eq!(compare["comparators"].as_array().unwrap().len(), 2);
+
+    // A parenthesized comparison is an operand of its own
+    let module = to_python_ast(&parse("(a < b) <= c\n"));
+    let compare = &module["body"][0]["value"];
+    assert_eq!(compare["ops"], json!([{ "_type": "LtE" }]));
+    assert_eq!(compare["left"]["_type"], "Compare");
+
+    let module = to_python_ast(&parse("a < b <= c\nd && e && f || g\n"));
+    let or = &module["body"][1]["value"];
+    assert_eq!((&or["_type"], &or["op"]["_type"]), (&json!("BoolOp"), &json!("Or")));
+    assert_eq!(or["values"][0]["values"].as_array().unwrap().len(), 3);
+}
+
+#[test]
+fn store_context() {
+    let module = to_python_ast(&parse("for a, b.c in d:\n    pass\nwith e as f.g:\n    pass\n"));
+
+    assert_eq!(module["body"][0]["target"]["ctx"]["_type"], "Store");
+    assert_eq!(module["body"][0]["target"]["elts"][1]["ctx"]["_type"], "Store");
+    assert_eq!(module["body"][0]["target"]["elts"][1]["value"]["ctx"]["_type"], "Load");
+    assert_eq!(module["body"][0]["iter"]["ctx"]["_type"], "Load");
+    assert_eq!(module["body"][1]["items"][0]["optional_vars"]["ctx"]["_type"], "Store");
+    assert_eq!(module["body"][1]["items"][0]["optional_vars"]["value"]["ctx"]["_type"], "Load");
+}
+
+#[test]
+fn round_trip() {
+    let sources = [
+        "x = a < b < c\ny = -3 + f(a, b.c)[0] ** 2\n",
+        "@d\ndef g[T: int, *Ts, **P](p: int, q=1, r=2) -> None:\n    for i in k:\n        yield i\n    return (1, 2)\n",
+        "class C[T](B):\n    x: int = 1\n    type A[U] = list[U]\n",
+        "match s:\n    case [v, *kw] | {\"k\": v, **kw} if v:\n        pass\n    case P(1, z=None) as w:\n        pass\n    case _:\n        pass\n",
+        "import a.b as c\nfrom ..e import d, f as g\ntry:\n    pass\nexcept* E as h:\n    raise X from Y\nelse:\n    pass\nfinally:\n    pass\n",
+        "async def f():\n    async for a in b:\n        pass\n    async with e as (f, g.h), i:\n        await j\n        x = yield k\ndef g():\n    x = yield from k\n",
+        "x = a && b || c != d // 2 % 3 / 4 - 5 * 6\n",
+    ];
+
+    for source in sources {
+        let program = parse(source);
+        let value = to_python_ast(&program);
+        let back = from_python_ast(&value).unwrap();
+        assert_eq!(back, program, "{}", source);
+        assert_eq!(to_python_ast(&back), value, "{}", source);
+    }
+}
+
+#[test]
+fn unsupported_nodes() {
+    let keyword = json!({
+        "_type": "Module",
+        "body": [{
+            "_type": "Expr",
+            "value": {
+                "_type": "Call",
+                "func": { "_type": "Name", "id": "f", "ctx": { "_type": "Load" } },
+                "args": [],
+                "keywords": [{ "_type": "keyword", "arg": "x", "value": { "_type": "Constant", "value": 1 } }],
+            },
+        }],
+        "type_ignores": [],
+    });
+    assert!(from_python_ast(&keyword).unwrap_err().to_string().contains("keywords are not supported"));
+
+    let float = json!({
+        "_type": "Module",
+        "body": [{ "_type": "Expr", "value": { "_type": "Constant", "value": 1.5 } }],
+    });
+    assert!(from_python_ast(&float).is_err());
+
+    let targets = json!({
+        "_type": "Module",
+        "body": [{
+            "_type": "Assign",
+            "targets": [
+                { "_type": "Name", "id": "a", "ctx": { "_type": "Store" } },
+                { "_type": "Name", "id": "b", "ctx": { "_type": "Store" } },
+            ],
+            "value": { "_type": "Constant", "value": 1 },
+        }],
+    });
+    assert!(from_python_ast(&targets).is_err());
+
+    assert!(from_python_ast(&json!({ "_type": "Expression" })).is_err());
+}
+
+#[test]
+fn native_json() {
+    let program = parse("def f(x=1):\n    return x + 2\n");
+
+    let text = program_to_json(&program).unwrap();
+    assert_eq!(text, serde_json::to_string(&program).unwrap());
+    let back = program_from_json(&text).unwrap();
+    assert_eq!(back, program);
+    assert_eq!(back.statements[0].span, program.statements[0].span);
+}
+
+#[test]
+fn long_chains() {
+    // Past serde_json's limit of 128 levels
+    let program = parse(&format!("x = 1{}\n", " + 1".repeat(200)));
+    let text = serde_json::to_string(&program).unwrap();
+    assert!(serde_json::from_str::<Program>(&text).is_err());
+    assert_eq!(program_from_json(&program_to_json(&program).unwrap()).unwrap(), program);
+    assert_eq!(python_ast_from_json(&python_ast_to_json(&program).unwrap()).unwrap(), program);
+
+    // On a thread with 2MB of stack, far less than these nest
+    std::thread::Builder::new().stack_size(2 << 20).spawn(|| {
+        let source = format!("x = 1{}\ny = a{}\n", " + 1".repeat(30_000), ".b(c)[d]".repeat(10_000));
+        let program = parse(&source);
+        assert!(program_from_json(&program_to_json(&program).unwrap()).unwrap() == program);
+        assert!(python_ast_from_json(&python_ast_to_json(&program).unwrap()).unwrap() == program);
+    }).unwrap().join().unwrap();
+}
@@ -0,0 +1,408 @@
+use parser::*;
+use proptest::prelude::*;
+use proptest::strategy::LazyJust;
+use tokenizer::tokenize_spanned;
+
+fn parse(input: &str) -> Program {
+    match parse_program(&tokenize_spanned(input)) {
+        Ok(program) => program,
+        Err(e) => panic!("failed to parse  program:  {:?}\n{}", e, input)
+    }
+}
+
+// Unparsing gives source that parses back to the same program, and unparsing
+// that again gives the same source
+fn assert_round_trip(program: &Program) {
+    let source = unparse(program);
+    let reparsed = parse(&source);
+    assert_eq!(&reparsed, program, "unparsed as:\n{}", source);
+    assert_eq!(unparse(&reparsed), source);
+}
+
+#[test]
+fn minimal_parentheses() {
+    let cases = [
+        ("(a + b) * c", "(a + b) * c"),
+        ("a + (b * c)", "a + b * c"),
+        ("a - (b - c)", "a - (b - c)"),
+        ("(a - b) - c", "a - b - c"),
+        ("a ** (b ** c)", "a ** b ** c"),
+        ("(a ** b) ** c", "(a ** b) ** c"),
+        ("2 ** -1", "2 ** -1"),
+        ("(-1) ** 2", "(-1) ** 2"),
+        ("a - -1", "a - -1"),
+        ("(a && b) || (c == d)", "a && b || c == d"),
+        ("a + (b == c)", "a + (b == c)"),
+        ("(a < b) == c", "(a < b) == c"),
+        ("a < (b == c)", "a < (b == c)"),
+        ("a < b == c", "a < b == c"),
+        ("(a < b == c) < d", "(a < b == c) < d"),
+        ("(a + b) < c * d", "a + b < c * d"),
+        ("(a)", "a"),
+        ("(a,)", "(a,)"),
+        ("((a, b))[0]", "(a, b)[0]"),
+        ("(1).real", "(1).real"),
+        ("f(a)[b].c", "f(a)[b].c"),
+    ];
+
+    for (input, expected) in cases {
+        let program = parse(&format!("x = {}", input));
+        let Stmt::Assignment(_, value) = &program.statements[0].node else { panic!() };
+        assert_eq!(unparse_expression(&value.node), expected, "{input:?}");
+    }
+}
+
+#[test]
+fn statements() {
+    let input = "@d
+async def f[T: int, *Ts, **P](a, b: int=1) -> (yield):
+    x = yield a, b
+    f((yield), (await g).h)
+    async with (a, b) as c, d:
+        pass
+from .. import (x,
+    y as z)
+class C(B): pass
+match p:
+    case [1, *rest] | {\"k\": -2, **rest} as q if q:
+        pass
+    case Point(0, y=(a as b)):
+        pass
+try:
+    pass
+except* E as e:
+    raise X from e
+finally:
+    pass
+";
+
+    let expected = "@d
+async def f[T: int, *Ts, **P](a, b: int = 1) -> (yield):
+    x = yield (a, b)
+    f((yield), (await g).h)
+    async with ((a, b) as c, d):
+        pass
+from .. import x, y as z
+class C(B):
+    pass
+match p:
+    case [1, *rest] | {\"k\": -2, **rest} as q if q:
+        pass
+    case Point(0, y=a as b):
+        pass
+try:
+    pass
+except* E as e:
+    raise X from e
+finally:
+    pass
+";
+
+    // The return annotation is not checked for yields, parse without checks
+    let program = parse_program_recovering(&tokenize_spanned(input)).0;
+    assert_eq!(unparse(&program), expected);
+}
+
+#[test]
+fn round_trip_sources() {
+    let inputs = [
+        "import a.b as c, d\nfrom .m import *\nx: int\ny: int = 2",
+        "def f(x):\n    for a, b in x:\n        return a\n    else:\n        yield from b\n",
+        "def f():\n    with (yield) as x:\n        pass",
+        "class C[T]:\n    type A[U: T] = dict[T, U]\n    def m(self): return self.x",
+        "try:\n    pass\nexcept E:\n    pass\nexcept:\n    pass\nelse:\n    pass",
+        "match a, b:\n    case (1, 2):\n        pass\n    case _:\n        pass",
+        "raise\nraise E\nx = \"text\" + None\ny = True != False",
+        "",
+    ];
+
+    for input in inputs {
+        let program = parse_program_recovering(&tokenize_spanned(input)).0;
+        assert_round_trip(&program);
+    }
+}
+
+#[test]
+fn empty_bodies() {
+    let program = Program {
+        statements: vec![Stmt::ClassDef {
+            name: "C".into(),
+            type_params: vec![],
+            bases: vec![],
+            body: vec![],
+            decorator_list: vec![],
+        }.into()]
+    };
+
+    assert_eq!(unparse(&program), "class C:\n    pass\n");
+}
+
+// Strategies generating programs the parser accepts
+
+fn name() -> impl Strategy<Value = Symbol> {
+    prop::sample::select(vec!["a", "b", "x", "value", "item2", "data"]).prop_map(Symbol::from)
+}
+
+fn dotted_name() -> impl Strategy<Value = Symbol> {
+    prop::collection::vec(name(), 1..3).prop_map(|names| names.join(".").into())
+}
+
+fn var(name: Symbol) -> Located<Expression> {
+    Expression::Variable(name).into()
+}
+
+fn boxed(expr: Located<Expression>) -> Box<Located<Expression>> {
+    Box::new(expr)
+}
+
+fn binary_operator() -> impl Strategy<Value = BinaryOperator> {
+    prop_oneof![
+        LazyJust::new(|| BinaryOperator::Add),
+        LazyJust::new(|| BinaryOperator::Subtract),
+        LazyJust::new(|| BinaryOperator::Multiply),
+        LazyJust::new(|| BinaryOperator::Divide),
+        LazyJust::new(|| BinaryOperator::FloorDivide),
+        LazyJust::new(|| BinaryOperator::Modulus),
+        LazyJust::new(|| BinaryOperator::Power),
+    ]
+}
+
+fn compare_operator() -> impl Strategy<Value = CompareOperator> {
+    prop_oneof![
+        comparison_operator(),
+        LazyJust::new(|| CompareOperator::And),
+        LazyJust::new(|| CompareOperator::Or),
+    ]
+}
+
+// The operators that chain, which leaves out `&&` and `||`
+fn comparison_operator() -> impl Strategy<Value = CompareOperator> {
+    prop_oneof![
+        LazyJust::new(|| CompareOperator::Equal),
+        LazyJust::new(|| CompareOperator::NotEqual),
+        LazyJust::new(|| CompareOperator::LessThan),
+        LazyJust::new(|| CompareOperator::LessEqual),
+        LazyJust::new(|| CompareOperator::GreaterThan),
+        LazyJust::new(|| CompareOperator::GreaterEqual),
+    ]
+}
+
+fn literal() -> impl Strategy<Value = Expression> {
+    prop_oneof![
+        any::<i64>().prop_map(Expression::Number),
+        "[a-z ]{0,5}".prop_map(Expression::String),
+        any::<bool>().prop_map(Expression::Boolean),
+        LazyJust::new(|| Expression::None),
+    ]
+}
+
+// Expressions without yield or await, which are valid anywhere
+fn expression() -> impl Strategy<Value = Located<Expression>> {
+    let leaf = prop_oneof![literal(), name().prop_map(Expression::Variable)].prop_map(Located::from);
+
+    leaf.prop_recursive(4, 32, 3, |inner| {
+        prop_oneof![
+            (inner.clone(), binary_operator(), inner.clone()).prop_map(|(l, op, r)| Expression::BinaryOp(boxed(l), op, boxed(r))),
+            (inner.clone(), compare_operator(), inner.clone()).prop_map(|(l, op, r)| Expression::CompareOp(boxed(l), op, boxed(r))),
+            (inner.clone(), prop::collection::vec((comparison_operator(), inner.clone()), 2..4))
+                .prop_map(|(left, rest)| Expression::Compare(boxed(left), rest)),
+            prop::collection::vec(inner.clone(), 0..3).prop_map(Expression::Tuple),
+            (inner.clone(), name()).prop_map(|(value, name)| Expression::Attribute(boxed(value), name)),
+            (inner.clone(), inner.clone()).prop_map(|(value, index)| Expression::Subscript(boxed(value), boxed(index))),
+            (inner.clone(), prop::collection::vec(inner, 0..3)).prop_map(|(func, args)| Expression::Call(boxed(func), args)),
+        ].prop_map(Located::from)
+    })
+}
+
+fn target() -> impl Strategy<Value = Located<Expression>> {
+    prop_oneof![
+        name().prop_map(var),
+        prop::collection::vec(name().prop_map(var), 0..3).prop_map(|names| Expression::Tuple(names).into()),
+    ]
+}
+
+fn dotted_value() -> impl Strategy<Value = Located<Expression>> {
+    (name(), prop::collection::vec(name(), 1..3)).prop_map(|(first, attrs)| {
+        attrs.into_iter().fold(var(first), |value, attr| Expression::Attribute(boxed(value), attr).into())
+    })
+}
+
+fn capture() -> impl Strategy<Value = Option<Symbol>> {
+    prop::option::of(name())
+}
+
+// Patterns that bind nothing and can fail, which any alternative of an
+// or-pattern may be
+fn literal_pattern() -> impl Strategy<Value = Located<Pattern>> {
+    prop_oneof![
+        prop_oneof![any::<i64>().prop_map(Expression::Number), "[a-z]{0,3}".prop_map(Expression::String)]
+            .prop_map(|value| Pattern::Value(value.into())),
+        dotted_value().prop_map(Pattern::Value),
+        prop_oneof![any::<bool>().prop_map(Expression::Boolean), LazyJust::new(|| Expression::None)]
+            .prop_map(|value| Pattern::Singleton(value.into())),
+    ].prop_map(Located::from)
+}
+
+fn pattern() -> impl Strategy<Value = Located<Pattern>> {
+    let leaf = prop_oneof![3 => literal_pattern(), 1 => capture().prop_map(|name| Pattern::As(None, name).into())];
+
+    leaf.prop_recursive(3, 16, 3, |inner| {
+        let item = prop_oneof![4 => inner.clone(), 1 => capture().prop_map(|name| Pattern::Star(name).into())];
+        let key = prop_oneof![any::<i64>().prop_map(|n| Expression::Number(n).into()), dotted_value()];
+
+        prop_oneof![
+            prop::collection::vec(item, 0..3).prop_map(Pattern::Sequence),
+            (prop::collection::vec((key, inner.clone()), 0..3), capture()).prop_map(|(items, rest)| {
+                let (keys, patterns) = items.into_iter().unzip();
+                Pattern::Mapping { keys, patterns, rest }
+            }),
+            (dotted_value(), prop::collection::vec(inner.clone(), 0..2), prop::collection::btree_map(name(), inner.clone(), 0..2))
+                .prop_map(|(cls, patterns, keywords)| {
+                    let (kwd_attrs, kwd_patterns) = keywords.into_iter().unzip();
+                    Pattern::Class { cls, patterns, kwd_attrs, kwd_patterns }
+                }),
+            (inner.clone(), name()).prop_map(|(pattern, name)| Pattern::As(Some(Box::new(pattern)), Some(name))),
+            prop::collection::vec(literal_pattern(), 2..4).prop_map(Pattern::Or),
+        ].prop_map(Located::from)
+    })
+}
+
+// Whether a pattern matches any subject
+fn irrefutable(pattern: &Pattern) -> bool {
+    match pattern {
+        Pattern::As(None, _) => true,
+        Pattern::As(Some(pattern), _) => irrefutable(&pattern.node),
+        _ => false,
+    }
+}
+
+fn alias(name: impl Strategy<Value = Symbol>) -> impl Strategy<Value = Located<Alias>> {
+    (name, capture()).prop_map(|(name, asname)| Alias { name, asname }.into())
+}
+
+fn type_params() -> impl Strategy<Value = Vec<Located<TypeParam>>> {
+    let type_param = prop_oneof![
+        (name(), prop::option::of(expression())).prop_map(|(name, bound)| TypeParam::TypeVar { name, bound }),
+        name().prop_map(TypeParam::TypeVarTuple),
+        name().prop_map(TypeParam::ParamSpec),
+    ];
+    prop::collection::vec(type_param.prop_map(Located::from), 0..3)
+}
+
+fn simple_statement() -> impl Strategy<Value = Stmt> {
+    prop_oneof![
+        (name(), expression()).prop_map(|(name, value)| Stmt::Assignment(name, value)),
+        expression().prop_map(Stmt::Expr),
+        LazyJust::new(|| Stmt::Pass),
+        prop::collection::vec(alias(dotted_name()), 1..3).prop_map(Stmt::Import),
+        (0..3usize, prop::option::of(dotted_name()), prop::collection::vec(alias(name()), 1..3), any::<bool>())
+            .prop_map(|(level, module, names, star)| {
+                // A module is needed unless the import is relative
+                let module = if level == 0 { Some(module.unwrap_or_else(|| "m".into())) } else { module };
+                let names = if star { vec![Alias { name: "*".into(), asname: None }.into()] } else { names };
+                Stmt::ImportFrom { module, names, level }
+            }),
+        prop::option::of((expression(), prop::option::of(expression())))
+            .prop_map(|raise| match raise {
+                Some((exc, cause)) => Stmt::Raise { exc: Some(exc), cause },
+                None => Stmt::Raise { exc: None, cause: None },
+            }),
+        (name(), expression(), prop::option::of(expression()))
+            .prop_map(|(target, annotation, value)| Stmt::AnnAssign { target, annotation, value }),
+        (name(), type_params(), expression()).prop_map(|(name, type_params, value)| Stmt::TypeAlias { name, type_params, value }),
+    ]
+}
+
+type Body = Vec<Located<Stmt>>;
+
+// A function around statements from inner, with yield, await and return
+// statements mixed into its body where they are allowed
+fn function(inner: impl Strategy<Value = Located<Stmt>> + Clone) -> impl Strategy<Value = Stmt> {
+    let sync_body = prop::collection::vec(prop_oneof![
+        inner.clone(),
+        prop::option::of(expression()).prop_map(|value| Stmt::Expr(Expression::Yield(value.map(Box::new)).into()).into()),
+        expression().prop_map(|value| Stmt::Expr(Expression::YieldFrom(boxed(value)).into()).into()),
+        (name(), expression()).prop_map(|(name, value)| Stmt::Assignment(name, Expression::Yield(Some(boxed(value))).into()).into()),
+        prop::option::of(expression()).prop_map(|value| Stmt::Return(value).into()),
+    ], 1..4);
+    let async_body = prop::collection::vec(prop_oneof![
+        inner,
+        expression().prop_map(|value| Stmt::Expr(Expression::Await(boxed(value)).into()).into()),
+        prop::option::of(expression()).prop_map(|value| Stmt::Return(value).into()),
+    ], 1..4);
+    let body = prop_oneof![sync_body.prop_map(|body| (false, body)), async_body.prop_map(|body| (true, body))];
+
+    let param = (name(), prop::option::of(expression()), prop::option::of(expression()))
+        .prop_map(|(name, annotation, default)| Parameter { name, annotation, default }.into());
+
+    (name(), type_params(), prop::collection::vec(param, 0..3), prop::option::of(expression()), body, prop::collection::vec(expression(), 0..2))
+        .prop_map(|(name, type_params, params, returns, (is_async, body), decorator_list)| {
+            let is_generator = body.iter().any(|stmt: &Located<Stmt>| match &stmt.node {
+                Stmt::Expr(value) | Stmt::Assignment(_, value) => matches!(value.node, Expression::Yield(_) | Expression::YieldFrom(_)),
+                _ => false,
+            });
+            Stmt::FunctionDef { name, type_params, params, returns, body, decorator_list, is_async, is_generator }
+        })
+}
+
+fn handler(exception: impl Strategy<Value = Option<Located<Expression>>>, body: impl Strategy<Value = Body>) -> impl Strategy<Value = Located<ExceptHandler>> {
+    (exception, capture(), body).prop_map(|(exception, name, body)| {
+        let name = if exception.is_some() { name } else { None };
+        ExceptHandler { exception, name, body }.into()
+    })
+}
+
+fn statement() -> impl Strategy<Value = Located<Stmt>> {
+    simple_statement().prop_map(Located::from).prop_recursive(3, 24, 3, |inner| {
+        let body = prop::collection::vec(inner.clone(), 1..3);
+
+        let try_statement = (
+            body.clone(),
+            prop::collection::vec(handler(expression().prop_map(Some), body.clone()), 0..3),
+            prop::option::of(handler(LazyJust::new(|| None), body.clone())),
+            body.clone(),
+            prop::collection::vec(inner.clone(), 0..2),
+            any::<bool>(),
+        ).prop_map(|(body, mut handlers, default, orelse, finalbody, star)| {
+            // A bare except goes last and is not allowed with except*
+            if !star {
+                handlers.extend(default);
+            }
+            let star = star && !handlers.is_empty();
+            let orelse = if handlers.is_empty() { Vec::new() } else { orelse };
+            let finalbody = if handlers.is_empty() { vec![Stmt::Pass.into()] } else { finalbody };
+            Stmt::Try { body, handlers, orelse, finalbody, star }
+        });
+
+        let with_item = (expression(), prop::option::of(target()))
+            .prop_map(|(context_expr, optional_vars)| WithItem { context_expr, optional_vars });
+        let with_statement = (prop::collection::vec(with_item, 1..3), body.clone())
+            .prop_map(|(items, body)| Stmt::With { items, body, is_async: false });
+
+        let class_def = (name(), type_params(), prop::collection::vec(expression(), 0..2), body.clone(), prop::collection::vec(expression(), 0..2))
+            .prop_map(|(name, type_params, bases, body, decorator_list)| Stmt::ClassDef { name, type_params, bases, body, decorator_list });
+
+        let case = (pattern(), prop::option::of(expression()), body.clone())
+            .prop_map(|(pattern, guard, body)| MatchCase { pattern, guard, body });
+        let match_statement = (expression(), prop::collection::vec(case, 1..3))
+            .prop_filter("only the last case may match anything", |(_, cases)| {
+                cases[..cases.len() - 1].iter().all(|case| case.guard.is_some() || !irrefutable(&case.pattern.node))
+            })
+            .prop_map(|(subject, cases)| Stmt::Match { subject, cases });
+
+        let for_statement = (target(), expression(), body.clone(), prop::collection::vec(inner.clone(), 0..2))
+            .prop_map(|(target, iter, body, orelse)| Stmt::For { target, iter, body, orelse, is_async: false });
+
+        prop_oneof![try_statement, with_statement, function(inner), class_def, match_statement, for_statement].prop_map(Located::from)
+    })
+}
+
+proptest! {
+    #[test]
+    fn round_trip_generated(statements in prop::collection::vec(statement(), 0..4)) {
+        let program = Program { statements };
+        let source = unparse(&program);
+        let reparsed = parse_program(&tokenize_spanned(&source));
+        prop_assert_eq!(reparsed.as_ref().ok(), Some(&program), "unparsed as:\n{}", source);
+    }
+}
@@ -0,0 +1,156 @@
+use parser::*;
+use tokenizer::tokenize_spanned;
+
+fn parse(input: &str) -> Program {
+    match parse_program(&tokenize_spanned(input)) {
+        Ok(program) => program,
+        Err(e) => panic!("failed to parse  program:  {:?}", e)
+    }
+}
+
+// Collects the names read anywhere in a program
+#[derive(Default)]
+struct Names(Vec<String>);
+
+impl Visitor for Names {
+    fn visit_expression(&mut self, expr: &Located<Expression>) {
+        if let Expression::Variable(name) = &expr.node {
+            self.0.push(name.to_string());
+        }
+        walk_expression(self, expr);
+    }
+}
+
+#[test]
+fn visitor_order() {
+    let program = parse("@d
+def f[T: b](p: c = e) -> g:
+    for h in i:
+        match j:
+            case {k.l: m} if n:
+                return o(q[r], s.t)
+");
+
+    let mut names = Names::default();
+    names.visit_program(&program);
+    assert_eq!(names.0, ["d", "b", "c", "e", "g", "h", "i", "j", "k", "n", "o", "q", "r", "s"]);
+}
+
+// Names, collected without recursing down chains like `a + b + ...`
+#[derive(Default)]
+struct ChainNames(Vec<String>);
+
+impl Visitor for ChainNames {
+    fn visit_expression(&mut self, expr: &Located<Expression>) {
+        for expr in left_spine(expr) {
+            if let Expression::Variable(name) = &expr.node {
+                self.0.push(name.to_string());
+            }
+        }
+        walk_chain(self, expr);
+    }
+}
+
+#[test]
+fn visitor_down_chains() {
+    let program = parse("x = a + b * c < d(e, f.g)[h].i
+");
+    let (mut names, mut chain_names) = (Names::default(), ChainNames::default());
+    names.visit_program(&program);
+    chain_names.visit_program(&program);
+    assert_eq!(names.0, ["a", "b", "c", "d", "e", "f", "h"]);
+    assert_eq!(chain_names.0, names.0);
+
+    // On a thread with 2MB of stack
+    std::thread::Builder::new().stack_size(2 << 20).spawn(|| {
+        let program = parse(&format!("x = a{}\n", " + b".repeat(100_000)));
+        let mut names = ChainNames::default();
+        names.visit_program(&program);
+        assert_eq!(names.0.len(), 100_001);
+    }).unwrap().join().unwrap();
+}
+
+// Counts nodes but does not look inside function bodies
+#[derive(Default)]
+struct TopLevel {
+    statements: usize,
+    aliases: usize,
+}
+
+impl Visitor for TopLevel {
+    fn visit_stmt(&mut self, stmt: &Located<Stmt>) {
+        self.statements += 1;
+        if !matches!(stmt.node, Stmt::FunctionDef { .. }) {
+            walk_stmt(self, stmt);
+        }
+    }
+
+    fn visit_alias(&mut self, _alias: &Located<Alias>) {
+        self.aliases += 1;
+    }
+}
+
+#[test]
+fn visitor_skips_children() {
+    let program = parse("import a, b\ndef f():\n    import c\n    pass\ntry:\n    pass\nexcept E:\n    from d import e\n");
+
+    let mut counts = TopLevel::default();
+    counts.visit_program(&program);
+    assert_eq!((counts.statements, counts.aliases), (5, 3));
+}
+
+struct Rename;
+
+impl VisitorMut for Rename {
+    fn visit_expression_mut(&mut self, expr: &mut Located<Expression>) {
+        if let Expression::Variable(name) = &mut expr.node {
+            *name = name.to_ascii_uppercase().into();
+        }
+        walk_expression_mut(self, expr);
+    }
+}
+
+#[test]
+fn visitor_mut_renames() {
+    let mut program = parse("x = f(a, (b, c.d))\nwith g as h:\n    pass\n");
+    Rename.visit_program_mut(&mut program);
+
+    assert_eq!(unparse(&program), "x = F(A, (B, C.d))\nwith G as H:\n    pass\n");
+}
+
+// Folds additions and multiplications of number literals
+struct ConstantFolder;
+
+impl Fold for ConstantFolder {
+    fn fold_expression(&mut self, expr: Located<Expression>) -> Located<Expression> {
+        let expr = fold_expression(self, expr);
+
+        let value = match &expr.node {
+            Expression::BinaryOp(left, operator, right) => match (&left.node, operator, &right.node) {
+                (Expression::Number(a), BinaryOperator::Add, Expression::Number(b)) => a.checked_add(*b),
+                (Expression::Number(a), BinaryOperator::Multiply, Expression::Number(b)) => a.checked_mul(*b),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        match value {
+            Some(value) => Located::new(Expression::Number(value), expr.span),
+            None => expr,
+        }
+    }
+}
+
+#[test]
+fn fold_replaces_nodes() {
+    let program = parse("def f(a=1 + 2 * 3):\n    return a + 2 * 5\n");
+    let folded = ConstantFolder.fold_program(program);
+
+    assert_eq!(unparse(&folded), "def f(a=7):\n    return a + 10\n");
+
+    // Spans survive the fold
+    let Stmt::FunctionDef { params, body, .. } = &folded.statements[0].node else { panic!() };
+    let default = params[0].node.default.as_ref().unwrap();
+    assert_eq!((default.span.start.column, default.span.end.column), (8, 17));
+    assert_eq!((body[0].span.start.line, body[0].span.start.column), (2, 4));
+}
@@ -0,0 +1,126 @@
+use std::borrow::Cow;
+
+use crate::{Position, Span, TokenizeError, TokenizeErrorKind};
+
+// The encodings a source file can declare
+#[derive(Clone, Copy, PartialEq)]
+enum Encoding {
+    Utf8,
+    Latin1,
+    Ascii,
+}
+
+impl Encoding {
+    // Spellings are matched the way CPython normalizes them, ignoring case
+    // and treating `_` as `-`
+    fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase().replace('_', "-");
+        let is = |base: &str| name == base || name.strip_prefix(base).is_some_and(|rest| rest.starts_with('-'));
+        if is("utf-8") || name == "utf8" {
+            Some(Encoding::Utf8)
+        } else if ["latin-1", "latin1", "iso-8859-1", "iso8859-1", "iso-latin-1", "l1"].iter().any(|base| is(base)) {
+            Some(Encoding::Latin1)
+        } else if name == "ascii" || name == "us-ascii" {
+            Some(Encoding::Ascii)
+        } else {
+            None
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "utf-8",
+            Encoding::Latin1 => "latin-1",
+            Encoding::Ascii => "ascii",
+        }
+    }
+}
+
+// Decode a source file given as bytes. A UTF-8 byte order mark is dropped,
+// and a PEP 263 declaration like `# -*- coding: latin-1 -*-` on the first or
+// second line picks the encoding, which is otherwise UTF-8. Positions, in
+// errors and in the tokens of the text, count from after the byte order mark
+pub fn decode_source(bytes: &[u8]) -> Result<Cow<'_, str>, TokenizeError> {
+    let (bytes, bom) = match bytes.strip_prefix(b"\xef\xbb\xbf") {
+        Some(rest) => (rest, true),
+        None => (bytes, false),
+    };
+
+    let encoding = match coding_declaration(bytes) {
+        None => Encoding::Utf8,
+        Some((name, span)) => match Encoding::from_name(&name) {
+            Some(Encoding::Utf8) => Encoding::Utf8,
+            Some(_) if bom => return Err(TokenizeError::new(TokenizeErrorKind::EncodingWithBom(name), span)),
+            Some(encoding) => encoding,
+            None => return Err(TokenizeError::new(TokenizeErrorKind::UnknownEncoding(name), span)),
+        },
+    };
+
+    // The first byte that cannot be decoded
+    let invalid = match encoding {
+        Encoding::Utf8 => std::str::from_utf8(bytes).err().map(|e| e.valid_up_to()),
+        Encoding::Ascii => bytes.iter().position(|b| !b.is_ascii()),
+        Encoding::Latin1 if !bytes.is_ascii() => return Ok(Cow::Owned(bytes.iter().map(|&b| char::from(b)).collect())),
+        Encoding::Latin1 => None,
+    };
+
+    match invalid {
+        Some(offset) => {
+            let start = position_at(bytes, offset);
+            let end = Position { column: start.column + 1, offset: offset + 1, ..start };
+            let kind = TokenizeErrorKind::InvalidEncoding(encoding.name().to_string(), bytes[offset]);
+            Err(TokenizeError::new(kind, Span::new(start, end)))
+        }
+        // Valid UTF-8 by now, ASCII being a subset of it
+        None => Ok(Cow::Borrowed(std::str::from_utf8(bytes).unwrap_or_default())),
+    }
+}
+
+// The encoding named on the first line, or on the second if the first is
+// blank or only a comment, and where the name is
+fn coding_declaration(bytes: &[u8]) -> Option<(String, Span)> {
+    let mut line_start = 0;
+    for line in 1..=2 {
+        let end = bytes[line_start..].iter().position(|&b| b == b'\n').map_or(bytes.len(), |i| line_start + i);
+        let text = &bytes[line_start..end];
+
+        if let Some(name) = coding_name(text) {
+            let start = Position { line, column: name.start, offset: line_start + name.start };
+            let end = Position { line, column: name.end, offset: line_start + name.end };
+            return Some((String::from_utf8_lossy(&text[name]).into_owned(), Span::new(start, end)));
+        }
+
+        let code = text.iter().find(|b| !matches!(b, b' ' | b'\t' | b'\x0c' | b'\r'));
+        if end == bytes.len() || code.is_some_and(|&b| b != b'#') {
+            break;
+        }
+        line_start = end + 1;
+    }
+    None
+}
+
+// Where the encoding name is in a line matching `^[ \t\f]*#.*?coding[:=][ \t]*([-\w.]+)`
+fn coding_name(line: &[u8]) -> Option<std::ops::Range<usize>> {
+    let hash = line.iter().position(|b| !matches!(b, b' ' | b'\t' | b'\x0c'))?;
+    if line[hash] != b'#' {
+        return None;
+    }
+
+    let coding = (hash..line.len()).find(|&i| {
+        line[i..].starts_with(b"coding") && matches!(line.get(i + 6), Some(b':' | b'='))
+    })?;
+    let mut start = coding + 7;
+    while matches!(line.get(start), Some(b' ' | b'\t')) {
+        start += 1;
+    }
+    let len = line[start..].iter().take_while(|&&b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.')).count();
+
+    (len > 0).then_some(start..start + len)
+}
+
+fn position_at(bytes: &[u8], offset: usize) -> Position {
+    let before = &bytes[..offset];
+    let line_start = before.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+    let line = 1 + before.iter().filter(|&&b| b == b'\n').count();
+    Position { line, column: offset - line_start, offset }
+}
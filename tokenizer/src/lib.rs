@@ -1,213 +1,705 @@
-use std::string;
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::fmt;
 
-#[derive(Debug, PartialEq)]
-pub enum Token {
+use serde::{Deserialize, Serialize};
+use unicode_normalization::{is_nfkc_quick, IsNormalized, UnicodeNormalization};
+use unicode_xid::UnicodeXID;
+
+mod encoding;
+pub use encoding::decode_source;
+
+// Text in a token borrows from the source where it can, into_owned detaches
+// a token from it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Token<'a> {
     Number(i64),
-    Identifier(String),
-    Operator(String),
-    String(String),
+    Identifier(Cow<'a, str>),
+    Operator(Cow<'a, str>),
+    // The text between the quotes
+    String(Cow<'a, str>),
     Boolean(bool),
+    Keyword(Cow<'a, str>),
     OpenParen,
     CloseParen,
+    OpenBracket,
+    CloseBracket,
+    OpenBrace,
+    CloseBrace,
     Assign,
+    Dot,
+    Comma,
+    Colon,
+    At,
+    Arrow, // -> before a return annotation
+    Newline, //End of a logical line
+    Indent,
+    Dedent,
     EOF, //End of Input
+    // Trivia, only produced by tokenize_with_trivia. Whitespace includes line
+    // continuations and the line breaks of blank lines
+    Whitespace(Cow<'a, str>),
+    Comment(Cow<'a, str>),
+    // A literal the tokenizer reported an error for, such as an integer too
+    // large for an i64, kept so that the parser can put Expression::Error in
+    // its place
+    Invalid(Cow<'a, str>),
 }
 
-pub fn tokenize(input: &str) -> Vec<Token> {
-    let mut tokens = Vec::new();
-    let mut chars = input.chars().peekable();
+impl Token<'_> {
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, Token::Whitespace(_) | Token::Comment(_))
+    }
+
+    pub fn into_owned(self) -> Token<'static> {
+        match self {
+            Token::Number(n) => Token::Number(n),
+            Token::Identifier(name) => Token::Identifier(Cow::Owned(name.into_owned())),
+            Token::Operator(op) => Token::Operator(Cow::Owned(op.into_owned())),
+            Token::String(value) => Token::String(Cow::Owned(value.into_owned())),
+            Token::Boolean(b) => Token::Boolean(b),
+            Token::Keyword(k) => Token::Keyword(Cow::Owned(k.into_owned())),
+            Token::OpenParen => Token::OpenParen,
+            Token::CloseParen => Token::CloseParen,
+            Token::OpenBracket => Token::OpenBracket,
+            Token::CloseBracket => Token::CloseBracket,
+            Token::OpenBrace => Token::OpenBrace,
+            Token::CloseBrace => Token::CloseBrace,
+            Token::Assign => Token::Assign,
+            Token::Dot => Token::Dot,
+            Token::Comma => Token::Comma,
+            Token::Colon => Token::Colon,
+            Token::At => Token::At,
+            Token::Arrow => Token::Arrow,
+            Token::Newline => Token::Newline,
+            Token::Indent => Token::Indent,
+            Token::Dedent => Token::Dedent,
+            Token::EOF => Token::EOF,
+            Token::Whitespace(text) => Token::Whitespace(Cow::Owned(text.into_owned())),
+            Token::Comment(text) => Token::Comment(Cow::Owned(text.into_owned())),
+            Token::Invalid(text) => Token::Invalid(Cow::Owned(text.into_owned())),
+        }
+    }
+}
+
+// Reserved words that can never be used as identifiers. True and False are
+// handled separately as boolean literals.
+pub const KEYWORDS: &[&str] = &[
+    "None", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global",
+    "if", "import", "in", "is", "lambda", "nonlocal", "not", "or", "pass",
+    "raise", "return", "try", "while", "with", "yield",
+];
+
+// A name in NFKC form, borrowed when it already is, so `ﬁle` and `file` are
+// the same name
+fn nfkc(name: &str) -> Cow<'_, str> {
+    if name.is_ascii() || is_nfkc_quick(name.chars()) == IsNormalized::Yes {
+        Cow::Borrowed(name)
+    } else {
+        Cow::Owned(name.nfkc().collect())
+    }
+}
+
+// A point in the source. Lines start at 1, the column is the UTF-8 byte
+// offset within the line and offset is the byte offset in the whole input,
+// matching CPython's lineno and col_offset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize,
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Position { line: 1, column: 0, offset: 0 }
+    }
+}
+
+// The source range a token covers, end is exclusive
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Self {
+        Span { start, end }
+    }
+
+    // The smallest span covering both spans
+    pub fn to(self, other: Span) -> Span {
+        Span { start: self.start.min(other.start), end: self.end.max(other.end) }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpannedToken<'a> {
+    pub token: Token<'a>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TokenizeErrorKind {
+    UnterminatedString,
+    // A character that cannot start any token
+    InvalidCharacter(char),
+    IntegerTooLarge,
+    // A coding declaration naming an encoding other than UTF-8, Latin-1 or
+    // ASCII
+    UnknownEncoding(String),
+    // A coding declaration other than UTF-8 after a UTF-8 byte order mark
+    EncodingWithBom(String),
+    // A byte that is not valid in the source's encoding
+    InvalidEncoding(String, u8),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenizeError {
+    pub kind: TokenizeErrorKind,
+    pub span: Span,
+}
+
+impl TokenizeError {
+    pub fn new(kind: TokenizeErrorKind, span: Span) -> Self {
+        TokenizeError { kind, span }
+    }
+}
+
+// Messages follow CPython's SyntaxError wording
+impl fmt::Display for TokenizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            TokenizeErrorKind::UnterminatedString => {
+                write!(f, "unterminated string literal (detected at line {})", self.span.start.line)
+            }
+            TokenizeErrorKind::InvalidCharacter(c) => write!(f, "invalid character '{}' (U+{:04X})", c, *c as u32),
+            TokenizeErrorKind::IntegerTooLarge => write!(f, "integer literal is too large"),
+            TokenizeErrorKind::UnknownEncoding(name) => write!(f, "unknown encoding: {}", name),
+            TokenizeErrorKind::EncodingWithBom(name) => write!(f, "encoding problem: {} with BOM", name),
+            TokenizeErrorKind::InvalidEncoding(encoding, byte) => {
+                write!(f, "(unicode error) '{}' codec can't decode byte 0x{:02x}", encoding, byte)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TokenizeError {}
+
+// Peekable character iterator that keeps track of its position in the input
+struct Cursor<'a> {
+    input: &'a str,
+    position: Position,
+    current: Option<char>,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(input: &'a str) -> Self {
+        Cursor::at(input, Position::default())
+    }
+
+    fn at(input: &'a str, position: Position) -> Self {
+        Cursor { input, position, current: input[position.offset..].chars().next() }
+    }
+
+    // The source from start up to the current position
+    fn since(&self, start: Position) -> &'a str {
+        &self.input[start.offset..self.position.offset]
+    }
+
+    fn peek(&self) -> Option<&char> {
+        self.current.as_ref()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let ch = self.current?;
+        self.position.offset += ch.len_utf8();
+        if ch == '\n' {
+            self.position.line += 1;
+            self.position.column = 0;
+        } else {
+            self.position.column += ch.len_utf8();
+        }
+        self.current = self.input[self.position.offset..].chars().next();
+        Some(ch)
+    }
+}
+
+pub fn tokenize(input: &str) -> Vec<Token<'_>> {
+    tokenize_spanned(input).into_iter().map(|t| t.token).collect()
+}
+
+// Tokenize and keep the source span of every token. Invalid input is skipped
+// over, use try_tokenize to have it reported
+pub fn tokenize_spanned(input: &str) -> Vec<SpannedToken<'_>> {
+    tokenize_with_errors(input).0
+}
+
+// Tokenize, failing on the first invalid character, unterminated string or
+// integer literal that does not fit an i64
+pub fn try_tokenize(input: &str) -> Result<Vec<SpannedToken<'_>>, TokenizeError> {
+    let (tokens, errors) = tokenize_with_errors(input);
+
+    match errors.into_iter().next() {
+        Some(e) => Err(e),
+        None => Ok(tokens),
+    }
+}
+
+// Tokenize keeping comments and whitespace as trivia tokens, so that the
+// source each token spans, put back together, is the whole input
+pub fn tokenize_with_trivia(input: &str) -> Result<Vec<SpannedToken<'_>>, TokenizeError> {
+    let tokens = try_tokenize(input)?;
+    let mut result = Vec::with_capacity(tokens.len());
+    let mut chars = Cursor::new(input);
+
+    for token in tokens {
+        // Everything between two tokens is whitespace or a comment
+        let gap_end = token.span.start.offset;
+        while chars.position.offset < gap_end {
+            let start = chars.position;
+            let is_comment = chars.peek() == Some(&'#');
+            // A comment runs to the end of its line
+            let stop = if is_comment { '\n' } else { '#' };
+            while chars.position.offset < gap_end && chars.peek() != Some(&stop) {
+                chars.next();
+            }
+
+            let text = Cow::Borrowed(chars.since(start));
+            let trivia = if is_comment { Token::Comment(text) } else { Token::Whitespace(text) };
+            result.push(SpannedToken { token: trivia, span: Span::new(start, chars.position) });
+        }
+
+        while chars.position.offset < token.span.end.offset {
+            chars.next();
+        }
+        result.push(token);
+    }
+
+    Ok(result)
+}
+
+// Splits source into tokens one at a time, with one token of lookahead.
+// Invalid input comes out as an Err and lexing carries on after it. The last
+// item is always EOF
+pub struct Lexer<'a> {
+    chars: Cursor<'a>,
+    // Found but not handed out yet, a line can start with several Dedents
+    pending: VecDeque<Result<SpannedToken<'a>, TokenizeError>>,
+    // Newlines inside brackets do not end a logical line
+    paren_depth: usize,
+    // Columns of the enclosing indented blocks, innermost last
+    indent_stack: Vec<usize>,
+    at_line_start: bool,
+    // Whether a token has been found since the last Newline, blank lines
+    // produce no Newline token
+    line_has_tokens: bool,
+    finished: bool,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Lexer::starting_at(input, Position::default())
+    }
+
+    // Lex the input from a position part way through it, which has to be the
+    // start of a line outside any brackets or indented blocks. Spans are still
+    // relative to the start of the input
+    pub fn starting_at(input: &'a str, position: Position) -> Self {
+        Lexer {
+            chars: Cursor::at(input, position),
+            pending: VecDeque::new(),
+            paren_depth: 0,
+            indent_stack: vec![0],
+            at_line_start: true,
+            line_has_tokens: false,
+            finished: false,
+        }
+    }
+
+    // The item next() will return, without consuming it
+    pub fn peek(&mut self) -> Option<&Result<SpannedToken<'a>, TokenizeError>> {
+        self.fill();
+        self.pending.front()
+    }
+
+    fn fill(&mut self) {
+        while self.pending.is_empty() && !self.finished {
+            self.scan();
+        }
+    }
+
+    fn push(&mut self, token: Token<'a>, span: Span) {
+        self.line_has_tokens = token != Token::Newline;
+        self.pending.push_back(Ok(SpannedToken { token, span }));
+    }
+
+    // Consume the next token's worth of input, which may turn out to be only
+    // whitespace, a comment or an error
+    fn scan(&mut self) {
+        let start = self.chars.position;
+
+        if self.chars.peek().is_none() {
+            // Close any blocks still open at the end of input
+            for _ in 1..self.indent_stack.len() {
+                self.push(Token::Dedent, Span::new(start, start));
+            }
+            self.indent_stack.truncate(1);
+            self.push(Token::EOF, Span::new(start, start));
+            self.finished = true;
+            return;
+        }
+
+        if self.at_line_start {
+            self.at_line_start = false;
+            self.scan_indentation(start);
+            return;
+        }
+
+        // Everything consumed belongs to the tokens found
+        let mut tokens = Vec::new();
+        let mut errors = Vec::new();
+        self.scan_token(start, &mut tokens, &mut errors);
+
+        let span = Span::new(start, self.chars.position);
+        self.pending.extend(errors.into_iter().map(Err));
+        for token in tokens {
+            self.push(token, span);
+        }
+    }
+
+    fn scan_indentation(&mut self, start: Position) {
+        let mut column = 0;
+        while let Some(&c) = self.chars.peek() {
+            match c {
+                ' ' => column += 1,
+                '\t' => column = (column / 8 + 1) * 8,
+                '\x0c' => column = 0,
+                _ => break,
+            }
+            self.chars.next();
+        }
+
+        // Blank and comment-only lines do not affect indentation. Dedents
+        // are empty and sit at the start of the line, an Indent covers
+        // the indentation
+        if !matches!(self.chars.peek(), None | Some('\n' | '\r' | '#')) {
+            while column < *self.indent_stack.last().unwrap_or(&0) {
+                self.indent_stack.pop();
+                self.push(Token::Dedent, Span::new(start, start));
+            }
+            // Dedenting to a column no enclosing block uses leaves a
+            // stray Indent that the parser rejects
+            if column > *self.indent_stack.last().unwrap_or(&0) {
+                self.indent_stack.push(column);
+                self.push(Token::Indent, Span::new(start, self.chars.position));
+            }
+        }
+    }
+
+    fn scan_token(&mut self, start: Position, tokens: &mut Vec<Token<'a>>, errors: &mut Vec<TokenizeError>) {
+        let Some(&ch) = self.chars.peek() else { return };
 
-    while let Some(&ch) = chars.peek() {
         match ch {
             // Skip Whitespace
-            ' ' | '\t' | '\n' => {
-                chars.next();
+            ' ' | '\t' | '\r' | '\x0c' => {
+                self.chars.next();
             }
 
-            //Handle boolean literals
-            'T' | 'F' => {
-                let mut literal = String::new();
-                while let Some(&c) = chars.peek() {
-                    if c.is_alphanumeric() {
-                        literal.push(c);
-                        chars.next();
-                    } else {
+            // End of line, blank lines produce no Newline token
+            '\n' => {
+                self.chars.next();
+                if self.paren_depth == 0 {
+                    if self.line_has_tokens {
+                        tokens.push(Token::Newline);
+                    }
+                    self.at_line_start = true;
+                }
+            }
+
+            // Skip comments up to the end of the line
+            '#' => {
+                while let Some(&c) = self.chars.peek() {
+                    if c == '\n' {
                         break;
                     }
+                    self.chars.next();
                 }
+            }
 
-                if literal == "True" {
-                    tokens.push(Token::Boolean(true));
-                } else if literal == "False" {
-                    tokens.push(Token::Boolean(false));
-                } else {
-                    tokens.push(Token::Identifier(literal));
+            // Explicit line joining with a backslash
+            '\\' => {
+                self.chars.next();
+                if let Some(&'\r') = self.chars.peek() {
+                    self.chars.next();
+                }
+                if let Some(&'\n') = self.chars.peek() {
+                    self.chars.next();
                 }
             }
 
             //Handle String
             '"' => {
-                chars.next();
-                let mut string_value = String::new();
+                self.chars.next();
+                let contents = self.chars.position;
+                let mut string_value = "";
 
-                while let Some(&char) = chars.peek()  {
+                let mut terminated = false;
+
+                // Strings end at the closing quote or, unterminated, at the end of the line
+                while let Some(&char) = self.chars.peek()  {
                     if char == '"' {
-                        chars.next();
+                        string_value = self.chars.since(contents);
+                        self.chars.next();
+                        terminated = true;
+                        break;
+                    } else if char == '\n' {
                         break;
                     } else {
-                        string_value.push(char);
-                        chars.next();
+                        self.chars.next();
                     }
                 }
 
-                tokens.push(Token::String(string_value));
+                if !terminated {
+                    string_value = self.chars.since(contents);
+                    let span = Span::new(start, self.chars.position);
+                    errors.push(TokenizeError::new(TokenizeErrorKind::UnterminatedString, span));
+                }
+                tokens.push(Token::String(Cow::Borrowed(string_value)));
             }
 
             // Handle numbers and subtraction
             '0'..='9' | '-' => {
-                let mut number = String::new();
-
                 if ch == '-' {
-                    chars.next();
-                    if let Some('0'..='9') = chars.peek() {
-                        number.push('-');
-                    } else {
-                        tokens.push(Token::Operator("-".to_string()));
-                        continue;
+                    self.chars.next();
+                    // A minus directly before digits is part of the number
+                    match self.chars.peek() {
+                        Some('0'..='9') => {}
+                        Some('>') => {
+                            self.chars.next();
+                            tokens.push(Token::Arrow);
+                            return;
+                        }
+                        _ => {
+                            tokens.push(Token::Operator(Cow::Borrowed("-")));
+                            return;
+                        }
                     }
                 }
 
-                while let Some(&digit) = chars.peek() {
+                while let Some(&digit) = self.chars.peek() {
                     if digit.is_numeric() {
-                        number.push(digit);
-                        chars.next();
+                        self.chars.next();
                     } else {
                         break;
                     }
                 }
 
-                let number_value = number.parse::<i64>().unwrap();
-                tokens.push(Token::Number(number_value));
+                let text = self.chars.since(start);
+                match text.parse::<i64>() {
+                    Ok(number_value) => tokens.push(Token::Number(number_value)),
+                    Err(_) => {
+                        let span = Span::new(start, self.chars.position);
+                        errors.push(TokenizeError::new(TokenizeErrorKind::IntegerTooLarge, span));
+                        tokens.push(Token::Invalid(Cow::Borrowed(text)));
+                    }
+                }
             }
 
-            // Handle identifiers (variables)
-            'a'..='z' | 'A'..='Z' | '_' => {
-                let mut identifier = String::new();
-                while let Some(&char) = chars.peek() {
-                    if char.is_alphanumeric() || char == '_' {
-                        identifier.push(char);
-                        chars.next();
-                    } else {
-                        break;
-                    }
+            // Names, keywords and booleans. Names are those of PEP 3131, in
+            // NFKC form so that spellings Python takes as the same name match
+            c if c == '_' || c.is_xid_start() => {
+                while self.chars.peek().is_some_and(|c| c.is_xid_continue()) {
+                    self.chars.next();
                 }
 
-                tokens.push(Token::Identifier(identifier));
+                let text = self.chars.since(start);
+                match text {
+                    "True" => tokens.push(Token::Boolean(true)),
+                    "False" => tokens.push(Token::Boolean(false)),
+                    _ if KEYWORDS.contains(&text) => tokens.push(Token::Keyword(Cow::Borrowed(text))),
+                    _ => tokens.push(Token::Identifier(nfkc(text))),
+                }
             }
 
             // Handle additon and modulus
             '+' | '%' => {
-                let operator =  ch.to_string();
-                tokens.push(Token::Operator(operator));
-                chars.next();
+                self.chars.next();
+                tokens.push(Token::Operator(Cow::Borrowed(self.chars.since(start))));
             }
 
             //Multiplication and Power
             '*' => {
-                chars.next();
-                if let Some(&'*') = chars.peek() {
-                    chars.next();
-                    tokens.push(Token::Operator("**".to_string()));
+                self.chars.next();
+                if let Some(&'*') = self.chars.peek() {
+                    self.chars.next();
+                    tokens.push(Token::Operator(Cow::Borrowed("**")));
                 } else {
-                    tokens.push(Token::Operator("*".to_string()))
+                    tokens.push(Token::Operator(Cow::Borrowed("*")))
                 }
             }
 
             //Division and floor division
             '/' => {
-                chars.next();
-                if let Some(&'/') = chars.peek() {
-                    chars.next();
-                    tokens.push(Token::Operator("//".to_string()));
+                self.chars.next();
+                if let Some(&'/') = self.chars.peek() {
+                    self.chars.next();
+                    tokens.push(Token::Operator(Cow::Borrowed("//")));
                 } else {
-                    tokens.push(Token::Operator("/".to_string()))
+                    tokens.push(Token::Operator(Cow::Borrowed("/")))
                 }
             }
 
 
             // Handle Assign and comparison
             '=' => {
-                chars.next();
-                if let Some(&'=') = chars.peek() {
-                    chars.next();
-                    tokens.push(Token::Operator("==".to_string()));
+                self.chars.next();
+                if let Some(&'=') = self.chars.peek() {
+                    self.chars.next();
+                    tokens.push(Token::Operator(Cow::Borrowed("==")));
                 } else {
                     tokens.push(Token::Assign);
                 }
             }
 
             '!' => {
-                chars.next();
-                if let Some('=') = chars.peek() {
-                    chars.next();
-                    tokens.push(Token::Operator("!=".to_string()));
+                self.chars.next();
+                if let Some('=') = self.chars.peek() {
+                    self.chars.next();
+                    tokens.push(Token::Operator(Cow::Borrowed("!=")));
+                } else {
+                    let span = Span::new(start, self.chars.position);
+                    errors.push(TokenizeError::new(TokenizeErrorKind::InvalidCharacter('!'), span));
                 }
             }
 
             '<' => {
-                chars.next();
-                if let Some('=') = chars.peek() {
-                    chars.next();
-                    tokens.push(Token::Operator("<=".to_string()))
+                self.chars.next();
+                if let Some('=') = self.chars.peek() {
+                    self.chars.next();
+                    tokens.push(Token::Operator(Cow::Borrowed("<=")))
                 } else {
-                    tokens.push(Token::Operator("<".to_string()))
+                    tokens.push(Token::Operator(Cow::Borrowed("<")))
                 }
             }
 
             '>' => {
-                chars.next();
-                if let Some('=') = chars.peek() {
-                    chars.next();
-                    tokens.push(Token::Operator(">=".to_string()));
+                self.chars.next();
+                if let Some('=') = self.chars.peek() {
+                    self.chars.next();
+                    tokens.push(Token::Operator(Cow::Borrowed(">=")));
                 } else {
-                    tokens.push(Token::Operator(">".to_string()));
+                    tokens.push(Token::Operator(Cow::Borrowed(">")));
                 }
             }
 
             '&' => {
-                chars.next();
-                if let Some('&') = chars.peek() {
-                    chars.next();
-                    tokens.push(Token::Operator("&&".to_string()));
+                self.chars.next();
+                if let Some('&') = self.chars.peek() {
+                    self.chars.next();
+                    tokens.push(Token::Operator(Cow::Borrowed("&&")));
+                } else {
+                    tokens.push(Token::Operator(Cow::Borrowed("&")));
                 }
             }
 
             '|' => {
-                chars.next();
-                if let Some('|') = chars.peek() {
-                    chars.next();
-                    tokens.push(Token::Operator("||".to_string()));
+                self.chars.next();
+                if let Some('|') = self.chars.peek() {
+                    self.chars.next();
+                    tokens.push(Token::Operator(Cow::Borrowed("||")));
+                } else {
+                    tokens.push(Token::Operator(Cow::Borrowed("|")));
                 }
             }
             
             //Handle Parentheses
             '(' => {
                 tokens.push(Token::OpenParen);
-                chars.next();
+                self.paren_depth += 1;
+                self.chars.next();
             }
 
             ')' => {
                 tokens.push(Token::CloseParen);
-                chars.next();
+                self.paren_depth = self.paren_depth.saturating_sub(1);
+                self.chars.next();
+            }
+
+            '[' => {
+                tokens.push(Token::OpenBracket);
+                self.paren_depth += 1;
+                self.chars.next();
+            }
+
+            ']' => {
+                tokens.push(Token::CloseBracket);
+                self.paren_depth = self.paren_depth.saturating_sub(1);
+                self.chars.next();
+            }
+
+            '{' => {
+                tokens.push(Token::OpenBrace);
+                self.paren_depth += 1;
+                self.chars.next();
+            }
+
+            '}' => {
+                tokens.push(Token::CloseBrace);
+                self.paren_depth = self.paren_depth.saturating_sub(1);
+                self.chars.next();
+            }
+
+            '.' => {
+                tokens.push(Token::Dot);
+                self.chars.next();
+            }
+
+            ',' => {
+                tokens.push(Token::Comma);
+                self.chars.next();
+            }
+
+            ':' => {
+                tokens.push(Token::Colon);
+                self.chars.next();
+            }
+
+            '@' => {
+                tokens.push(Token::At);
+                self.chars.next();
             }
 
             _ => {
-                chars.next();
+                self.chars.next();
+                let span = Span::new(start, self.chars.position);
+                errors.push(TokenizeError::new(TokenizeErrorKind::InvalidCharacter(ch), span));
             }
         }
     }
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Result<SpannedToken<'a>, TokenizeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.fill();
+        self.pending.pop_front()
+    }
+}
 
-    tokens.push(Token::EOF);
+fn tokenize_with_errors(input: &str) -> (Vec<SpannedToken<'_>>, Vec<TokenizeError>) {
+    let mut tokens = Vec::new();
+    let mut errors = Vec::new();
 
-    tokens
-}
\ No newline at end of file
+    for item in Lexer::new(input) {
+        match item {
+            Ok(token) => tokens.push(token),
+            Err(e) => errors.push(e),
+        }
+    }
+
+    (tokens, errors)
+}
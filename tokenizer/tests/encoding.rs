@@ -0,0 +1,103 @@
+use std::borrow::Cow;
+
+use tokenizer::*;
+
+fn error_at(bytes: &[u8]) -> (TokenizeErrorKind, (usize, usize, usize), String) {
+    let error = decode_source(bytes).unwrap_err();
+    let start = error.span.start;
+    (error.kind.clone(), (start.line, start.column, start.offset), error.to_string())
+}
+
+#[test]
+fn utf8() {
+    // UTF-8 is the default and is not copied
+    let source = decode_source("x = \"café\"\n".as_bytes()).unwrap();
+    assert!(matches!(source, Cow::Borrowed("x = \"café\"\n")));
+
+    // A byte order mark is dropped, and positions count from after it
+    let source = decode_source(b"\xef\xbb\xbfx = 1\n").unwrap();
+    assert_eq!(source, "x = 1\n");
+    assert_eq!(tokenize_spanned(&source)[0].span.start.offset, 0);
+
+    let source = decode_source(b"\xef\xbb\xbf# coding: utf-8\nx = 1\n").unwrap();
+    assert_eq!(source, "# coding: utf-8\nx = 1\n");
+}
+
+#[test]
+fn coding_declarations() {
+    let cases: [&[u8]; 6] = [
+        b"# -*- coding: latin-1 -*-\nx = \"caf\xe9\"\n",
+        b"#!/usr/bin/env python\n# vim: set fileencoding=iso-8859-1 :\nx = \"caf\xe9\"\n",
+        b"# coding=latin_1\nx = \"caf\xe9\"\n",
+        b"\n#coding:L1\nx = \"caf\xe9\"\n",
+        b"# This Python file uses the following encoding: ISO-8859-1-unix\nx = \"caf\xe9\"\n",
+        b"# -*- coding: Latin-1 -*-\r\nx = \"caf\xe9\"\r\n",
+    ];
+
+    for bytes in cases {
+        let source = decode_source(bytes).unwrap();
+        assert!(source.contains("x = \"café\""), "for {:?}", source);
+        assert!(matches!(source, Cow::Owned(_)));
+    }
+
+    // Plain ASCII in a declared encoding is still borrowed
+    assert!(matches!(decode_source(b"# coding: latin-1\nx = 1\n").unwrap(), Cow::Borrowed(_)));
+    assert_eq!(decode_source(b"# coding: ascii\nx = 1\n").unwrap(), "# coding: ascii\nx = 1\n");
+}
+
+#[test]
+fn ignored_declarations() {
+    // Only comments on the first two lines count, and the second only after
+    // a comment or blank line
+    let cases: [&[u8]; 4] = [
+        b"x = 1  # coding: latin-1\n",
+        b"x = 1\n# coding: latin-1\n",
+        b"#!/usr/bin/env python\n\n# coding: latin-1\n",
+        b"# coding:\n",
+    ];
+
+    for bytes in cases {
+        assert_eq!(decode_source(bytes).unwrap().as_bytes(), bytes);
+    }
+    assert!(decode_source(b"x = 1\n# coding: latin-1\ny = \"\xe9\"\n").is_err());
+}
+
+#[test]
+fn errors() {
+    let cases: [(&[u8], _, _, _); 5] = [
+        (
+            b"x = 1\ny = \"\xff\"\n",
+            TokenizeErrorKind::InvalidEncoding("utf-8".into(), 0xff),
+            (2, 5, 11),
+            "(unicode error) 'utf-8' codec can't decode byte 0xff",
+        ),
+        (
+            b"\xef\xbb\xbfx = \"\xe9\"",
+            TokenizeErrorKind::InvalidEncoding("utf-8".into(), 0xe9),
+            (1, 5, 5),
+            "(unicode error) 'utf-8' codec can't decode byte 0xe9",
+        ),
+        (
+            b"# coding: ascii\nx = \"caf\xc3\xa9\"\n",
+            TokenizeErrorKind::InvalidEncoding("ascii".into(), 0xc3),
+            (2, 8, 24),
+            "(unicode error) 'ascii' codec can't decode byte 0xc3",
+        ),
+        (
+            b"#!/usr/bin/env python\n# -*- coding: klingon -*-\n",
+            TokenizeErrorKind::UnknownEncoding("klingon".into()),
+            (2, 14, 36),
+            "unknown encoding: klingon",
+        ),
+        (
+            b"\xef\xbb\xbf# coding: latin-1\n",
+            TokenizeErrorKind::EncodingWithBom("latin-1".into()),
+            (1, 10, 10),
+            "encoding problem: latin-1 with BOM",
+        ),
+    ];
+
+    for (bytes, kind, position, message) in cases {
+        assert_eq!(error_at(bytes), (kind, position, message.to_string()));
+    }
+}
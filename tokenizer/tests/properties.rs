@@ -0,0 +1,75 @@
+use proptest::prelude::*;
+use tokenizer::*;
+
+// Source that looks enough like Python to reach most of the tokenizer, with
+// the odd piece it rejects
+fn python_like() -> impl Strategy<Value = String> {
+    let piece = prop_oneof![
+        10 => "[a-zA-Z_][a-zA-Z_0-9]{0,3}|-?[0-9]{1,3}|\"[a-z ]{0,3}\"|#[a-z ]{0,4}",
+        10 => "[-+*/%<>=!&|@.,:()\\[\\]{}]{1,2}",
+        10 => "  |\t|\n|\r\n|\\\\\n|\x0c|é",
+        1 => "[0-9]{20,25}|\"[a-z]{0,3}|\\$|\\?",
+    ];
+    prop::collection::vec(piece, 0..40).prop_map(|pieces| pieces.concat())
+}
+
+// Line and column of a byte offset, as the tokenizer counts them
+fn position_of(input: &str, offset: usize) -> Position {
+    let before = &input[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position { line: before.matches('\n').count() + 1, column: offset - line_start, offset }
+}
+
+fn check_spans(input: &str, tokens: &[SpannedToken]) -> Result<(), TestCaseError> {
+    let mut previous_end = 0;
+    for token in tokens {
+        let Span { start, end } = token.span;
+        prop_assert!(previous_end <= start.offset && start.offset <= end.offset && end.offset <= input.len(), "{:?} out of order", token);
+        prop_assert!(input.is_char_boundary(start.offset) && input.is_char_boundary(end.offset), "{:?} splits a character", token);
+        prop_assert_eq!(start, position_of(input, start.offset));
+        prop_assert_eq!(end, position_of(input, end.offset));
+        previous_end = end.offset;
+    }
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn never_panics(input in any::<String>()) {
+        let tokens = tokenize_spanned(&input);
+        prop_assert_eq!(tokens.last().map(|t| &t.token), Some(&Token::EOF));
+        let _ = try_tokenize(&input);
+        let _ = tokenize_with_trivia(&input);
+    }
+
+    #[test]
+    fn spans_are_ordered(input in python_like()) {
+        check_spans(&input, &tokenize_spanned(&input))?;
+    }
+
+    #[test]
+    fn trivia_tiles_the_source(input in python_like()) {
+        let Ok(tokens) = tokenize_with_trivia(&input) else { return Ok(()) };
+        check_spans(&input, &tokens)?;
+
+        // Every byte belongs to exactly one token
+        let mut end = 0;
+        for token in &tokens {
+            prop_assert_eq!(token.span.start.offset, end, "gap or overlap before {:?}", token);
+            end = token.span.end.offset;
+        }
+        prop_assert_eq!(end, input.len());
+
+        let significant: Vec<_> = tokens.into_iter().filter(|t| !t.token.is_trivia()).collect();
+        prop_assert_eq!(significant, try_tokenize(&input).unwrap());
+    }
+
+    #[test]
+    fn errors_match_recovery(input in python_like()) {
+        // try_tokenize fails exactly when tokenize_spanned has skipped something
+        match try_tokenize(&input) {
+            Ok(tokens) => prop_assert_eq!(tokens, tokenize_spanned(&input)),
+            Err(e) => prop_assert!(e.span.end.offset <= input.len()),
+        }
+    }
+}
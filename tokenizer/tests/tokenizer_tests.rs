@@ -1,3 +1,5 @@
+use std::borrow::Cow;
+
 use tokenizer::*;
 
 #[test]
@@ -5,15 +7,15 @@ fn test_tokenizer() {
     let input = "x = 5 + 3 * (2 - 8)";
 
     let expected_tokens = vec![
-        Token::Identifier("x".to_string()),
+        Token::Identifier("x".into()),
         Token::Assign,
         Token::Number(5),
-        Token::Operator("+".to_string()),
+        Token::Operator("+".into()),
         Token::Number(3),
-        Token::Operator("*".to_string()),
+        Token::Operator("*".into()),
         Token::OpenParen,
         Token::Number(2),
-        Token::Operator("-".to_string()),
+        Token::Operator("-".into()),
         Token::Number(8),
         Token::CloseParen,
         Token::EOF,
@@ -29,8 +31,8 @@ fn comparison() {
     let input = "y == 1";
 
     let  expected_tokens = vec![
-        Token::Identifier("y".to_string()),
-        Token::Equal,
+        Token::Identifier("y".into()),
+        Token::Operator("==".into()),
         Token::Number(1),
         Token::EOF
     ];
@@ -38,4 +40,250 @@ fn comparison() {
     let tokens = tokenize(input);
     
     assert_eq!(tokens, expected_tokens);
-}
\ No newline at end of file
+}
+#[test]
+fn keywords_and_newlines() {
+    let input = "from . import (a,\n b) # comment\n\nx = 1";
+
+    let expected_tokens = vec![
+        Token::Keyword("from".into()),
+        Token::Dot,
+        Token::Keyword("import".into()),
+        Token::OpenParen,
+        Token::Identifier("a".into()),
+        Token::Comma,
+        Token::Identifier("b".into()),
+        Token::CloseParen,
+        Token::Newline,
+        Token::Identifier("x".into()),
+        Token::Assign,
+        Token::Number(1),
+        Token::EOF,
+    ];
+
+    let tokens = tokenize(input);
+
+    assert_eq!(tokens, expected_tokens);
+}
+
+#[test]
+fn indentation() {
+    let input = "try:\n    x = 1\n\n    # comment\nfinally:\n  y";
+
+    let expected_tokens = vec![
+        Token::Keyword("try".into()),
+        Token::Colon,
+        Token::Newline,
+        Token::Indent,
+        Token::Identifier("x".into()),
+        Token::Assign,
+        Token::Number(1),
+        Token::Newline,
+        Token::Dedent,
+        Token::Keyword("finally".into()),
+        Token::Colon,
+        Token::Newline,
+        Token::Indent,
+        Token::Identifier("y".into()),
+        Token::Dedent,
+        Token::EOF,
+    ];
+
+    let tokens = tokenize(input);
+
+    assert_eq!(tokens, expected_tokens);
+}
+
+#[test]
+fn arrow_and_negative_numbers() {
+    let input = "-> - -1";
+
+    let expected_tokens = vec![
+        Token::Arrow,
+        Token::Operator("-".into()),
+        Token::Number(-1),
+        Token::EOF,
+    ];
+
+    assert_eq!(tokenize(input), expected_tokens);
+}
+
+#[test]
+fn spans() {
+    // Columns count UTF-8 bytes
+    let input = "if x:\n    y = \"ü\"\n";
+
+    let spans: Vec<_> = tokenize_spanned(input)
+        .into_iter()
+        .map(|t| (t.token, (t.span.start.line, t.span.start.column), (t.span.end.line, t.span.end.column)))
+        .collect();
+
+    assert_eq!(spans, vec![
+        (Token::Keyword("if".into()), (1, 0), (1, 2)),
+        (Token::Identifier("x".into()), (1, 3), (1, 4)),
+        (Token::Colon, (1, 4), (1, 5)),
+        (Token::Newline, (1, 5), (2, 0)),
+        (Token::Indent, (2, 0), (2, 4)),
+        (Token::Identifier("y".into()), (2, 4), (2, 5)),
+        (Token::Assign, (2, 6), (2, 7)),
+        (Token::String("ü".into()), (2, 8), (2, 12)),
+        (Token::Newline, (2, 12), (3, 0)),
+        (Token::Dedent, (3, 0), (3, 0)),
+        (Token::EOF, (3, 0), (3, 0)),
+    ]);
+}
+
+#[test]
+fn tokenize_errors() {
+    let cases = [
+        ("x = \"abc\ny = 1", TokenizeErrorKind::UnterminatedString, (1, 4), "unterminated string literal (detected at line 1)"),
+        ("x = 1 $ 2", TokenizeErrorKind::InvalidCharacter('$'), (1, 6), "invalid character '$' (U+0024)"),
+        ("x = !y", TokenizeErrorKind::InvalidCharacter('!'), (1, 4), "invalid character '!' (U+0021)"),
+        ("\nx = 99999999999999999999", TokenizeErrorKind::IntegerTooLarge, (2, 4), "integer literal is too large"),
+    ];
+
+    for (input, kind, (line, column), message) in cases {
+        let error = try_tokenize(input).unwrap_err();
+        assert_eq!(error.kind, kind);
+        assert_eq!((error.span.start.line, error.span.start.column), (line, column));
+        assert_eq!(error.to_string(), message);
+    }
+
+    assert_eq!(try_tokenize("x = 1").unwrap(), tokenize_spanned("x = 1"));
+
+    // An integer too large is kept as an Invalid token where it was
+    let tokens = tokenize_spanned("x = -99999999999999999999 + 1");
+    assert_eq!(tokens[2].token, Token::Invalid("-99999999999999999999".into()));
+    assert_eq!((tokens[2].span.start.column, tokens[2].span.end.column), (4, 25));
+    assert_eq!(tokens[3].token, Token::Operator("+".into()));
+}
+
+#[test]
+fn lexer() {
+    let input = "if x:\n    y = $1\n";
+    let mut lexer = Lexer::new(input);
+
+    assert_eq!(lexer.peek().unwrap().as_ref().unwrap().token, Token::Keyword("if".into()));
+    assert_eq!(lexer.next().unwrap().unwrap().token, Token::Keyword("if".into()));
+    assert_eq!(lexer.peek().unwrap().as_ref().unwrap().token, Token::Identifier("x".into()));
+
+    // Errors come out where they occur and lexing carries on past them
+    let rest: Vec<_> = lexer.map(|item| item.map(|t| t.token).map_err(|e| e.kind)).collect();
+    assert_eq!(rest, vec![
+        Ok(Token::Identifier("x".into())),
+        Ok(Token::Colon),
+        Ok(Token::Newline),
+        Ok(Token::Indent),
+        Ok(Token::Identifier("y".into())),
+        Ok(Token::Assign),
+        Err(TokenizeErrorKind::InvalidCharacter('$')),
+        Ok(Token::Number(1)),
+        Ok(Token::Newline),
+        Ok(Token::Dedent),
+        Ok(Token::EOF),
+    ]);
+
+    let tokens: Vec<_> = Lexer::new(input).filter_map(Result::ok).collect();
+    assert_eq!(tokens, tokenize_spanned(input));
+
+    let mut lexer = Lexer::new("");
+    assert_eq!(lexer.next().unwrap().unwrap().token, Token::EOF);
+    assert!(lexer.next().is_none());
+}
+
+#[test]
+fn lexer_starting_at() {
+    let input = "x = 1
+if y:
+    z
+w
+";
+    let tokens = tokenize_spanned(input);
+
+    // Starting at a line gives the same tokens and spans as lexing the whole input
+    let start = tokens.iter().find(|t| t.token == Token::Keyword("if".into())).unwrap().span.start;
+    let rest: Vec<_> = Lexer::starting_at(input, start).filter_map(Result::ok).collect();
+    assert_eq!(rest[0].span.start, Position { line: 2, column: 0, offset: 6 });
+    assert_eq!(rest, tokens[4..]);
+}
+
+#[test]
+fn trivia() {
+    let input = "x = 1  # one\n\n\tif y: \\\n  pass\n# end";
+    let tokens = tokenize_with_trivia(input).unwrap();
+
+    let text: String = tokens.iter().map(|t| &input[t.span.start.offset..t.span.end.offset]).collect();
+    assert_eq!(text, input);
+
+    let significant: Vec<_> = tokens.iter().filter(|t| !t.token.is_trivia()).cloned().collect();
+    assert_eq!(significant, try_tokenize(input).unwrap());
+
+    assert!(tokens.contains(&SpannedToken {
+        token: Token::Comment("# one".into()),
+        span: Span::new(Position { line: 1, column: 7, offset: 7 }, Position { line: 1, column: 12, offset: 12 }),
+    }));
+    assert_eq!(tokens.iter().filter(|t| matches!(t.token, Token::Comment(_))).count(), 2);
+}
+
+#[test]
+fn borrowed_text() {
+    let input = "name = \"text\" + 1  # note";
+    let tokens = tokenize_with_trivia(input).unwrap();
+
+    // Token text points into the input rather than being copied
+    let source = input.as_bytes().as_ptr_range();
+    for token in &tokens {
+        if let Token::Identifier(text) | Token::Operator(text) | Token::String(text) | Token::Whitespace(text) | Token::Comment(text) = &token.token {
+            assert!(matches!(text, Cow::Borrowed(_)), "{:?}", token);
+            assert!(source.contains(&text.as_ptr()), "{:?}", token);
+        }
+    }
+    assert_eq!(tokens[4].token, Token::String("text".into()));
+
+    let owned: Token<'static> = tokens[0].token.clone().into_owned();
+    assert_eq!(owned, Token::Identifier("name".into()));
+}
+
+#[test]
+fn unicode_names() {
+    let tokens = tokenize("café = π + ﬁle + ℌ_2 + True_")
+        .into_iter()
+        .filter(|t| matches!(t, Token::Identifier(_)))
+        .collect::<Vec<_>>();
+
+    // Names are compared in NFKC form, so the ligature is `fi` and the
+    // blackletter H a plain one
+    assert_eq!(tokens, vec![
+        Token::Identifier("café".into()),
+        Token::Identifier("π".into()),
+        Token::Identifier("file".into()),
+        Token::Identifier("H_2".into()),
+        Token::Identifier("True_".into()),
+    ]);
+
+    // Spans still cover the name as written, and only names that change are
+    // copied
+    let tokens = tokenize_spanned("ﬁle = café");
+    assert_eq!((tokens[0].span.end.column, tokens[1].span.start.column), (5, 6));
+    assert!(matches!(&tokens[0].token, Token::Identifier(Cow::Owned(_))));
+    assert!(matches!(&tokens[2].token, Token::Identifier(Cow::Borrowed(_))));
+
+    // Combining marks only continue a name, and symbols are not names at all
+    for (input, ch) in [("\u{301}a = 1", '\u{301}'), ("x = 5€", '€'), ("😀 = 1", '😀')] {
+        let error = try_tokenize(input).unwrap_err();
+        assert_eq!(error.kind, TokenizeErrorKind::InvalidCharacter(ch), "for {:?}", input);
+    }
+}
+
+#[test]
+fn json() {
+    let tokens = try_tokenize("def f(x):\n    return \"hi\" -1\n").unwrap();
+
+    let text = serde_json::to_string(&tokens).unwrap();
+    let back: Vec<SpannedToken> = serde_json::from_str(&text).unwrap();
+    assert_eq!(back, tokens);
+
+    let value = serde_json::to_value(&tokens[0]).unwrap();
+    assert_eq!(value["token"], serde_json::json!({ "Keyword": "def" }));
+    assert_eq!(value["span"]["end"], serde_json::json!({ "line": 1, "column": 3, "offset": 3 }));
+}